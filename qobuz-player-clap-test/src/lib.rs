@@ -0,0 +1,520 @@
+//! Minimal CLAP gain plugin used to test plugin hosting. It has a single
+//! "Gain" parameter and stores it as its state.
+
+use std::{
+    ffi::{CStr, c_char, c_void},
+    ptr,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+pub const PLUGIN_ID: &str = "com.qobuz-player.test-gain";
+pub const GAIN_PARAM_ID: u32 = 0;
+
+const PLUGIN_ID_C: &CStr = c"com.qobuz-player.test-gain";
+const PLUGIN_FACTORY_ID: &CStr = c"clap.plugin-factory";
+const EXT_PARAMS: &CStr = c"clap.params";
+const EXT_STATE: &CStr = c"clap.state";
+const EXT_AUDIO_PORTS: &CStr = c"clap.audio-ports";
+
+const EVENT_PARAM_VALUE: u16 = 5;
+const PROCESS_CONTINUE: i32 = 1;
+const PARAM_IS_AUTOMATABLE: u32 = 1 << 5;
+const AUDIO_PORT_IS_MAIN: u32 = 1;
+const INVALID_ID: u32 = u32::MAX;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Version {
+    major: u32,
+    minor: u32,
+    revision: u32,
+}
+
+const CLAP_VERSION: Version = Version {
+    major: 1,
+    minor: 2,
+    revision: 2,
+};
+
+#[repr(C)]
+pub struct Entry {
+    clap_version: Version,
+    init: unsafe extern "C" fn(*const c_char) -> bool,
+    deinit: unsafe extern "C" fn(),
+    get_factory: unsafe extern "C" fn(*const c_char) -> *const c_void,
+}
+
+#[repr(C)]
+struct Factory {
+    get_plugin_count: unsafe extern "C" fn(*const Factory) -> u32,
+    get_plugin_descriptor: unsafe extern "C" fn(*const Factory, u32) -> *const Descriptor,
+    create_plugin:
+        unsafe extern "C" fn(*const Factory, *const c_void, *const c_char) -> *const Plugin,
+}
+
+#[repr(C)]
+struct Descriptor {
+    clap_version: Version,
+    id: *const c_char,
+    name: *const c_char,
+    vendor: *const c_char,
+    url: *const c_char,
+    manual_url: *const c_char,
+    support_url: *const c_char,
+    version: *const c_char,
+    description: *const c_char,
+    features: *const *const c_char,
+}
+
+#[repr(C)]
+struct Plugin {
+    desc: *const Descriptor,
+    plugin_data: *mut c_void,
+    init: unsafe extern "C" fn(*const Plugin) -> bool,
+    destroy: unsafe extern "C" fn(*const Plugin),
+    activate: unsafe extern "C" fn(*const Plugin, f64, u32, u32) -> bool,
+    deactivate: unsafe extern "C" fn(*const Plugin),
+    start_processing: unsafe extern "C" fn(*const Plugin) -> bool,
+    stop_processing: unsafe extern "C" fn(*const Plugin),
+    reset: unsafe extern "C" fn(*const Plugin),
+    process: unsafe extern "C" fn(*const Plugin, *const Process) -> i32,
+    get_extension: unsafe extern "C" fn(*const Plugin, *const c_char) -> *const c_void,
+    on_main_thread: unsafe extern "C" fn(*const Plugin),
+}
+
+#[repr(C)]
+struct AudioBuffer {
+    data32: *mut *mut f32,
+    data64: *mut *mut f64,
+    channel_count: u32,
+    latency: u32,
+    constant_mask: u64,
+}
+
+#[repr(C)]
+struct Process {
+    steady_time: i64,
+    frames_count: u32,
+    transport: *const c_void,
+    audio_inputs: *const AudioBuffer,
+    audio_outputs: *mut AudioBuffer,
+    audio_inputs_count: u32,
+    audio_outputs_count: u32,
+    in_events: *const InputEvents,
+    out_events: *const c_void,
+}
+
+#[repr(C)]
+struct EventHeader {
+    size: u32,
+    time: u32,
+    space_id: u16,
+    event_type: u16,
+    flags: u32,
+}
+
+#[repr(C)]
+struct EventParamValue {
+    header: EventHeader,
+    param_id: u32,
+    cookie: *mut c_void,
+    note_id: i32,
+    port_index: i16,
+    channel: i16,
+    key: i16,
+    value: f64,
+}
+
+#[repr(C)]
+struct InputEvents {
+    ctx: *mut c_void,
+    size: unsafe extern "C" fn(*const InputEvents) -> u32,
+    get: unsafe extern "C" fn(*const InputEvents, u32) -> *const EventHeader,
+}
+
+#[repr(C)]
+struct ParamInfo {
+    id: u32,
+    flags: u32,
+    cookie: *mut c_void,
+    name: [c_char; 256],
+    module: [c_char; 1024],
+    min_value: f64,
+    max_value: f64,
+    default_value: f64,
+}
+
+#[repr(C)]
+struct Params {
+    count: unsafe extern "C" fn(*const Plugin) -> u32,
+    get_info: unsafe extern "C" fn(*const Plugin, u32, *mut ParamInfo) -> bool,
+    get_value: unsafe extern "C" fn(*const Plugin, u32, *mut f64) -> bool,
+    value_to_text: unsafe extern "C" fn(*const Plugin, u32, f64, *mut c_char, u32) -> bool,
+    text_to_value: unsafe extern "C" fn(*const Plugin, u32, *const c_char, *mut f64) -> bool,
+    flush: unsafe extern "C" fn(*const Plugin, *const InputEvents, *const c_void),
+}
+
+#[repr(C)]
+struct OutputStream {
+    ctx: *mut c_void,
+    write: unsafe extern "C" fn(*const OutputStream, *const c_void, u64) -> i64,
+}
+
+#[repr(C)]
+struct InputStream {
+    ctx: *mut c_void,
+    read: unsafe extern "C" fn(*const InputStream, *mut c_void, u64) -> i64,
+}
+
+#[repr(C)]
+struct AudioPortInfo {
+    id: u32,
+    name: [c_char; 256],
+    flags: u32,
+    channel_count: u32,
+    port_type: *const c_char,
+    in_place_pair: u32,
+}
+
+#[repr(C)]
+struct AudioPorts {
+    count: unsafe extern "C" fn(*const Plugin, bool) -> u32,
+    get: unsafe extern "C" fn(*const Plugin, u32, bool, *mut AudioPortInfo) -> bool,
+}
+
+#[repr(C)]
+struct State {
+    save: unsafe extern "C" fn(*const Plugin, *const OutputStream) -> bool,
+    load: unsafe extern "C" fn(*const Plugin, *const InputStream) -> bool,
+}
+
+struct Static<T>(T);
+
+// The statics only hold pointers to other immutable statics.
+unsafe impl<T> Sync for Static<T> {}
+
+static FEATURES: Static<[*const c_char; 2]> = Static([c"audio-effect".as_ptr(), ptr::null()]);
+
+static DESCRIPTOR: Static<Descriptor> = Static(Descriptor {
+    clap_version: CLAP_VERSION,
+    id: PLUGIN_ID_C.as_ptr(),
+    name: c"Test Gain".as_ptr(),
+    vendor: c"qobuz-player".as_ptr(),
+    url: c"".as_ptr(),
+    manual_url: c"".as_ptr(),
+    support_url: c"".as_ptr(),
+    version: c"1.0.0".as_ptr(),
+    description: c"Gain plugin for testing".as_ptr(),
+    features: FEATURES.0.as_ptr(),
+});
+
+static FACTORY: Factory = Factory {
+    get_plugin_count,
+    get_plugin_descriptor,
+    create_plugin,
+};
+
+static PARAMS: Params = Params {
+    count: params_count,
+    get_info: params_get_info,
+    get_value: params_get_value,
+    value_to_text: params_value_to_text,
+    text_to_value: params_text_to_value,
+    flush: params_flush,
+};
+
+static AUDIO_PORTS: AudioPorts = AudioPorts {
+    count: audio_ports_count,
+    get: audio_ports_get,
+};
+
+static STATE: State = State {
+    save: state_save,
+    load: state_load,
+};
+
+#[unsafe(no_mangle)]
+#[allow(non_upper_case_globals)]
+pub static clap_entry: Entry = Entry {
+    clap_version: CLAP_VERSION,
+    init: entry_init,
+    deinit: entry_deinit,
+    get_factory: entry_get_factory,
+};
+
+/// Gain stored as f64 bits so the audio and main threads can share it.
+struct Gain {
+    gain: AtomicU64,
+}
+
+impl Gain {
+    fn get(&self) -> f64 {
+        f64::from_bits(self.gain.load(Ordering::Relaxed))
+    }
+
+    fn set(&self, value: f64) {
+        self.gain
+            .store(value.clamp(0.0, 2.0).to_bits(), Ordering::Relaxed);
+    }
+
+    fn apply_events(&self, events: *const InputEvents) {
+        if events.is_null() {
+            return;
+        }
+
+        let events = unsafe { &*events };
+        for index in 0..unsafe { (events.size)(events) } {
+            let header = unsafe { (events.get)(events, index) };
+            if header.is_null() || unsafe { (*header).event_type } != EVENT_PARAM_VALUE {
+                continue;
+            }
+
+            let event = unsafe { &*(header as *const EventParamValue) };
+            if event.param_id == GAIN_PARAM_ID {
+                self.set(event.value);
+            }
+        }
+    }
+}
+
+unsafe fn gain<'a>(plugin: *const Plugin) -> &'a Gain {
+    unsafe { &*((*plugin).plugin_data as *const Gain) }
+}
+
+unsafe extern "C" fn entry_init(_plugin_path: *const c_char) -> bool {
+    true
+}
+
+unsafe extern "C" fn entry_deinit() {}
+
+unsafe extern "C" fn entry_get_factory(factory_id: *const c_char) -> *const c_void {
+    if unsafe { CStr::from_ptr(factory_id) } == PLUGIN_FACTORY_ID {
+        &FACTORY as *const Factory as *const c_void
+    } else {
+        ptr::null()
+    }
+}
+
+unsafe extern "C" fn get_plugin_count(_factory: *const Factory) -> u32 {
+    1
+}
+
+unsafe extern "C" fn get_plugin_descriptor(
+    _factory: *const Factory,
+    index: u32,
+) -> *const Descriptor {
+    if index == 0 {
+        &DESCRIPTOR.0
+    } else {
+        ptr::null()
+    }
+}
+
+unsafe extern "C" fn create_plugin(
+    _factory: *const Factory,
+    _host: *const c_void,
+    plugin_id: *const c_char,
+) -> *const Plugin {
+    if unsafe { CStr::from_ptr(plugin_id) } != PLUGIN_ID_C {
+        return ptr::null();
+    }
+
+    let data = Box::into_raw(Box::new(Gain {
+        gain: AtomicU64::new(1f64.to_bits()),
+    }));
+    Box::into_raw(Box::new(Plugin {
+        desc: &DESCRIPTOR.0,
+        plugin_data: data as *mut c_void,
+        init: plugin_init,
+        destroy: plugin_destroy,
+        activate: plugin_activate,
+        deactivate: plugin_noop,
+        start_processing: plugin_start_processing,
+        stop_processing: plugin_noop,
+        reset: plugin_noop,
+        process: plugin_process,
+        get_extension: plugin_get_extension,
+        on_main_thread: plugin_noop,
+    }))
+}
+
+unsafe extern "C" fn plugin_init(_plugin: *const Plugin) -> bool {
+    true
+}
+
+unsafe extern "C" fn plugin_destroy(plugin: *const Plugin) {
+    unsafe {
+        let plugin = Box::from_raw(plugin as *mut Plugin);
+        drop(Box::from_raw(plugin.plugin_data as *mut Gain));
+    }
+}
+
+unsafe extern "C" fn plugin_activate(
+    _plugin: *const Plugin,
+    _rate: f64,
+    _min: u32,
+    _max: u32,
+) -> bool {
+    true
+}
+
+unsafe extern "C" fn plugin_start_processing(_plugin: *const Plugin) -> bool {
+    true
+}
+
+unsafe extern "C" fn plugin_noop(_plugin: *const Plugin) {}
+
+unsafe extern "C" fn plugin_process(plugin: *const Plugin, process: *const Process) -> i32 {
+    let gain = unsafe { gain(plugin) };
+    let process = unsafe { &*process };
+    gain.apply_events(process.in_events);
+
+    let value = gain.get() as f32;
+    let frames = process.frames_count as usize;
+    let input = unsafe { &*process.audio_inputs };
+    let output = unsafe { &*process.audio_outputs };
+
+    for channel in 0..input.channel_count.min(output.channel_count) as usize {
+        let (input, output) = unsafe {
+            (
+                std::slice::from_raw_parts(*input.data32.add(channel), frames),
+                std::slice::from_raw_parts_mut(*output.data32.add(channel), frames),
+            )
+        };
+        for (output, input) in output.iter_mut().zip(input) {
+            *output = input * value;
+        }
+    }
+
+    PROCESS_CONTINUE
+}
+
+unsafe extern "C" fn plugin_get_extension(
+    _plugin: *const Plugin,
+    id: *const c_char,
+) -> *const c_void {
+    let id = unsafe { CStr::from_ptr(id) };
+    if id == EXT_PARAMS {
+        &PARAMS as *const Params as *const c_void
+    } else if id == EXT_STATE {
+        &STATE as *const State as *const c_void
+    } else if id == EXT_AUDIO_PORTS {
+        &AUDIO_PORTS as *const AudioPorts as *const c_void
+    } else {
+        ptr::null()
+    }
+}
+
+unsafe extern "C" fn audio_ports_count(_plugin: *const Plugin, _is_input: bool) -> u32 {
+    1
+}
+
+unsafe extern "C" fn audio_ports_get(
+    _plugin: *const Plugin,
+    index: u32,
+    _is_input: bool,
+    info: *mut AudioPortInfo,
+) -> bool {
+    if index != 0 {
+        return false;
+    }
+
+    let info = unsafe { &mut *info };
+    info.id = 0;
+    info.name = [0; 256];
+    info.flags = AUDIO_PORT_IS_MAIN;
+    info.channel_count = 2;
+    info.port_type = c"stereo".as_ptr();
+    info.in_place_pair = INVALID_ID;
+    true
+}
+
+unsafe extern "C" fn params_count(_plugin: *const Plugin) -> u32 {
+    1
+}
+
+unsafe extern "C" fn params_get_info(
+    _plugin: *const Plugin,
+    index: u32,
+    info: *mut ParamInfo,
+) -> bool {
+    if index != 0 {
+        return false;
+    }
+
+    let info = unsafe { &mut *info };
+    info.id = GAIN_PARAM_ID;
+    info.flags = PARAM_IS_AUTOMATABLE;
+    info.cookie = ptr::null_mut();
+    info.name = [0; 256];
+    for (target, byte) in info.name.iter_mut().zip(b"Gain") {
+        *target = *byte as c_char;
+    }
+    info.module = [0; 1024];
+    info.min_value = 0.0;
+    info.max_value = 2.0;
+    info.default_value = 1.0;
+    true
+}
+
+unsafe extern "C" fn params_get_value(
+    plugin: *const Plugin,
+    param_id: u32,
+    value: *mut f64,
+) -> bool {
+    if param_id != GAIN_PARAM_ID {
+        return false;
+    }
+    unsafe { *value = gain(plugin).get() };
+    true
+}
+
+unsafe extern "C" fn params_value_to_text(
+    _plugin: *const Plugin,
+    _param_id: u32,
+    _value: f64,
+    _buffer: *mut c_char,
+    _capacity: u32,
+) -> bool {
+    false
+}
+
+unsafe extern "C" fn params_text_to_value(
+    _plugin: *const Plugin,
+    _param_id: u32,
+    _text: *const c_char,
+    _value: *mut f64,
+) -> bool {
+    false
+}
+
+unsafe extern "C" fn params_flush(
+    plugin: *const Plugin,
+    in_events: *const InputEvents,
+    _out: *const c_void,
+) {
+    unsafe { gain(plugin) }.apply_events(in_events);
+}
+
+unsafe extern "C" fn state_save(plugin: *const Plugin, stream: *const OutputStream) -> bool {
+    let bytes = unsafe { gain(plugin) }.get().to_le_bytes();
+    let written =
+        unsafe { ((*stream).write)(stream, bytes.as_ptr() as *const c_void, bytes.len() as u64) };
+    written == bytes.len() as i64
+}
+
+unsafe extern "C" fn state_load(plugin: *const Plugin, stream: *const InputStream) -> bool {
+    let mut bytes = [0u8; 8];
+    let read = unsafe {
+        ((*stream).read)(
+            stream,
+            bytes.as_mut_ptr() as *mut c_void,
+            bytes.len() as u64,
+        )
+    };
+    if read != bytes.len() as i64 {
+        return false;
+    }
+    unsafe { gain(plugin) }.set(f64::from_le_bytes(bytes));
+    true
+}
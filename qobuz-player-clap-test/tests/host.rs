@@ -0,0 +1,97 @@
+#![cfg(target_os = "linux")]
+
+use std::{fs, path::PathBuf};
+
+use qobuz_player_clap_test::{GAIN_PARAM_ID, PLUGIN_ID};
+use qobuz_player_controls::clap::{
+    ClapChain, ClapChainEntry, ClapChainSource, ClapPlugin, scan_plugins,
+};
+use rodio::buffer::SamplesBuffer;
+
+fn plugin_library() -> PathBuf {
+    let executable = std::env::current_exe().unwrap();
+    executable
+        .ancestors()
+        .skip(1)
+        .take(2)
+        .map(|directory| directory.join("libqobuz_player_clap_test.so"))
+        .find(|path| path.exists())
+        .expect("test plugin is built next to the test binary")
+}
+
+#[test]
+fn scans_plugin_directory() {
+    let directory = std::env::temp_dir().join(format!("qobuz-player-clap-{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    fs::copy(plugin_library(), directory.join("gain.clap")).unwrap();
+    fs::write(directory.join("readme.txt"), "not a plugin").unwrap();
+
+    let plugins = scan_plugins(&directory);
+    fs::remove_dir_all(&directory).unwrap();
+
+    assert_eq!(plugins.len(), 1);
+    assert_eq!(plugins[0].id, PLUGIN_ID);
+    assert_eq!(plugins[0].name, "Test Gain");
+}
+
+#[test]
+fn exposes_parameters_and_restores_state() {
+    let mut plugin = ClapPlugin::load(&plugin_library(), PLUGIN_ID).unwrap();
+    let params = plugin.params();
+    assert_eq!(params.len(), 1);
+    assert_eq!(params[0].name, "Gain");
+    assert_eq!(params[0].value, 1.0);
+
+    plugin.set_param(GAIN_PARAM_ID, 0.25);
+    assert_eq!(plugin.params()[0].value, 0.25);
+    let state = plugin.save_state().unwrap();
+
+    let mut restored = ClapPlugin::load(&plugin_library(), PLUGIN_ID).unwrap();
+    assert!(restored.load_state(&state));
+    assert_eq!(restored.params()[0].value, 0.25);
+}
+
+#[test]
+fn processes_audio_through_chain() {
+    let mut plugin = ClapPlugin::load(&plugin_library(), PLUGIN_ID).unwrap();
+    plugin.set_param(GAIN_PARAM_ID, 0.5);
+
+    let chain = ClapChain::default();
+    chain.set_entries(vec![ClapChainEntry::new(0, plugin)]);
+
+    let samples = (0..1500)
+        .map(|index| (index % 7) as f32 / 10.0)
+        .collect::<Vec<_>>();
+    let source = SamplesBuffer::new(2, 44_100, samples.clone());
+    let output = ClapChainSource::new(source, &chain).collect::<Vec<_>>();
+
+    assert_eq!(output.len(), samples.len());
+    for (output, input) in output.iter().zip(&samples) {
+        assert_eq!(*output, input * 0.5);
+    }
+
+    let (_state, params) = chain.set_param(0, GAIN_PARAM_ID, 2.0).unwrap();
+    assert_eq!(params[0].value, 2.0);
+    let source = SamplesBuffer::new(2, 44_100, vec![0.25; 4]);
+    let output = ClapChainSource::new(source, &chain).collect::<Vec<_>>();
+    assert_eq!(output, vec![0.5; 4]);
+}
+
+#[test]
+fn sources_at_different_rates_get_their_own_instances() {
+    let plugin = ClapPlugin::load(&plugin_library(), PLUGIN_ID).unwrap();
+    let chain = ClapChain::default();
+    chain.set_entries(vec![ClapChainEntry::new(0, plugin)]);
+
+    // A queued track is built while the current one is still playing
+    let mut playing = ClapChainSource::new(SamplesBuffer::new(2, 44_100, vec![0.5; 2048]), &chain);
+    let first = playing.by_ref().take(1024).collect::<Vec<_>>();
+    let queued = ClapChainSource::new(SamplesBuffer::new(2, 96_000, vec![0.5; 1024]), &chain);
+
+    chain.set_param(0, GAIN_PARAM_ID, 0.5).unwrap();
+    let rest = playing.collect::<Vec<_>>();
+
+    assert_eq!(first, vec![0.5; 1024]);
+    assert_eq!(rest, vec![0.25; 1024]);
+    assert_eq!(queued.collect::<Vec<_>>(), vec![0.25; 1024]);
+}
@@ -1,19 +1,31 @@
 use std::{
     io::{Write, stdin, stdout},
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::Arc,
+    time::Duration,
 };
 
-use clap::{Parser, Subcommand};
+use chrono::{Datelike, Timelike};
+use clap::{ArgGroup, Parser, Subcommand};
 use qobuz_player_controls::{
-    AudioQuality, client::Client, database::Database, notification::{Notification, NotificationBroadcast},
-    player::Player, list_audio_devices, get_default_device_name,
+    AudioQuality, audio_cache::verify_audio_cache, channel_mix::UpmixPreset, client::Client,
+    crossfeed::CrossfeedPreset, get_default_device_name,
+    export::{DEFAULT_EXPORT_TEMPLATE, export_album}, database::Database, notification::{Notification, NotificationBroadcast},
+    output::{OutputBackend, OutputSampleFormat},
+    player::Player,
+    playlist_file::{PlaylistFormat, export_tracks, import_playlist, read_playlist},
+    render::{RenderSection, render_track},
+    schedule::{EVERY_DAY, MAX_RAMP_UP_MINUTES, ScheduleTarget, TimeOfDay, parse_weekdays},
 };
 use qobuz_player_rfid::RfidState;
 use snafu::prelude::*;
 use tokio::sync::broadcast;
 use tokio_schedule::{Job, every};
 
+/// A running player stores its own queue over changes made from the command line.
+const PLAYER_RUNNING: &str =
+    "The player is running. Save or load queues from the web or terminal interface.";
+
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
 struct Cli {
@@ -82,15 +94,79 @@ enum Commands {
         #[clap(long, default_value_t = 1)]
         /// Hours before audio cache is cleaned. 0 for disable
         audio_cache_time_to_live: u32,
+
+        #[clap(long, default_value = "cpal")]
+        /// Audio output: cpal, null, wav:<path> or jack:<client>. Null and wav outputs do not need a sound card
+        output: OutputBackend,
     },
     /// Persist configurations
     Config {
         #[clap(subcommand)]
         command: ConfigCommands,
     },
+    /// Manage scheduled playback
+    Schedule {
+        #[clap(subcommand)]
+        command: ScheduleCommands,
+    },
+    /// Manage saved queues
+    Queue {
+        #[clap(subcommand)]
+        command: QueueCommands,
+    },
+    /// Import and export M3U8, XSPF and CSV playlist files
+    Playlist {
+        #[clap(subcommand)]
+        command: PlaylistCommands,
+    },
     /// Refresh database
     #[clap(name = "refresh")]
     RefreshDatabase,
+    /// Verify cached audio files and remove broken ones
+    #[clap(name = "verify-cache")]
+    VerifyCache {
+        #[clap(long)]
+        /// Audio cache directory [default: Temporary directory]
+        audio_cache: Option<PathBuf>,
+    },
+    /// Export a cached album to a tagged music folder
+    Export {
+        /// Id of the album to export
+        album_id: String,
+
+        #[clap(short, long)]
+        /// Target directory (overrides any configured value)
+        target: Option<PathBuf>,
+
+        #[clap(long)]
+        /// Naming template with {artist}, {album}, {year}, {number} and {title} (overrides any configured value)
+        template: Option<String>,
+
+        #[clap(long)]
+        /// Audio cache directory [default: Temporary directory]
+        audio_cache: Option<PathBuf>,
+    },
+    /// Render a cached track or part of it to WAV with the saved tempo, pitch and plugins applied
+    Render {
+        /// Id of the track to render
+        track_id: u32,
+
+        #[clap(long)]
+        /// Start of the section in seconds [default: Start of the track]
+        start: Option<f64>,
+
+        #[clap(long)]
+        /// End of the section in seconds [default: End of the track]
+        end: Option<f64>,
+
+        #[clap(short, long)]
+        /// Target directory (overrides the configured export directory)
+        target: Option<PathBuf>,
+
+        #[clap(long)]
+        /// Audio cache directory [default: Temporary directory]
+        audio_cache: Option<PathBuf>,
+    },
 }
 
 #[derive(Subcommand)]
@@ -107,6 +183,142 @@ pub enum ConfigCommands {
         #[clap(value_enum)]
         quality: AudioQuality,
     },
+    /// Set directory albums are exported to.
+    #[clap(value_parser)]
+    ExportDirectory { directory: PathBuf },
+    /// Set naming template for exported albums.
+    #[clap(value_parser)]
+    ExportTemplate { template: String },
+    /// Play FLAC files at their native sample rate and bit depth when tempo, pitch and volume are neutral.
+    #[clap(value_parser)]
+    BitPerfect {
+        #[clap(action = clap::ArgAction::Set)]
+        enabled: bool,
+    },
+    /// Set output sample format (i16, i24, i32 or f32) for an output. Leave empty to use the device default.
+    #[clap(value_parser)]
+    OutputSampleFormat {
+        sample_format: Option<OutputSampleFormat>,
+        /// Output to configure [default: Default output]
+        #[clap(short, long)]
+        device: Option<String>,
+    },
+    /// Set how stereo is spread over outputs with more than two channels (front, mirror or center).
+    #[clap(value_parser)]
+    UpmixPreset { preset: UpmixPreset },
+    /// Turn crossfeed on or off for an output. Leave empty to turn it on automatically for headphones.
+    #[clap(value_parser)]
+    Crossfeed {
+        #[clap(action = clap::ArgAction::Set)]
+        enabled: Option<bool>,
+        /// Output to configure [default: Default output]
+        #[clap(short, long)]
+        device: Option<String>,
+    },
+    /// Set crossfeed preset (bauer or meier) and strength.
+    #[clap(value_parser)]
+    CrossfeedPreset {
+        preset: CrossfeedPreset,
+        /// Strength from 0.0 to 1.0
+        #[clap(short, long, default_value_t = 1.0)]
+        strength: f32,
+    },
+    /// Start playing the restored queue from its saved position on startup.
+    #[clap(value_parser)]
+    ResumePlayback {
+        #[clap(action = clap::ArgAction::Set)]
+        enabled: bool,
+    },
+    /// Set the directory scanned for CLAP plugins. Leave empty to unset.
+    #[clap(value_parser)]
+    ClapPluginPath { path: Option<String> },
+}
+
+#[derive(Subcommand)]
+pub enum ScheduleCommands {
+    /// List scheduled playback.
+    List,
+    /// Start an album or playlist at a time of day.
+    Add {
+        /// Id of the album to play
+        #[clap(long, conflicts_with = "playlist", required_unless_present = "playlist")]
+        album: Option<String>,
+        /// Id of the playlist to play
+        #[clap(long)]
+        playlist: Option<u32>,
+        /// Local time of day as HH:MM
+        #[clap(long)]
+        time: TimeOfDay,
+        /// Comma separated weekdays, like mon,tue,fri [default: Every day]
+        #[clap(long, value_parser = parse_weekdays)]
+        days: Option<u8>,
+        /// Raise the volume from silence over this many minutes
+        #[clap(long, value_parser = clap::value_parser!(u64).range(..=MAX_RAMP_UP_MINUTES))]
+        ramp_up_minutes: Option<u64>,
+    },
+    /// Remove scheduled playback.
+    #[clap(value_parser)]
+    Remove { id: i64 },
+}
+
+#[derive(Subcommand)]
+pub enum QueueCommands {
+    /// List saved queues.
+    List,
+    /// Save the queue from the last session under a name. Use the web or
+    /// terminal interface while the player is running.
+    #[clap(value_parser)]
+    Save { name: String },
+    /// Restore a saved queue for the next session. Use the web or terminal
+    /// interface while the player is running.
+    Load {
+        name: String,
+        /// Add the saved tracks to the end of the queue instead of replacing it
+        #[clap(short, long, default_value_t = false)]
+        append: bool,
+    },
+    /// Delete a saved queue.
+    #[clap(value_parser)]
+    Delete { name: String },
+}
+
+#[derive(Subcommand)]
+pub enum PlaylistCommands {
+    /// Export a Qobuz playlist, an album or the queue from the last session.
+    #[clap(group(ArgGroup::new("source").required(true).args(["playlist", "album", "queue"])))]
+    Export {
+        /// File to write
+        output: PathBuf,
+
+        #[clap(long)]
+        /// Id of the playlist to export
+        playlist: Option<u32>,
+
+        #[clap(long)]
+        /// Id of the album to export
+        album: Option<String>,
+
+        #[clap(long, default_value_t = false)]
+        /// Export the queue from the last session
+        queue: bool,
+
+        #[clap(short, long)]
+        /// m3u8, xspf or csv [default: From the file extension]
+        format: Option<PlaylistFormat>,
+    },
+    /// Import a playlist file into a new Qobuz playlist.
+    Import {
+        /// File to read
+        file: PathBuf,
+
+        #[clap(short, long)]
+        /// Name of the new playlist [default: Title in the file or the file name]
+        name: Option<String>,
+
+        #[clap(short, long)]
+        /// m3u8, xspf or csv [default: From the file extension]
+        format: Option<PlaylistFormat>,
+    },
 }
 
 #[derive(Debug, Snafu)]
@@ -121,6 +333,12 @@ pub enum Error {
     PasswordMissing,
     #[snafu(display("Error reading error prompt"))]
     PasswordError,
+    #[snafu(display("No export directory found. Set with config or arguments"))]
+    ExportDirectoryMissing,
+    #[snafu(display("Unknown playlist format. Use --format with m3u8, xspf or csv"))]
+    PlaylistFormatMissing,
+    #[snafu(display("Unable to access {path}: {error}"))]
+    FileError { path: String, error: String },
 }
 
 impl From<qobuz_player_controls::error::Error> for Error {
@@ -224,6 +442,7 @@ pub async fn run() -> Result<(), Error> {
         audio_cache: Default::default(),
         audio_cache_time_to_live: Default::default(),
         disable_tui_album_cover: false,
+        output: Default::default(),
     }) {
         Commands::Open {
             username,
@@ -241,6 +460,7 @@ pub async fn run() -> Result<(), Error> {
             audio_cache,
             audio_cache_time_to_live,
             disable_tui_album_cover,
+            output,
         } => {
             let database_credentials = database.get_credentials().await?;
 
@@ -260,11 +480,10 @@ pub async fn run() -> Result<(), Error> {
 
             let (exit_sender, exit_receiver) = broadcast::channel(5);
 
-            let audio_cache = audio_cache.unwrap_or_else(|| {
-                let mut cache_dir = std::env::temp_dir();
-                cache_dir.push("qobuz-player-cache");
-                cache_dir
-            });
+            let audio_cache = audio_cache.unwrap_or_else(default_audio_cache_dir);
+            let audio_cache_for_verification = audio_cache.clone();
+            let audio_cache_for_web = audio_cache.clone();
+            let audio_cache_for_tui = audio_cache.clone();
 
             let username = match username {
                 Some(username) => username,
@@ -297,6 +516,7 @@ pub async fn run() -> Result<(), Error> {
                 broadcast.clone(),
                 audio_cache,
                 database.clone(),
+                output,
             )?;
 
             let rfid_state = rfid.then(RfidState::default);
@@ -307,6 +527,7 @@ pub async fn run() -> Result<(), Error> {
                 let tracklist_receiver = player.tracklist();
                 let volume_receiver = player.volume();
                 let status_receiver = player.status();
+                let repeat_mode_receiver = player.repeat_mode();
                 let controls = player.controls();
                 let exit_sender = exit_sender.clone();
                 tokio::spawn(async move {
@@ -315,6 +536,7 @@ pub async fn run() -> Result<(), Error> {
                         tracklist_receiver,
                         volume_receiver,
                         status_receiver,
+                        repeat_mode_receiver,
                         controls,
                         exit_sender,
                     )
@@ -327,9 +549,11 @@ pub async fn run() -> Result<(), Error> {
 
             if web {
                 let position_receiver = player.position();
+                let levels_receiver = player.levels();
                 let tracklist_receiver = player.tracklist();
                 let volume_receiver = player.volume();
                 let status_receiver = player.status();
+                let sleep_timer_receiver = player.sleep_timer();
                 let controls = player.controls();
                 let rfid_state = rfid_state.clone();
                 let broadcast = broadcast.clone();
@@ -341,9 +565,11 @@ pub async fn run() -> Result<(), Error> {
                     if let Err(e) = qobuz_player_web::init(
                         controls,
                         position_receiver,
+                        levels_receiver,
                         tracklist_receiver,
                         volume_receiver,
                         status_receiver,
+                        sleep_timer_receiver,
                         port,
                         web_secret,
                         rfid_state,
@@ -351,6 +577,7 @@ pub async fn run() -> Result<(), Error> {
                         client,
                         database,
                         exit_sender,
+                        audio_cache_for_web,
                     )
                     .await
                     {
@@ -389,8 +616,10 @@ pub async fn run() -> Result<(), Error> {
                 });
             } else if !disable_tui {
                 let position_receiver = player.position();
+                let levels_receiver = player.levels();
                 let tracklist_receiver = player.tracklist();
                 let status_receiver = player.status();
+                let sleep_timer_receiver = player.sleep_timer();
                 let controls = player.controls();
                 let client = client.clone();
                 let broadcast = broadcast.clone();
@@ -401,11 +630,14 @@ pub async fn run() -> Result<(), Error> {
                         broadcast,
                         controls,
                         position_receiver,
+                        levels_receiver,
                         tracklist_receiver,
                         status_receiver,
+                        sleep_timer_receiver,
                         exit_sender,
                         database,
                         disable_tui_album_cover,
+                        audio_cache_for_tui,
                     )
                     .await
                     {
@@ -415,8 +647,6 @@ pub async fn run() -> Result<(), Error> {
             };
 
             let database_for_cleanup = database.clone();
-            let database_for_monitoring = database.clone();
-            let broadcast_for_monitoring = broadcast.clone();
 
             if audio_cache_time_to_live != 0 {
                 let clean_up_schedule = every(1).hour().perform(move || {
@@ -438,11 +668,56 @@ pub async fn run() -> Result<(), Error> {
                 tokio::spawn(clean_up_schedule);
             }
 
-            let controls_for_monitoring = player.controls();
-            tokio::spawn(async move {
-                monitor_audio_devices(controls_for_monitoring, database_for_monitoring, broadcast_for_monitoring).await;
+            let database_for_verification = database.clone();
+            let broadcast_for_verification = broadcast.clone();
+            let verification_schedule = every(6).hours().perform(move || {
+                let database = database_for_verification.clone();
+                let broadcast = broadcast_for_verification.clone();
+                let audio_cache = audio_cache_for_verification.clone();
+                async move {
+                    match verify_audio_cache(&audio_cache, &database).await {
+                        Ok(report) => {
+                            if !report.removed_broken_files.is_empty() {
+                                broadcast.send(Notification::Warning(format!(
+                                    "Removed {} broken files from audio cache. They download again when played",
+                                    report.removed_broken_files.len()
+                                )));
+                            }
+                        }
+                        Err(e) => tracing::error!("Unable to verify audio cache: {e}"),
+                    }
+                }
             });
 
+            tokio::spawn(verification_schedule);
+
+            let database_for_schedules = database.clone();
+            let controls_for_schedules = player.controls();
+            let playback_schedule = every(1).minute().at(0).perform(move || {
+                let database = database_for_schedules.clone();
+                let controls = controls_for_schedules.clone();
+                async move {
+                    let now = chrono::Local::now();
+                    let weekday = now.weekday().num_days_from_monday() as u8;
+                    let time = TimeOfDay {
+                        hour: now.hour() as u8,
+                        minute: now.minute() as u8,
+                    };
+
+                    match database.get_schedules().await {
+                        Ok(schedules) => {
+                            for schedule in schedules.iter().filter(|s| s.is_due(weekday, time)) {
+                                tracing::info!("Starting scheduled {}", schedule.target);
+                                schedule.start(&controls);
+                            }
+                        }
+                        Err(e) => tracing::error!("Unable to load schedules: {e}"),
+                    }
+                }
+            });
+
+            tokio::spawn(playback_schedule);
+
             player.player_loop(exit_receiver).await?;
             Ok(())
         }
@@ -474,109 +749,390 @@ pub async fn run() -> Result<(), Error> {
                 println!("Max audio quality saved.");
                 Ok(())
             }
+            ConfigCommands::ExportDirectory { directory } => {
+                let configuration = database.get_configuration().await?;
+                database
+                    .set_export_settings(
+                        Some(directory.to_string_lossy().into_owned()),
+                        configuration.export_template,
+                    )
+                    .await?;
+                println!("Export directory saved.");
+                Ok(())
+            }
+            ConfigCommands::ExportTemplate { template } => {
+                let configuration = database.get_configuration().await?;
+                database
+                    .set_export_settings(configuration.export_directory, Some(template))
+                    .await?;
+                println!("Export template saved.");
+                Ok(())
+            }
+            ConfigCommands::BitPerfect { enabled } => {
+                database.set_bit_perfect(enabled).await?;
+                println!("Bit-perfect playback saved.");
+                Ok(())
+            }
+            ConfigCommands::OutputSampleFormat {
+                sample_format,
+                device,
+            } => {
+                database
+                    .set_output_sample_format(device.as_deref(), sample_format)
+                    .await?;
+                println!("Output sample format saved.");
+                Ok(())
+            }
+            ConfigCommands::UpmixPreset { preset } => {
+                database.set_upmix_preset(preset).await?;
+                println!("Upmix preset saved.");
+                Ok(())
+            }
+            ConfigCommands::Crossfeed { enabled, device } => {
+                let Some(device) = device.or_else(|| get_default_device_name().ok().flatten())
+                else {
+                    println!("No default output found. Set one with --device.");
+                    return Ok(());
+                };
+                database.set_crossfeed_device(&device, enabled).await?;
+                println!("Crossfeed saved for '{device}'.");
+                Ok(())
+            }
+            ConfigCommands::CrossfeedPreset { preset, strength } => {
+                database.set_crossfeed_preset(preset, strength).await?;
+                println!("Crossfeed preset saved.");
+                Ok(())
+            }
+            ConfigCommands::ResumePlayback { enabled } => {
+                database.set_resume_playback(enabled).await?;
+                println!("Resume playback saved.");
+                Ok(())
+            }
+            ConfigCommands::ClapPluginPath { path } => {
+                database.set_clap_plugin_path(path).await?;
+                println!("Plugin directory saved.");
+                Ok(())
+            }
         },
-        Commands::RefreshDatabase => {
-            database.refresh_database().await?;
-            println!("Database refreshed successfully.");
-            Ok(())
-        }
-    }
-}
-
-async fn monitor_audio_devices(
-    controls: qobuz_player_controls::controls::Controls,
-    database: Arc<Database>,
-    broadcast: Arc<NotificationBroadcast>,
-) {
-    tracing::info!("Starting audio device monitoring");
-    let mut last_device_count = 0;
-    let mut last_selected_device: Option<String> = None;
-    let mut last_default_device: Option<String> = None;
-    
-    loop {
-        tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-        
-        match list_audio_devices() {
-            Ok(devices) => {
-                let current_count = devices.len();
-                let current_selected = database.get_configuration().await
-                    .ok()
-                    .and_then(|c| c.audio_device_name);
-                
-                let current_default = get_default_device_name().unwrap_or(None);
-                
-                let mut device_changes = Vec::new();
-                let mut needs_device_update = false;
-                    
-                if current_count != last_device_count {
-                    tracing::info!("Audio device count changed: {} -> {}", last_device_count, current_count);
-                    last_device_count = current_count;
-                    device_changes.push("Audio device list updated".to_string());
-                    
-                    if let Some(selected) = &current_selected {
-                        if !devices.iter().any(|d| &d.name == selected) {
-                            tracing::warn!("Selected audio device '{}' no longer available", selected);
-                            broadcast.send(Notification::Warning(
-                                format!("Audio device '{}' was removed. Using default device.", selected)
-                            ));
-                            if database.set_audio_device(None).await.is_ok() {
-                                controls.set_audio_device(None);
-                            }
-                        }
-                    }
+        Commands::Schedule { command } => match command {
+            ScheduleCommands::List => {
+                let schedules = database.get_schedules().await?;
+                if schedules.is_empty() {
+                    println!("No scheduled playback.");
                 }
-                
-                if current_selected.is_none() && current_default != last_default_device {
-                    match (&last_default_device, &current_default) {
-                        (Some(old), Some(new)) if old != new => {
-                            tracing::info!("System default device changed: '{}' -> '{}'", old, new);
-                            device_changes.push(format!("Default audio device changed to '{}'", new));
-                            needs_device_update = true;
-                        }
-                        (None, Some(new)) => {
-                            tracing::info!("Default audio device appeared: '{}'", new);
-                            device_changes.push(format!("Default audio device is now '{}'", new));
-                            needs_device_update = true;
-                        }
-                        (Some(_), None) => {
-                            tracing::warn!("Default audio device disappeared");
-                            broadcast.send(Notification::Warning(
-                                "Default audio device was removed.".to_string()
-                            ));
-                            needs_device_update = true;
-                        }
-                        _ => {}
-                    }
+                for schedule in schedules {
+                    let ramp_up = schedule
+                        .ramp_up
+                        .map(|ramp_up| format!(", ramp up {} min", ramp_up.as_secs() / 60))
+                        .unwrap_or_default();
+                    println!(
+                        "{}: {} at {} {}{ramp_up}",
+                        schedule.id,
+                        schedule.target,
+                        schedule.time,
+                        schedule.weekday_names()
+                    );
                 }
-                
-                if current_selected.is_none() && current_count != last_device_count {
-                    tracing::info!("Device list changed while 'Default' is selected, rechecking default device");
-                    needs_device_update = true;
+                Ok(())
+            }
+            ScheduleCommands::Add {
+                album,
+                playlist,
+                time,
+                days,
+                ramp_up_minutes,
+            } => {
+                let target = match (album, playlist) {
+                    (Some(album), _) => ScheduleTarget::Album(album),
+                    (None, Some(playlist)) => ScheduleTarget::Playlist(playlist),
+                    (None, None) => unreachable!("clap requires album or playlist"),
+                };
+                let weekdays = days.unwrap_or(EVERY_DAY);
+                let ramp_up = ramp_up_minutes
+                    .filter(|minutes| *minutes > 0)
+                    .map(|minutes| Duration::from_secs(minutes * 60));
+                let id = database
+                    .add_schedule(&target, time, weekdays, ramp_up)
+                    .await?;
+                println!("Schedule {id} saved.");
+                Ok(())
+            }
+            ScheduleCommands::Remove { id } => {
+                database.remove_schedule(id).await?;
+                println!("Schedule removed.");
+                Ok(())
+            }
+        },
+        Commands::Queue { command } => match command {
+            QueueCommands::List => {
+                let queues = database.get_saved_queues().await?;
+                if queues.is_empty() {
+                    println!("No saved queues.");
                 }
-                
-                if needs_device_update {
-                    controls.set_audio_device(None);
+                for queue in queues {
+                    println!("{} ({} tracks)", queue.name, queue.tracklist.total());
+                }
+                Ok(())
+            }
+            QueueCommands::Save { name } => {
+                if database.player_running() {
+                    println!("{PLAYER_RUNNING}");
+                    return Ok(());
                 }
-                
-                last_default_device = current_default.clone();
-                
-                if !device_changes.is_empty() {
-                    broadcast.send(Notification::Info(device_changes.join(". ")));
+                let tracklist = database.get_tracklist().await.unwrap_or_default();
+                if tracklist.queue.is_empty() {
+                    println!("The queue is empty.");
+                    return Ok(());
+                }
+                database.save_queue(&name, &tracklist).await?;
+                println!("Queue saved as {name}.");
+                Ok(())
+            }
+            QueueCommands::Load { name, append } => {
+                if database.player_running() {
+                    println!("{PLAYER_RUNNING}");
+                    return Ok(());
+                }
+                let Some(mut saved) = database.get_saved_queue(&name).await? else {
+                    println!("No saved queue named {name}.");
+                    return Ok(());
+                };
+                let tracklist = if append {
+                    let mut tracklist = database.get_tracklist().await.unwrap_or_default();
+                    tracklist.append(saved.queue);
+                    tracklist
+                } else {
+                    database.set_playback_position(None).await?;
+                    saved.stop_after = None;
+                    saved
+                };
+                database.set_tracklist(&tracklist).await?;
+                println!("Queue {name} loaded for the next session.");
+                Ok(())
+            }
+            QueueCommands::Delete { name } => {
+                database.remove_saved_queue(&name).await?;
+                println!("Saved queue deleted.");
+                Ok(())
+            }
+        },
+        Commands::Playlist { command } => match command {
+            PlaylistCommands::Export {
+                output,
+                playlist,
+                album,
+                queue,
+                format,
+            } => {
+                let format = playlist_format(format, &output)?;
+                let (title, tracks) = if queue {
+                    let tracklist = database.get_tracklist().await.unwrap_or_default();
+                    ("Queue".to_string(), tracklist.queue)
+                } else {
+                    let client = client(&database).await?;
+                    match (playlist, album) {
+                        (Some(id), _) => {
+                            let playlist = client.playlist(id).await?;
+                            (playlist.title, playlist.tracks)
+                        }
+                        (None, Some(id)) => {
+                            let album = client.album(&id).await?;
+                            (album.title, album.tracks)
+                        }
+                        (None, None) => unreachable!("clap requires a source"),
+                    }
+                };
+
+                if tracks.is_empty() {
+                    println!("Nothing to export.");
+                    return Ok(());
                 }
-                
-                if current_selected != last_selected_device {
-                    tracing::info!("Selected audio device changed externally: {:?} -> {:?}", 
-                        last_selected_device, current_selected);
-                    last_selected_device = current_selected.clone();
+
+                write_file(&output, &export_tracks(format, &title, &tracks))?;
+                println!(
+                    "Exported {} tracks to {}.",
+                    tracks.len(),
+                    output.to_string_lossy()
+                );
+                Ok(())
+            }
+            PlaylistCommands::Import { file, name, format } => {
+                let format = playlist_format(format, &file)?;
+                let contents =
+                    std::fs::read_to_string(&file).map_err(|error| Error::FileError {
+                        path: file.to_string_lossy().into_owned(),
+                        error: error.to_string(),
+                    })?;
+                let playlist_file = read_playlist(format, &contents)?;
+                let name = name.or(playlist_file.title).unwrap_or_else(|| {
+                    file.file_stem()
+                        .map(|stem| stem.to_string_lossy().into_owned())
+                        .unwrap_or_else(|| "Imported playlist".to_string())
+                });
+
+                let client = client(&database).await?;
+                let report = import_playlist(&client, name, &playlist_file.entries).await?;
+
+                for entry in &report.unmatched {
+                    println!("Not found: {entry}");
                 }
+                println!(
+                    "Created playlist {} with {} of {} tracks.",
+                    report.playlist.title,
+                    report.added,
+                    playlist_file.entries.len()
+                );
+                Ok(())
             }
-            Err(e) => {
-                tracing::error!("Failed to list audio devices: {}", e);
+        },
+        Commands::RefreshDatabase => {
+            database.refresh_database().await?;
+            println!("Database refreshed successfully.");
+            Ok(())
+        }
+        Commands::Export {
+            album_id,
+            target,
+            template,
+            audio_cache,
+        } => export(&database, album_id, target, template, audio_cache).await,
+        Commands::Render {
+            track_id,
+            start,
+            end,
+            target,
+            audio_cache,
+        } => {
+            let section = RenderSection::from_secs(start, end)?;
+            render(&database, track_id, section, target, audio_cache).await
+        }
+        Commands::VerifyCache { audio_cache } => {
+            let audio_cache = audio_cache.unwrap_or_else(default_audio_cache_dir);
+            let report = verify_audio_cache(&audio_cache, &database).await?;
+
+            for path in &report.removed_broken_files {
+                println!("Removed broken file: {}", path.to_string_lossy());
             }
+            for path in &report.removed_partial_files {
+                println!("Removed partial download: {}", path.to_string_lossy());
+            }
+            for track_id in &report.refetch_track_ids {
+                println!("Track {track_id} will be downloaded again when played");
+            }
+
+            println!(
+                "Checked {} files, {} unchanged since the last check. Removed {} broken files, {} partial downloads and {} stale cache entries.",
+                report.checked_files,
+                report.unchanged_files,
+                report.removed_broken_files.len(),
+                report.removed_partial_files.len(),
+                report.pruned_entries
+            );
+            Ok(())
         }
     }
 }
 
+async fn export(
+    database: &Database,
+    album_id: String,
+    target: Option<PathBuf>,
+    template: Option<String>,
+    audio_cache: Option<PathBuf>,
+) -> Result<(), Error> {
+    let database_configuration = database.get_configuration().await?;
+
+    let target = target
+        .or_else(|| database_configuration.export_directory.map(PathBuf::from))
+        .ok_or(Error::ExportDirectoryMissing)?;
+    let template = template
+        .or(database_configuration.export_template)
+        .unwrap_or_else(|| DEFAULT_EXPORT_TEMPLATE.to_string());
+    let audio_cache = audio_cache.unwrap_or_else(default_audio_cache_dir);
+
+    let client = client(database).await?;
+    let report = export_album(&client, &audio_cache, &album_id, &target, &template).await?;
+
+    for path in &report.exported {
+        println!("Exported: {}", path.to_string_lossy());
+    }
+    for path in &report.untagged {
+        println!("Unable to tag, not exported: {}", path.to_string_lossy());
+    }
+    for title in &report.not_cached {
+        println!("Not cached, play it once to export: {title}");
+    }
+
+    println!(
+        "Exported {} of {} tracks.",
+        report.exported.len(),
+        report.exported.len() + report.not_cached.len() + report.untagged.len()
+    );
+    Ok(())
+}
+
+async fn render(
+    database: &Database,
+    track_id: u32,
+    section: RenderSection,
+    target: Option<PathBuf>,
+    audio_cache: Option<PathBuf>,
+) -> Result<(), Error> {
+    let database_configuration = database.get_configuration().await?;
+
+    let target = target
+        .or_else(|| database_configuration.export_directory.map(PathBuf::from))
+        .ok_or(Error::ExportDirectoryMissing)?;
+    let audio_cache = audio_cache.unwrap_or_else(default_audio_cache_dir);
+
+    let client = client(database).await?;
+    let report = render_track(&client, database, &audio_cache, track_id, section, &target).await?;
+
+    println!(
+        "Rendered {:.1}s to {}",
+        report.duration.as_secs_f64(),
+        report.path.to_string_lossy()
+    );
+    Ok(())
+}
+
+async fn client(database: &Database) -> Result<Client, Error> {
+    let database_credentials = database.get_credentials().await?;
+    let database_configuration = database.get_configuration().await?;
+
+    let username = database_credentials
+        .username
+        .ok_or(Error::UsernameMissing)?;
+    let password = database_credentials
+        .password
+        .ok_or(Error::PasswordMissing)?;
+    let max_audio_quality = database_configuration
+        .max_audio_quality
+        .try_into()
+        .expect("This should always convert");
+
+    Ok(Client::new(username, password, max_audio_quality))
+}
+
+fn playlist_format(format: Option<PlaylistFormat>, path: &Path) -> Result<PlaylistFormat, Error> {
+    format
+        .or_else(|| PlaylistFormat::from_path(path))
+        .ok_or(Error::PlaylistFormatMissing)
+}
+
+fn write_file(path: &Path, contents: &str) -> Result<(), Error> {
+    std::fs::write(path, contents).map_err(|error| Error::FileError {
+        path: path.to_string_lossy().into_owned(),
+        error: error.to_string(),
+    })
+}
+
+fn default_audio_cache_dir() -> PathBuf {
+    let mut cache_dir = std::env::temp_dir();
+    cache_dir.push("qobuz-player-cache");
+    cache_dir
+}
+
 fn error_exit(error: Error) {
     eprintln!("{error}");
     std::process::exit(1);
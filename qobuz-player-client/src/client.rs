@@ -37,7 +37,7 @@ pub struct Client {
     max_audio_quality: AudioQuality,
 }
 
-#[derive(Clone, Debug, clap::ValueEnum)]
+#[derive(Clone, Debug, PartialEq, clap::ValueEnum)]
 pub enum AudioQuality {
     Mp3 = 5,
     CD = 6,
@@ -56,6 +56,17 @@ impl Display for AudioQuality {
     }
 }
 
+impl AudioQuality {
+    pub fn lower(&self) -> Option<AudioQuality> {
+        match self {
+            AudioQuality::Mp3 => None,
+            AudioQuality::CD => Some(AudioQuality::Mp3),
+            AudioQuality::HIFI96 => Some(AudioQuality::CD),
+            AudioQuality::HIFI192 => Some(AudioQuality::HIFI96),
+        }
+    }
+}
+
 impl TryFrom<i64> for AudioQuality {
     type Error = ();
 
@@ -491,6 +502,15 @@ impl Client {
     }
 
     pub async fn track_url(&self, track_id: u32) -> Result<TrackURL> {
+        self.track_url_with_quality(track_id, &self.max_audio_quality)
+            .await
+    }
+
+    pub async fn track_url_with_quality(
+        &self,
+        track_id: u32,
+        audio_quality: &AudioQuality,
+    ) -> Result<TrackURL> {
         track_url(
             track_id,
             &self.active_secret,
@@ -498,7 +518,7 @@ impl Client {
             &self.http_client,
             &self.app_id,
             &self.user_token,
-            &self.max_audio_quality,
+            audio_quality,
         )
         .await
     }
@@ -1221,6 +1241,8 @@ fn parse_album(
                 album_title: Some(value.title.clone()),
                 album_id: Some(value.id.clone()),
                 playlist_track_id: None,
+                isrc: t.isrc,
+                stream_format: None,
             })
             .collect()
     });
@@ -1308,6 +1330,8 @@ fn parse_artist_page(value: artist_page::ArtistPage) -> qobuz_player_models::Art
                     album_title: Some(t.album.title),
                     album_id: Some(t.album.id),
                     playlist_track_id: None,
+                    isrc: Some(t.isrc),
+                    stream_format: None,
                 }
             })
             .collect(),
@@ -1408,6 +1432,8 @@ fn parse_track(
         album_title: value.album.as_ref().map(|a| a.title.clone()),
         album_id: value.album.as_ref().map(|a| a.id.clone()),
         playlist_track_id: value.playlist_track_id,
+        isrc: value.isrc,
+        stream_format: None,
     }
 }
 
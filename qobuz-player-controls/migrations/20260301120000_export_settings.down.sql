@@ -0,0 +1,2 @@
+ALTER TABLE configuration DROP COLUMN export_directory;
+ALTER TABLE configuration DROP COLUMN export_template;
@@ -0,0 +1,2 @@
+ALTER TABLE configuration ADD COLUMN export_directory TEXT;
+ALTER TABLE configuration ADD COLUMN export_template TEXT;
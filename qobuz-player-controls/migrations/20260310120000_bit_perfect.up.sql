@@ -0,0 +1 @@
+ALTER TABLE configuration ADD COLUMN bit_perfect BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1 @@
+ALTER TABLE configuration DROP COLUMN output_sample_formats;
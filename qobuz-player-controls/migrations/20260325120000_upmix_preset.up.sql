@@ -0,0 +1 @@
+ALTER TABLE configuration ADD COLUMN upmix_preset TEXT;
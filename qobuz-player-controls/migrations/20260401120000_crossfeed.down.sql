@@ -0,0 +1,3 @@
+ALTER TABLE configuration DROP COLUMN crossfeed_devices;
+ALTER TABLE configuration DROP COLUMN crossfeed_preset;
+ALTER TABLE configuration DROP COLUMN crossfeed_strength;
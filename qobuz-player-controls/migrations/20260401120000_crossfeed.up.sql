@@ -0,0 +1,3 @@
+ALTER TABLE configuration ADD COLUMN crossfeed_devices TEXT;
+ALTER TABLE configuration ADD COLUMN crossfeed_preset TEXT;
+ALTER TABLE configuration ADD COLUMN crossfeed_strength REAL NOT NULL DEFAULT 1.0;
@@ -0,0 +1,2 @@
+DROP TABLE clap_plugins;
+ALTER TABLE configuration DROP COLUMN clap_plugin_path;
@@ -0,0 +1,10 @@
+ALTER TABLE configuration ADD COLUMN clap_plugin_path TEXT;
+CREATE TABLE clap_plugins (
+    chain TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    path TEXT NOT NULL,
+    plugin_id TEXT NOT NULL,
+    state BLOB,
+    params TEXT,
+    PRIMARY KEY (chain, position)
+);
@@ -0,0 +1 @@
+DROP TABLE chord_timelines;
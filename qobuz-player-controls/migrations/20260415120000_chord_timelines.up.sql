@@ -0,0 +1,4 @@
+CREATE TABLE chord_timelines (
+    track_id INTEGER PRIMARY KEY NOT NULL,
+    segments TEXT NOT NULL
+);
@@ -0,0 +1 @@
+ALTER TABLE configuration ADD COLUMN repeat_mode TEXT;
@@ -0,0 +1,9 @@
+CREATE TABLE schedules (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    target_kind TEXT NOT NULL,
+    target_id TEXT NOT NULL,
+    hour INTEGER NOT NULL,
+    minute INTEGER NOT NULL,
+    weekdays INTEGER NOT NULL,
+    ramp_up_seconds INTEGER
+);
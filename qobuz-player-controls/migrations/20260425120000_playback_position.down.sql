@@ -0,0 +1,2 @@
+ALTER TABLE configuration DROP COLUMN resume_playback;
+DROP TABLE IF EXISTS "playback_position";
@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS "playback_position" (
+	"track_id" integer not null,
+	"position_ms" integer not null
+);
+ALTER TABLE configuration ADD COLUMN resume_playback BOOLEAN NOT NULL DEFAULT 0;
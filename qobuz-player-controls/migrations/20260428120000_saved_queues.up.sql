@@ -0,0 +1,4 @@
+CREATE TABLE IF NOT EXISTS "saved_queues" (
+	"name" text primary key not null,
+	"tracklist" string not null
+);
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS local_playlist_entries;
+DROP TABLE IF EXISTS local_playlists;
@@ -0,0 +1,14 @@
+CREATE TABLE local_playlists (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    title TEXT NOT NULL
+);
+
+CREATE TABLE local_playlist_entries (
+    playlist_id INTEGER NOT NULL REFERENCES local_playlists (id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    track_id INTEGER NOT NULL,
+    track TEXT NOT NULL,
+    note TEXT
+);
+
+CREATE INDEX local_playlist_entries_playlist ON local_playlist_entries (playlist_id, position);
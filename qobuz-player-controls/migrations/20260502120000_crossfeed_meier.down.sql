@@ -0,0 +1 @@
+UPDATE configuration SET crossfeed_preset = 'linkwitz' WHERE crossfeed_preset = 'meier';
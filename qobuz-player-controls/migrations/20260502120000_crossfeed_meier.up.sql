@@ -0,0 +1 @@
+UPDATE configuration SET crossfeed_preset = 'meier' WHERE crossfeed_preset = 'linkwitz';
@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS verified_cache_files;
+
+ALTER TABLE cache_entries DROP COLUMN track_id;
@@ -0,0 +1,7 @@
+ALTER TABLE cache_entries ADD COLUMN track_id INTEGER;
+
+CREATE TABLE IF NOT EXISTS "verified_cache_files" (
+    "path" text primary key not null,
+    "size" integer not null,
+    "modified" integer not null
+);
@@ -0,0 +1,229 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use rodio::{Source, decoder::DecoderBuilder};
+
+use crate::{Result, database::Database, waveform::WAVEFORM_DIR};
+
+const PARTIAL_MAX_AGE: Duration = Duration::from_secs(10 * 60);
+
+const DURATION_TOLERANCE: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Default)]
+pub struct CacheVerificationReport {
+    pub checked_files: usize,
+    /// Files skipped because they have not changed since they last decoded.
+    pub unchanged_files: usize,
+    pub removed_broken_files: Vec<PathBuf>,
+    /// Tracks whose cached file was removed. They are downloaded again the
+    /// next time they play.
+    pub refetch_track_ids: Vec<u32>,
+    pub removed_partial_files: Vec<PathBuf>,
+    pub pruned_entries: usize,
+}
+
+/// Size and modification time of a file, to tell whether it changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileStamp {
+    pub size: u64,
+    /// Nanoseconds since the Unix epoch.
+    pub modified: i64,
+}
+
+impl FileStamp {
+    fn of(path: &Path) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(Self {
+            size: metadata.len(),
+            modified: i64::try_from(modified.as_nanos()).ok()?,
+        })
+    }
+}
+
+/// Removes files that fail to decode and stale partial downloads. Files
+/// that decoded cleanly before and have not changed since are not decoded
+/// again.
+pub async fn verify_audio_cache(
+    audio_cache_dir: &Path,
+    database: &Database,
+) -> Result<CacheVerificationReport> {
+    let audio_cache_dir = audio_cache_dir.to_path_buf();
+    let verified = database.verified_cache_files().await?;
+    let (mut report, verified) =
+        tokio::task::spawn_blocking(move || verify_files(&audio_cache_dir, verified))
+            .await
+            .expect("infallible")?;
+    database.set_verified_cache_files(&verified).await?;
+
+    for path in &report.removed_broken_files {
+        if let Some(track_id) = database.cache_entry_track_id(path).await? {
+            report.refetch_track_ids.push(track_id);
+        }
+        database.remove_cache_entry(path).await?;
+    }
+
+    for path in database.cache_entry_paths().await? {
+        if !path.exists() {
+            database.remove_cache_entry(&path).await?;
+            report.pruned_entries += 1;
+        }
+    }
+
+    Ok(report)
+}
+
+/// Returns the report and the stamps of every file that now decodes cleanly.
+fn verify_files(
+    audio_cache_dir: &Path,
+    mut verified: HashMap<PathBuf, FileStamp>,
+) -> Result<(CacheVerificationReport, HashMap<PathBuf, FileStamp>)> {
+    let mut report = CacheVerificationReport::default();
+    let mut still_verified = HashMap::new();
+
+    if !audio_cache_dir.exists() {
+        return Ok((report, still_verified));
+    }
+
+    let mut files = vec![];
+    collect_files(audio_cache_dir, &mut files)?;
+
+    for path in files {
+        if path.extension().is_some_and(|extension| extension == "partial") {
+            if is_stale(&path) {
+                tracing::info!("Removing partial download: {}", path.to_string_lossy());
+                fs::remove_file(&path)?;
+                report.removed_partial_files.push(path);
+            }
+            continue;
+        }
+
+        let stamp = FileStamp::of(&path);
+        if let Some(stamp) = stamp
+            && verified.remove(&path) == Some(stamp)
+        {
+            report.unchanged_files += 1;
+            still_verified.insert(path, stamp);
+            continue;
+        }
+
+        report.checked_files += 1;
+
+        match check_decodable(&path) {
+            Ok(()) => {
+                if let Some(stamp) = stamp {
+                    still_verified.insert(path, stamp);
+                }
+            }
+            Err(message) => {
+                tracing::warn!(
+                    "Removing broken cache file {}: {message}",
+                    path.to_string_lossy()
+                );
+                fs::remove_file(&path)?;
+                report.removed_broken_files.push(path);
+            }
+        }
+    }
+
+    Ok((report, still_verified))
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            if path.ends_with(WAVEFORM_DIR) {
+                continue;
+            }
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+fn is_stale(path: &Path) -> bool {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .is_none_or(|age| age > PARTIAL_MAX_AGE)
+}
+
+fn check_decodable(path: &Path) -> Result<(), String> {
+    let file = fs::File::open(path).map_err(|e| e.to_string())?;
+    let byte_len = file.metadata().map_err(|e| e.to_string())?.len();
+
+    let decoder = DecoderBuilder::new()
+        .with_data(file)
+        .with_byte_len(byte_len)
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    let channels = decoder.channels() as u64;
+    let sample_rate = decoder.sample_rate() as u64;
+    let expected_duration = decoder.total_duration();
+
+    if channels == 0 || sample_rate == 0 {
+        return Err("Invalid stream header".to_string());
+    }
+
+    let frames = decoder.count() as u64 / channels;
+    let decoded_duration = Duration::from_secs_f64(frames as f64 / sample_rate as f64);
+
+    match expected_duration {
+        Some(expected) if decoded_duration + DURATION_TOLERANCE < expected => Err(format!(
+            "Decoded {decoded_duration:?} of {expected:?}"
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn garbage_is_not_decodable() {
+        let dir =
+            std::env::temp_dir().join(format!("qobuz-player-audio-cache-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("garbage.flac");
+        fs::write(&path, b"fLaC this is not audio").unwrap();
+
+        let result = check_decodable(&path);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn only_changed_files_are_decoded_again() {
+        let dir = std::env::temp_dir().join(format!(
+            "qobuz-player-audio-cache-stamps-{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("track.flac");
+        fs::write(&path, include_bytes!("../tests/data/mono-8khz.flac")).unwrap();
+
+        let (first, verified) = verify_files(&dir, HashMap::new()).unwrap();
+        let (second, verified) = verify_files(&dir, verified).unwrap();
+        fs::write(&path, b"fLaC this is not audio").unwrap();
+        let (third, verified) = verify_files(&dir, verified).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!((first.checked_files, first.unchanged_files), (1, 0));
+        assert_eq!((second.checked_files, second.unchanged_files), (0, 1));
+        assert_eq!((third.checked_files, third.unchanged_files), (1, 0));
+        assert_eq!(third.removed_broken_files, vec![path]);
+        assert!(verified.is_empty());
+    }
+}
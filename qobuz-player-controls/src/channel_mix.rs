@@ -0,0 +1,303 @@
+use std::{str::FromStr, time::Duration};
+
+use rodio::{ChannelCount, SampleRate, Source, source::SeekError};
+
+const MINUS_3DB: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UpmixPreset {
+    /// Stereo on the front pair only.
+    #[default]
+    Front,
+    /// Front pair mirrored to the surround channels.
+    Mirror,
+    /// Front pair, a phantom center and surrounds at -3 dB.
+    Center,
+}
+
+impl UpmixPreset {
+    pub const ALL: [Self; 3] = [Self::Front, Self::Mirror, Self::Center];
+}
+
+impl std::fmt::Display for UpmixPreset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Front => "front",
+            Self::Mirror => "mirror",
+            Self::Center => "center",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for UpmixPreset {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|preset| preset.to_string() == value)
+            .ok_or_else(|| format!("Unknown upmix preset '{value}'. Use front, mirror or center"))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Role {
+    Left,
+    Right,
+    Center,
+    Lfe,
+    LeftSurround,
+    RightSurround,
+    Other,
+}
+
+/// Channel roles in WAVE/ALSA order.
+fn roles(channels: u16) -> Vec<Role> {
+    use Role::*;
+
+    let mut roles = match channels {
+        1 => vec![Center],
+        2 => vec![Left, Right],
+        3 => vec![Left, Right, Center],
+        4 => vec![Left, Right, LeftSurround, RightSurround],
+        5 => vec![Left, Right, Center, LeftSurround, RightSurround],
+        6 | 7 => vec![Left, Right, Center, Lfe, LeftSurround, RightSurround],
+        _ => vec![Left, Right, Center, Lfe, LeftSurround, RightSurround, LeftSurround, RightSurround],
+    };
+    roles.resize(channels as usize, Other);
+    roles
+}
+
+fn stereo_gains(role: Role) -> (f32, f32) {
+    match role {
+        Role::Left => (1.0, 0.0),
+        Role::Right => (0.0, 1.0),
+        Role::Center | Role::Other => (MINUS_3DB, MINUS_3DB),
+        Role::LeftSurround => (MINUS_3DB, 0.0),
+        Role::RightSurround => (0.0, MINUS_3DB),
+        Role::Lfe => (0.0, 0.0),
+    }
+}
+
+/// Gain matrix with one row per output channel and one column per input
+/// channel.
+pub fn mix_matrix(input: u16, output: u16, preset: UpmixPreset) -> Vec<Vec<f32>> {
+    let (input_count, output_count) = (input as usize, output as usize);
+    let mut matrix = vec![vec![0.0; input_count]; output_count];
+
+    if input == output {
+        for (index, row) in matrix.iter_mut().enumerate() {
+            row[index] = 1.0;
+        }
+        return matrix;
+    }
+
+    let input_roles = roles(input);
+    let output_roles = roles(output);
+
+    if output == 1 {
+        for (column, role) in input_roles.iter().enumerate() {
+            let (left, right) = if input == 1 { (1.0, 1.0) } else { stereo_gains(*role) };
+            matrix[0][column] = (left + right) * 0.5;
+        }
+    } else if input <= 2 {
+        let (left, right) = if input == 1 { (0, 0) } else { (0, 1) };
+        for (row, role) in output_roles.iter().enumerate() {
+            match (role, preset) {
+                (Role::Left, _) => matrix[row][left] += 1.0,
+                (Role::Right, _) => matrix[row][right] += 1.0,
+                (Role::Center, UpmixPreset::Center) => {
+                    matrix[row][left] += 0.5 * MINUS_3DB;
+                    matrix[row][right] += 0.5 * MINUS_3DB;
+                }
+                (Role::LeftSurround, UpmixPreset::Mirror) => matrix[row][left] += 1.0,
+                (Role::RightSurround, UpmixPreset::Mirror) => matrix[row][right] += 1.0,
+                (Role::LeftSurround, UpmixPreset::Center) => matrix[row][left] += MINUS_3DB,
+                (Role::RightSurround, UpmixPreset::Center) => matrix[row][right] += MINUS_3DB,
+                _ => (),
+            }
+        }
+    } else if output > input {
+        for (index, row) in matrix.iter_mut().enumerate().take(input_count) {
+            row[index] = 1.0;
+        }
+    } else {
+        let output_left = output_roles.iter().position(|role| *role == Role::Left);
+        let output_right = output_roles.iter().position(|role| *role == Role::Right);
+        let mut taken = vec![false; output_count];
+
+        for (column, role) in input_roles.iter().enumerate() {
+            let same_role = (*role != Role::Other)
+                .then(|| {
+                    output_roles
+                        .iter()
+                        .enumerate()
+                        .position(|(row, output_role)| output_role == role && !taken[row])
+                })
+                .flatten();
+
+            match same_role {
+                Some(row) => {
+                    taken[row] = true;
+                    matrix[row][column] = 1.0;
+                }
+                None => {
+                    let (left, right) = stereo_gains(*role);
+                    if let (Some(output_left), Some(output_right)) = (output_left, output_right) {
+                        matrix[output_left][column] += left;
+                        matrix[output_right][column] += right;
+                    }
+                }
+            }
+        }
+    }
+
+    // Downmixed channels share an output, so keep it from clipping
+    for row in matrix.iter_mut() {
+        let sum: f32 = row.iter().sum();
+        if sum > 1.0 {
+            row.iter_mut().for_each(|gain| *gain /= sum);
+        }
+    }
+
+    matrix
+}
+
+/// Adapts the channel layout of a source to the output's channel count.
+pub struct ChannelMixSource<S> {
+    inner: S,
+    input_channels: u16,
+    output_channels: u16,
+    matrix: Vec<Vec<f32>>,
+    frame: Vec<f32>,
+    output_index: usize,
+}
+
+impl<S> ChannelMixSource<S>
+where
+    S: Source<Item = f32>,
+{
+    pub fn new(inner: S, output_channels: u16, preset: UpmixPreset) -> Self {
+        let input_channels = inner.channels();
+        Self {
+            matrix: mix_matrix(input_channels, output_channels, preset),
+            inner,
+            input_channels,
+            output_channels,
+            frame: vec![0.0; input_channels as usize],
+            output_index: output_channels as usize,
+        }
+    }
+}
+
+impl<S> Iterator for ChannelMixSource<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.input_channels == self.output_channels {
+            return self.inner.next();
+        }
+
+        if self.output_index >= self.output_channels as usize {
+            for sample in self.frame.iter_mut() {
+                *sample = self.inner.next()?;
+            }
+            self.output_index = 0;
+        }
+
+        let gains = &self.matrix[self.output_index];
+        self.output_index += 1;
+
+        Some(gains.iter().zip(&self.frame).map(|(gain, sample)| gain * sample).sum())
+    }
+}
+
+impl<S> Source for ChannelMixSource<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_span_len(&self) -> Option<usize> {
+        self.inner.current_span_len().map(|len| {
+            len / self.input_channels as usize * self.output_channels as usize
+        })
+    }
+
+    fn channels(&self) -> ChannelCount {
+        self.output_channels
+    }
+
+    fn sample_rate(&self) -> SampleRate {
+        self.inner.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.inner.try_seek(pos)?;
+        self.output_index = self.output_channels as usize;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    #[test]
+    fn downmixes_stereo_to_mono_at_half_gain() {
+        let source = SamplesBuffer::new(2, 44_100, vec![1.0, 0.0, 0.5, 0.5]);
+        let mixed = ChannelMixSource::new(source, 1, UpmixPreset::Front).collect::<Vec<_>>();
+
+        assert_eq!(mixed, vec![0.5, 0.5]);
+    }
+
+    #[test]
+    fn upmixes_stereo_with_presets() {
+        let front = mix_matrix(2, 6, UpmixPreset::Front);
+        assert_eq!(front[0], vec![1.0, 0.0]);
+        assert_eq!(front[1], vec![0.0, 1.0]);
+        assert!(front[2..].iter().flatten().all(|gain| *gain == 0.0));
+
+        let mirror = mix_matrix(2, 6, UpmixPreset::Mirror);
+        assert_eq!(mirror[4], vec![1.0, 0.0]);
+        assert_eq!(mirror[5], vec![0.0, 1.0]);
+        assert_eq!(mirror[3], vec![0.0, 0.0]);
+    }
+
+    #[test]
+    fn downmixes_surround_without_clipping() {
+        let matrix = mix_matrix(6, 2, UpmixPreset::Front);
+        for row in &matrix {
+            assert!((row.iter().sum::<f32>() - 1.0).abs() < 1e-6);
+        }
+        assert_eq!(matrix[0][3], 0.0);
+    }
+
+    #[test]
+    fn downmixes_to_mono_without_clipping() {
+        for input in 2..=8 {
+            let matrix = mix_matrix(input, 1, UpmixPreset::Front);
+            let sum = matrix[0].iter().sum::<f32>();
+            assert!(sum <= 1.0 + 1e-6, "{input} channels sum to {sum}");
+        }
+
+        let matrix = mix_matrix(6, 1, UpmixPreset::Front);
+        assert!((matrix[0].iter().sum::<f32>() - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn passes_multichannel_through() {
+        let source = SamplesBuffer::new(6, 48_000, (0..12).map(|x| x as f32).collect::<Vec<_>>());
+        let mixed = ChannelMixSource::new(source, 6, UpmixPreset::Center).collect::<Vec<_>>();
+
+        assert_eq!(mixed, (0..12).map(|x| x as f32).collect::<Vec<_>>());
+    }
+}
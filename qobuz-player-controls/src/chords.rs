@@ -0,0 +1,387 @@
+use std::{fs, path::Path, sync::Arc, time::Duration};
+
+use parking_lot::Mutex;
+use qobuz_player_models::Track;
+use rodio::{Source, decoder::DecoderBuilder};
+use rustfft::{Fft, FftPlanner, num_complex::Complex};
+
+use crate::{Result, database::Database, downloader::find_cached_track, error::Error};
+
+const NOTE_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+const FRAMES_PER_SECOND: u32 = 10;
+const WINDOW_SECONDS: f64 = 0.18;
+const LOWEST_HZ: f32 = 55.0;
+const HIGHEST_HZ: f32 = 1_760.0;
+/// Frames quieter than this fraction of the loudest frame count as silence.
+const SILENCE_RATIO: f32 = 0.02;
+const NO_CHORD_SCORE: f32 = 0.5;
+/// Cost of changing chord between frames, in template match units.
+const SWITCH_PENALTY: f32 = 0.8;
+
+/// Tracks being analysed, so overlapping requests do not repeat the work.
+static ANALYSING: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+
+/// Removes a track from `ANALYSING` however the analysis ends.
+struct AnalysingGuard(u32);
+
+impl Drop for AnalysingGuard {
+    fn drop(&mut self) {
+        ANALYSING.lock().retain(|id| *id != self.0);
+    }
+}
+
+/// Whole semitones to transpose chord labels by for a pitch shift, rounded
+/// to the nearest semitone.
+pub fn transpose_semitones(pitch_semitones: i16, pitch_cents: i16) -> i16 {
+    ((pitch_semitones as f32 * 100.0 + pitch_cents as f32) / 100.0).round() as i16
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Chord {
+    /// Pitch class of the root, 0 is C.
+    pub root: u8,
+    pub minor: bool,
+}
+
+impl Chord {
+    pub fn label(&self, transpose_semitones: i16) -> String {
+        let root = (self.root as i16 + transpose_semitones).rem_euclid(12) as usize;
+        format!("{}{}", NOTE_NAMES[root], if self.minor { "m" } else { "" })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ChordSegment {
+    pub start: Duration,
+    pub end: Duration,
+    /// `None` where nothing tonal is sounding.
+    pub chord: Option<Chord>,
+}
+
+impl ChordSegment {
+    pub fn label(&self, transpose_semitones: i16) -> String {
+        self.chord
+            .map(|chord| chord.label(transpose_semitones))
+            .unwrap_or_else(|| "N.C.".to_string())
+    }
+}
+
+/// Major and minor triads over the length of a track, in track time.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ChordTimeline {
+    pub segments: Vec<ChordSegment>,
+}
+
+impl ChordTimeline {
+    pub fn compute(track_path: &Path) -> Result<Self> {
+        let file = fs::File::open(track_path).map_err(|e| Error::Analysis {
+            message: format!("{}: {e}", track_path.to_string_lossy()),
+        })?;
+        let decoded = DecoderBuilder::new().with_data(file).build()?;
+
+        let channels = decoded.channels().max(1) as usize;
+        let mut estimator = ChordEstimator::new(decoded.sample_rate());
+        let mut frame = Vec::with_capacity(channels);
+        for sample in decoded {
+            frame.push(sample);
+            if frame.len() == channels {
+                estimator.push(frame.iter().sum::<f32>() / channels as f32);
+                frame.clear();
+            }
+        }
+
+        Ok(estimator.finish())
+    }
+
+    /// The segment playing at `position` and the one after it.
+    pub fn around(&self, position: Duration) -> (Option<&ChordSegment>, Option<&ChordSegment>) {
+        let index = self
+            .segments
+            .iter()
+            .position(|segment| position >= segment.start && position < segment.end);
+
+        match index {
+            Some(index) => (self.segments.get(index), self.segments.get(index + 1)),
+            None => (None, None),
+        }
+    }
+}
+
+/// Loads the stored timeline for a track, analysing the cached file when
+/// missing. Returns `None` while the track is not cached or is already being
+/// analysed.
+pub async fn track_chords(
+    database: &Database,
+    track: &Track,
+    audio_cache_dir: &Path,
+) -> Result<Option<ChordTimeline>> {
+    if let Some(timeline) = database.get_chord_timeline(track.id).await? {
+        return Ok(Some(timeline));
+    }
+
+    let Some(cached) = find_cached_track(track, audio_cache_dir) else {
+        return Ok(None);
+    };
+
+    let _guard = {
+        let mut analysing = ANALYSING.lock();
+        if analysing.contains(&track.id) {
+            return Ok(None);
+        }
+        analysing.push(track.id);
+        AnalysingGuard(track.id)
+    };
+
+    let timeline = tokio::task::spawn_blocking(move || ChordTimeline::compute(&cached))
+        .await
+        .expect("infallible")?;
+    database.set_chord_timeline(track.id, &timeline).await?;
+    Ok(Some(timeline))
+}
+
+/// Collects a chroma vector per frame from mono samples, then picks the best
+/// matching triad per frame with a penalty on changes.
+struct ChordEstimator {
+    sample_rate: u32,
+    fft: Arc<dyn Fft<f32>>,
+    fft_size: usize,
+    hop: usize,
+    hann: Vec<f32>,
+    pitch_classes: Vec<Option<usize>>,
+    window: Vec<f32>,
+    chroma: Vec<[f32; 12]>,
+    energy: Vec<f32>,
+}
+
+impl ChordEstimator {
+    fn new(sample_rate: u32) -> Self {
+        let fft_size = ((sample_rate as f64 * WINDOW_SECONDS) as usize).next_power_of_two();
+        let bin_hz = sample_rate as f32 / fft_size as f32;
+        let pitch_classes = (0..fft_size / 2)
+            .map(|bin| {
+                let frequency = bin as f32 * bin_hz;
+                (LOWEST_HZ..=HIGHEST_HZ).contains(&frequency).then(|| {
+                    let midi = 69.0 + 12.0 * (frequency / 440.0).log2();
+                    (midi.round() as i32).rem_euclid(12) as usize
+                })
+            })
+            .collect();
+        let hann = (0..fft_size)
+            .map(|i| {
+                let phase = 2.0 * std::f32::consts::PI * i as f32 / (fft_size - 1) as f32;
+                0.5 - 0.5 * phase.cos()
+            })
+            .collect();
+
+        Self {
+            sample_rate,
+            fft: FftPlanner::new().plan_fft_forward(fft_size),
+            fft_size,
+            hop: (sample_rate / FRAMES_PER_SECOND) as usize,
+            hann,
+            pitch_classes,
+            window: Vec::with_capacity(fft_size),
+            chroma: vec![],
+            energy: vec![],
+        }
+    }
+
+    fn push(&mut self, sample: f32) {
+        self.window.push(sample);
+        if self.window.len() == self.fft_size {
+            self.analyse_window();
+            self.window.drain(..self.hop);
+        }
+    }
+
+    fn analyse_window(&mut self) {
+        let mut buffer = self
+            .window
+            .iter()
+            .zip(&self.hann)
+            .map(|(sample, hann)| Complex::new(sample * hann, 0.0))
+            .collect::<Vec<_>>();
+        self.fft.process(&mut buffer);
+
+        let mut chroma = [0.0; 12];
+        for (bin, pitch_class) in buffer.iter().zip(&self.pitch_classes) {
+            if let Some(pitch_class) = pitch_class {
+                chroma[*pitch_class] += bin.norm();
+            }
+        }
+
+        let energy = chroma.iter().sum::<f32>();
+        let norm = chroma.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            chroma.iter_mut().for_each(|x| *x /= norm);
+        }
+
+        self.chroma.push(chroma);
+        self.energy.push(energy);
+    }
+
+    fn finish(self) -> ChordTimeline {
+        let loudest = self.energy.iter().copied().fold(0.0, f32::max);
+        let states = chord_states();
+        let templates = states
+            .iter()
+            .map(|chord| chord.map(template))
+            .collect::<Vec<_>>();
+
+        let scores = self
+            .chroma
+            .iter()
+            .zip(&self.energy)
+            .map(|(chroma, energy)| {
+                let silent = *energy <= loudest * SILENCE_RATIO;
+                templates
+                    .iter()
+                    .map(|template| match (template, silent) {
+                        (None, true) => 1.0,
+                        (None, false) => NO_CHORD_SCORE,
+                        (Some(_), true) => 0.0,
+                        (Some(template), false) => {
+                            chroma.iter().zip(template).map(|(a, b)| a * b).sum()
+                        }
+                    })
+                    .collect::<Vec<f32>>()
+            })
+            .collect::<Vec<_>>();
+
+        let path = viterbi(&scores);
+        let frame_duration = |frame: usize| {
+            let start = frame * self.hop + self.fft_size / 2;
+            Duration::from_secs_f64(start as f64 / self.sample_rate as f64)
+        };
+
+        let mut segments: Vec<ChordSegment> = vec![];
+        for (frame, state) in path.into_iter().enumerate() {
+            let chord = states[state];
+            match segments.last_mut() {
+                Some(segment) if segment.chord == chord => segment.end = frame_duration(frame + 1),
+                _ => segments.push(ChordSegment {
+                    start: if frame == 0 {
+                        Duration::ZERO
+                    } else {
+                        frame_duration(frame)
+                    },
+                    end: frame_duration(frame + 1),
+                    chord,
+                }),
+            }
+        }
+
+        ChordTimeline { segments }
+    }
+}
+
+/// Every triad followed by the no chord state.
+fn chord_states() -> Vec<Option<Chord>> {
+    (0..12u8)
+        .flat_map(|root| [false, true].map(|minor| Some(Chord { root, minor })))
+        .chain([None])
+        .collect()
+}
+
+fn template(chord: Chord) -> [f32; 12] {
+    let third = if chord.minor { 3 } else { 4 };
+    let mut template = [0.0; 12];
+    for interval in [0, third, 7] {
+        template[(chord.root as usize + interval) % 12] = 1.0 / 3f32.sqrt();
+    }
+    template
+}
+
+fn viterbi(scores: &[Vec<f32>]) -> Vec<usize> {
+    let Some(first) = scores.first() else {
+        return vec![];
+    };
+
+    let mut totals = first.clone();
+    let mut backtrack = vec![vec![0; first.len()]; scores.len()];
+    for (frame, frame_scores) in scores.iter().enumerate().skip(1) {
+        let (best_state, best_total) = best(&totals);
+        totals = frame_scores
+            .iter()
+            .enumerate()
+            .map(|(state, score)| {
+                let switch = best_total - SWITCH_PENALTY;
+                if totals[state] >= switch {
+                    backtrack[frame][state] = state;
+                    totals[state] + score
+                } else {
+                    backtrack[frame][state] = best_state;
+                    switch + score
+                }
+            })
+            .collect();
+    }
+
+    let mut state = best(&totals).0;
+    let mut path = vec![state; scores.len()];
+    for frame in (1..scores.len()).rev() {
+        state = backtrack[frame][state];
+        path[frame - 1] = state;
+    }
+    path
+}
+
+fn best(values: &[f32]) -> (usize, f32) {
+    values
+        .iter()
+        .copied()
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .expect("states are never empty")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triad(frequencies: [f32; 3], sample_rate: u32, seconds: f32) -> Vec<f32> {
+        (0..(sample_rate as f32 * seconds) as usize)
+            .map(|i| {
+                frequencies
+                    .iter()
+                    .map(|frequency| {
+                        0.2 * (2.0 * std::f32::consts::PI * frequency * i as f32
+                            / sample_rate as f32)
+                            .sin()
+                    })
+                    .sum()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn follows_chord_changes() {
+        let sample_rate = 22_050;
+        let mut estimator = ChordEstimator::new(sample_rate);
+        let c_major = triad([261.63, 329.63, 392.0], sample_rate, 3.0);
+        let a_minor = triad([220.0, 261.63, 329.63], sample_rate, 3.0);
+        for sample in c_major.into_iter().chain(a_minor) {
+            estimator.push(sample);
+        }
+
+        let timeline = estimator.finish();
+        let labels = timeline
+            .segments
+            .iter()
+            .map(|segment| segment.label(0))
+            .collect::<Vec<_>>();
+        assert_eq!(labels, ["C", "Am"]);
+
+        let change = timeline.segments[1].start.as_secs_f32();
+        assert!((change - 3.0).abs() < 0.5, "{change}");
+
+        let (current, next) = timeline.around(Duration::from_secs(1));
+        assert_eq!(current.unwrap().label(2), "D");
+        assert_eq!(next.unwrap().label(2), "Bm");
+        assert_eq!(current.unwrap().label(transpose_semitones(1, 80)), "D");
+        assert_eq!(current.unwrap().label(transpose_semitones(0, -40)), "C");
+        assert_eq!(current.unwrap().label(transpose_semitones(0, -60)), "B");
+    }
+}
@@ -0,0 +1,1037 @@
+use std::{
+    ffi::{CStr, CString, c_char, c_void},
+    fs,
+    path::{Path, PathBuf},
+    ptr,
+    sync::Arc,
+    time::Duration,
+};
+
+use libloading::Library;
+use parking_lot::{Mutex, RwLock};
+use rodio::{ChannelCount, SampleRate, Source, source::SeekError};
+
+use crate::{Result, database::DatabaseClapPlugin, error::Error};
+
+pub const DEFAULT_CHAIN: &str = "default";
+pub const PLUGIN_EXTENSION: &str = "clap";
+
+const BLOCK_FRAMES: usize = 512;
+const CHANNELS: usize = 2;
+
+const PLUGIN_FACTORY_ID: &CStr = c"clap.plugin-factory";
+const EXT_PARAMS: &CStr = c"clap.params";
+const EXT_STATE: &CStr = c"clap.state";
+const EXT_AUDIO_PORTS: &CStr = c"clap.audio-ports";
+
+const EVENT_PARAM_VALUE: u16 = 5;
+const PROCESS_ERROR: i32 = 0;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct ClapVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub revision: u32,
+}
+
+pub const CLAP_VERSION: ClapVersion = ClapVersion {
+    major: 1,
+    minor: 2,
+    revision: 2,
+};
+
+#[repr(C)]
+pub struct ClapPluginEntry {
+    pub clap_version: ClapVersion,
+    pub init: unsafe extern "C" fn(plugin_path: *const c_char) -> bool,
+    pub deinit: unsafe extern "C" fn(),
+    pub get_factory: unsafe extern "C" fn(factory_id: *const c_char) -> *const c_void,
+}
+
+#[repr(C)]
+pub struct ClapPluginFactory {
+    pub get_plugin_count: unsafe extern "C" fn(factory: *const ClapPluginFactory) -> u32,
+    pub get_plugin_descriptor: unsafe extern "C" fn(
+        factory: *const ClapPluginFactory,
+        index: u32,
+    ) -> *const ClapPluginDescriptorRaw,
+    pub create_plugin: unsafe extern "C" fn(
+        factory: *const ClapPluginFactory,
+        host: *const ClapHost,
+        plugin_id: *const c_char,
+    ) -> *const ClapPluginRaw,
+}
+
+#[repr(C)]
+pub struct ClapPluginDescriptorRaw {
+    pub clap_version: ClapVersion,
+    pub id: *const c_char,
+    pub name: *const c_char,
+    pub vendor: *const c_char,
+    pub url: *const c_char,
+    pub manual_url: *const c_char,
+    pub support_url: *const c_char,
+    pub version: *const c_char,
+    pub description: *const c_char,
+    pub features: *const *const c_char,
+}
+
+#[repr(C)]
+pub struct ClapHost {
+    pub clap_version: ClapVersion,
+    pub host_data: *mut c_void,
+    pub name: *const c_char,
+    pub vendor: *const c_char,
+    pub url: *const c_char,
+    pub version: *const c_char,
+    pub get_extension:
+        unsafe extern "C" fn(host: *const ClapHost, extension_id: *const c_char) -> *const c_void,
+    pub request_restart: unsafe extern "C" fn(host: *const ClapHost),
+    pub request_process: unsafe extern "C" fn(host: *const ClapHost),
+    pub request_callback: unsafe extern "C" fn(host: *const ClapHost),
+}
+
+#[repr(C)]
+pub struct ClapPluginRaw {
+    pub desc: *const ClapPluginDescriptorRaw,
+    pub plugin_data: *mut c_void,
+    pub init: unsafe extern "C" fn(plugin: *const ClapPluginRaw) -> bool,
+    pub destroy: unsafe extern "C" fn(plugin: *const ClapPluginRaw),
+    pub activate: unsafe extern "C" fn(
+        plugin: *const ClapPluginRaw,
+        sample_rate: f64,
+        min_frames_count: u32,
+        max_frames_count: u32,
+    ) -> bool,
+    pub deactivate: unsafe extern "C" fn(plugin: *const ClapPluginRaw),
+    pub start_processing: unsafe extern "C" fn(plugin: *const ClapPluginRaw) -> bool,
+    pub stop_processing: unsafe extern "C" fn(plugin: *const ClapPluginRaw),
+    pub reset: unsafe extern "C" fn(plugin: *const ClapPluginRaw),
+    pub process:
+        unsafe extern "C" fn(plugin: *const ClapPluginRaw, process: *const ClapProcess) -> i32,
+    pub get_extension:
+        unsafe extern "C" fn(plugin: *const ClapPluginRaw, id: *const c_char) -> *const c_void,
+    pub on_main_thread: unsafe extern "C" fn(plugin: *const ClapPluginRaw),
+}
+
+#[repr(C)]
+pub struct ClapAudioBuffer {
+    pub data32: *mut *mut f32,
+    pub data64: *mut *mut f64,
+    pub channel_count: u32,
+    pub latency: u32,
+    pub constant_mask: u64,
+}
+
+#[repr(C)]
+pub struct ClapProcess {
+    pub steady_time: i64,
+    pub frames_count: u32,
+    pub transport: *const c_void,
+    pub audio_inputs: *const ClapAudioBuffer,
+    pub audio_outputs: *mut ClapAudioBuffer,
+    pub audio_inputs_count: u32,
+    pub audio_outputs_count: u32,
+    pub in_events: *const ClapInputEvents,
+    pub out_events: *const ClapOutputEvents,
+}
+
+#[repr(C)]
+pub struct ClapEventHeader {
+    pub size: u32,
+    pub time: u32,
+    pub space_id: u16,
+    pub event_type: u16,
+    pub flags: u32,
+}
+
+#[repr(C)]
+pub struct ClapEventParamValue {
+    pub header: ClapEventHeader,
+    pub param_id: u32,
+    pub cookie: *mut c_void,
+    pub note_id: i32,
+    pub port_index: i16,
+    pub channel: i16,
+    pub key: i16,
+    pub value: f64,
+}
+
+#[repr(C)]
+pub struct ClapInputEvents {
+    pub ctx: *mut c_void,
+    pub size: unsafe extern "C" fn(list: *const ClapInputEvents) -> u32,
+    pub get:
+        unsafe extern "C" fn(list: *const ClapInputEvents, index: u32) -> *const ClapEventHeader,
+}
+
+#[repr(C)]
+pub struct ClapOutputEvents {
+    pub ctx: *mut c_void,
+    pub try_push:
+        unsafe extern "C" fn(list: *const ClapOutputEvents, event: *const ClapEventHeader) -> bool,
+}
+
+#[repr(C)]
+pub struct ClapParamInfo {
+    pub id: u32,
+    pub flags: u32,
+    pub cookie: *mut c_void,
+    pub name: [c_char; 256],
+    pub module: [c_char; 1024],
+    pub min_value: f64,
+    pub max_value: f64,
+    pub default_value: f64,
+}
+
+#[repr(C)]
+pub struct ClapPluginParams {
+    pub count: unsafe extern "C" fn(plugin: *const ClapPluginRaw) -> u32,
+    pub get_info: unsafe extern "C" fn(
+        plugin: *const ClapPluginRaw,
+        param_index: u32,
+        param_info: *mut ClapParamInfo,
+    ) -> bool,
+    pub get_value:
+        unsafe extern "C" fn(plugin: *const ClapPluginRaw, param_id: u32, value: *mut f64) -> bool,
+    pub value_to_text: unsafe extern "C" fn(
+        plugin: *const ClapPluginRaw,
+        param_id: u32,
+        value: f64,
+        buffer: *mut c_char,
+        capacity: u32,
+    ) -> bool,
+    pub text_to_value: unsafe extern "C" fn(
+        plugin: *const ClapPluginRaw,
+        param_id: u32,
+        text: *const c_char,
+        value: *mut f64,
+    ) -> bool,
+    pub flush: unsafe extern "C" fn(
+        plugin: *const ClapPluginRaw,
+        in_events: *const ClapInputEvents,
+        out_events: *const ClapOutputEvents,
+    ),
+}
+
+#[repr(C)]
+pub struct ClapAudioPortInfo {
+    pub id: u32,
+    pub name: [c_char; 256],
+    pub flags: u32,
+    pub channel_count: u32,
+    pub port_type: *const c_char,
+    pub in_place_pair: u32,
+}
+
+#[repr(C)]
+pub struct ClapPluginAudioPorts {
+    pub count: unsafe extern "C" fn(plugin: *const ClapPluginRaw, is_input: bool) -> u32,
+    pub get: unsafe extern "C" fn(
+        plugin: *const ClapPluginRaw,
+        index: u32,
+        is_input: bool,
+        info: *mut ClapAudioPortInfo,
+    ) -> bool,
+}
+
+#[repr(C)]
+pub struct ClapOutputStream {
+    pub ctx: *mut c_void,
+    pub write: unsafe extern "C" fn(
+        stream: *const ClapOutputStream,
+        buffer: *const c_void,
+        size: u64,
+    ) -> i64,
+}
+
+#[repr(C)]
+pub struct ClapInputStream {
+    pub ctx: *mut c_void,
+    pub read:
+        unsafe extern "C" fn(stream: *const ClapInputStream, buffer: *mut c_void, size: u64) -> i64,
+}
+
+#[repr(C)]
+pub struct ClapPluginState {
+    pub save:
+        unsafe extern "C" fn(plugin: *const ClapPluginRaw, stream: *const ClapOutputStream) -> bool,
+    pub load:
+        unsafe extern "C" fn(plugin: *const ClapPluginRaw, stream: *const ClapInputStream) -> bool,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ClapPluginDescriptor {
+    pub path: PathBuf,
+    pub id: String,
+    pub name: String,
+    pub vendor: String,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ClapParam {
+    pub id: u32,
+    pub name: String,
+    pub min: f64,
+    pub max: f64,
+    pub default: f64,
+    pub value: f64,
+}
+
+struct ClapLibrary {
+    _library: Library,
+    entry: *const ClapPluginEntry,
+    factory: *const ClapPluginFactory,
+}
+
+// The entry and factory are immutable tables owned by the loaded library.
+unsafe impl Send for ClapLibrary {}
+unsafe impl Sync for ClapLibrary {}
+
+impl ClapLibrary {
+    fn load(path: &Path) -> Result<Self> {
+        let library = unsafe { Library::new(path) }
+            .map_err(|e| clap_error(path, &format!("unable to load: {e}")))?;
+        let entry = unsafe { library.get::<*const ClapPluginEntry>(b"clap_entry\0") }
+            .map(|symbol| *symbol)
+            .map_err(|_| clap_error(path, "not a CLAP plugin"))?;
+
+        let version = unsafe { (*entry).clap_version };
+        if version.major < 1 {
+            return Err(clap_error(path, "unsupported CLAP version"));
+        }
+
+        let plugin_path = CString::new(path.to_string_lossy().as_bytes()).expect("infallible");
+        if !unsafe { ((*entry).init)(plugin_path.as_ptr()) } {
+            return Err(clap_error(path, "plugin failed to initialize"));
+        }
+
+        let factory = unsafe { ((*entry).get_factory)(PLUGIN_FACTORY_ID.as_ptr()) }
+            as *const ClapPluginFactory;
+        if factory.is_null() {
+            unsafe { ((*entry).deinit)() };
+            return Err(clap_error(path, "no plugin factory"));
+        }
+
+        Ok(Self {
+            _library: library,
+            entry,
+            factory,
+        })
+    }
+
+    fn descriptors(&self, path: &Path) -> Vec<ClapPluginDescriptor> {
+        let count = unsafe { ((*self.factory).get_plugin_count)(self.factory) };
+        (0..count)
+            .filter_map(|index| {
+                let descriptor =
+                    unsafe { ((*self.factory).get_plugin_descriptor)(self.factory, index) };
+                (!descriptor.is_null()).then(|| unsafe { descriptor_from_raw(path, descriptor) })
+            })
+            .collect()
+    }
+}
+
+impl Drop for ClapLibrary {
+    fn drop(&mut self) {
+        unsafe { ((*self.entry).deinit)() };
+    }
+}
+
+unsafe fn string_from_raw(value: *const c_char) -> String {
+    if value.is_null() {
+        return String::new();
+    }
+    unsafe { CStr::from_ptr(value) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+unsafe fn descriptor_from_raw(
+    path: &Path,
+    descriptor: *const ClapPluginDescriptorRaw,
+) -> ClapPluginDescriptor {
+    unsafe {
+        ClapPluginDescriptor {
+            path: path.to_path_buf(),
+            id: string_from_raw((*descriptor).id),
+            name: string_from_raw((*descriptor).name),
+            vendor: string_from_raw((*descriptor).vendor),
+        }
+    }
+}
+
+pub fn scan_plugins(directory: &Path) -> Vec<ClapPluginDescriptor> {
+    let Ok(entries) = fs::read_dir(directory) else {
+        return vec![];
+    };
+
+    let mut paths = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.is_file()
+                && path
+                    .extension()
+                    .is_some_and(|extension| extension == PLUGIN_EXTENSION)
+        })
+        .collect::<Vec<_>>();
+    paths.sort();
+
+    paths
+        .into_iter()
+        .flat_map(|path| match ClapLibrary::load(&path) {
+            Ok(library) => library.descriptors(&path),
+            Err(e) => {
+                tracing::warn!("{}", e);
+                vec![]
+            }
+        })
+        .collect()
+}
+
+unsafe extern "C" fn host_get_extension(
+    _host: *const ClapHost,
+    _extension_id: *const c_char,
+) -> *const c_void {
+    ptr::null()
+}
+
+unsafe extern "C" fn host_request(_host: *const ClapHost) {}
+
+unsafe extern "C" fn input_events_size(list: *const ClapInputEvents) -> u32 {
+    let events = unsafe { &*((*list).ctx as *const Vec<ClapEventParamValue>) };
+    events.len() as u32
+}
+
+unsafe extern "C" fn input_events_get(
+    list: *const ClapInputEvents,
+    index: u32,
+) -> *const ClapEventHeader {
+    let events = unsafe { &*((*list).ctx as *const Vec<ClapEventParamValue>) };
+    events
+        .get(index as usize)
+        .map(|event| &event.header as *const ClapEventHeader)
+        .unwrap_or(ptr::null())
+}
+
+unsafe extern "C" fn output_events_try_push(
+    _list: *const ClapOutputEvents,
+    _event: *const ClapEventHeader,
+) -> bool {
+    true
+}
+
+unsafe extern "C" fn state_write(
+    stream: *const ClapOutputStream,
+    buffer: *const c_void,
+    size: u64,
+) -> i64 {
+    let data = unsafe { &mut *((*stream).ctx as *mut Vec<u8>) };
+    let bytes = unsafe { std::slice::from_raw_parts(buffer as *const u8, size as usize) };
+    data.extend_from_slice(bytes);
+    size as i64
+}
+
+unsafe extern "C" fn state_read(
+    stream: *const ClapInputStream,
+    buffer: *mut c_void,
+    size: u64,
+) -> i64 {
+    let data = unsafe { &mut *((*stream).ctx as *mut &[u8]) };
+    let count = data.len().min(size as usize);
+    unsafe { ptr::copy_nonoverlapping(data.as_ptr(), buffer as *mut u8, count) };
+    *data = &data[count..];
+    count as i64
+}
+
+fn param_event(param_id: u32, value: f64) -> ClapEventParamValue {
+    ClapEventParamValue {
+        header: ClapEventHeader {
+            size: size_of::<ClapEventParamValue>() as u32,
+            time: 0,
+            space_id: 0,
+            event_type: EVENT_PARAM_VALUE,
+            flags: 0,
+        },
+        param_id,
+        cookie: ptr::null_mut(),
+        note_id: -1,
+        port_index: -1,
+        channel: -1,
+        key: -1,
+        value,
+    }
+}
+
+/// Whether the plugin has exactly one stereo input and one stereo output,
+/// the only layout the chain feeds.
+unsafe fn has_stereo_ports(plugin: *const ClapPluginRaw) -> bool {
+    let ports = unsafe { ((*plugin).get_extension)(plugin, EXT_AUDIO_PORTS.as_ptr()) }
+        as *const ClapPluginAudioPorts;
+    if ports.is_null() {
+        return false;
+    }
+
+    [true, false].into_iter().all(|is_input| {
+        let mut info = ClapAudioPortInfo {
+            id: 0,
+            name: [0; 256],
+            flags: 0,
+            channel_count: 0,
+            port_type: ptr::null(),
+            in_place_pair: 0,
+        };
+        unsafe {
+            ((*ports).count)(plugin, is_input) == 1
+                && ((*ports).get)(plugin, 0, is_input, &mut info)
+                && info.channel_count == CHANNELS as u32
+        }
+    })
+}
+
+pub struct ClapPlugin {
+    descriptor: ClapPluginDescriptor,
+    plugin: *const ClapPluginRaw,
+    params: *const ClapPluginParams,
+    state: *const ClapPluginState,
+    sample_rate: Option<u32>,
+    processing: bool,
+    pending: Vec<ClapEventParamValue>,
+    steady_time: i64,
+    _host: Box<ClapHost>,
+    library: Arc<ClapLibrary>,
+}
+
+// A plugin instance is only used behind a mutex, one thread at a time.
+unsafe impl Send for ClapPlugin {}
+
+impl ClapPlugin {
+    pub fn load(path: &Path, plugin_id: &str) -> Result<Self> {
+        let library = Arc::new(ClapLibrary::load(path)?);
+        let descriptor = library
+            .descriptors(path)
+            .into_iter()
+            .find(|descriptor| descriptor.id == plugin_id)
+            .ok_or_else(|| clap_error(path, &format!("no plugin with id '{plugin_id}'")))?;
+
+        Self::create(library, descriptor)
+    }
+
+    fn create(library: Arc<ClapLibrary>, descriptor: ClapPluginDescriptor) -> Result<Self> {
+        let path = descriptor.path.as_path();
+        let host = Box::new(ClapHost {
+            clap_version: CLAP_VERSION,
+            host_data: ptr::null_mut(),
+            name: c"qobuz-player".as_ptr(),
+            vendor: c"qobuz-player".as_ptr(),
+            url: c"https://github.com/sofusa/qobuz-player".as_ptr(),
+            version: c"1.0".as_ptr(),
+            get_extension: host_get_extension,
+            request_restart: host_request,
+            request_process: host_request,
+            request_callback: host_request,
+        });
+
+        let plugin_id = CString::new(descriptor.id.as_str()).expect("infallible");
+        let factory = library.factory;
+        let plugin =
+            unsafe { ((*factory).create_plugin)(factory, host.as_ref(), plugin_id.as_ptr()) };
+        if plugin.is_null() {
+            return Err(clap_error(path, "unable to create plugin"));
+        }
+        if !unsafe { ((*plugin).init)(plugin) } {
+            unsafe { ((*plugin).destroy)(plugin) };
+            return Err(clap_error(path, "plugin failed to initialize"));
+        }
+        if !unsafe { has_stereo_ports(plugin) } {
+            unsafe { ((*plugin).destroy)(plugin) };
+            return Err(clap_error(path, "plugin does not have stereo audio ports"));
+        }
+
+        let params = unsafe { ((*plugin).get_extension)(plugin, EXT_PARAMS.as_ptr()) }
+            as *const ClapPluginParams;
+        let state = unsafe { ((*plugin).get_extension)(plugin, EXT_STATE.as_ptr()) }
+            as *const ClapPluginState;
+
+        Ok(Self {
+            descriptor,
+            plugin,
+            params,
+            state,
+            sample_rate: None,
+            processing: false,
+            pending: vec![],
+            steady_time: 0,
+            _host: host,
+            library,
+        })
+    }
+
+    /// Creates another instance of the plugin with the same state and
+    /// parameter values.
+    fn duplicate(&self) -> Result<Self> {
+        let mut plugin = Self::create(self.library.clone(), self.descriptor.clone())?;
+        if let Some(state) = self.save_state() {
+            plugin.load_state(&state);
+        }
+        for param in self.params() {
+            plugin.set_param(param.id, param.value);
+        }
+
+        Ok(plugin)
+    }
+
+    pub fn descriptor(&self) -> &ClapPluginDescriptor {
+        &self.descriptor
+    }
+
+    pub fn params(&self) -> Vec<ClapParam> {
+        if self.params.is_null() {
+            return vec![];
+        }
+
+        let params = unsafe { &*self.params };
+        let count = unsafe { (params.count)(self.plugin) };
+        (0..count)
+            .filter_map(|index| {
+                let mut info = ClapParamInfo {
+                    id: 0,
+                    flags: 0,
+                    cookie: ptr::null_mut(),
+                    name: [0; 256],
+                    module: [0; 1024],
+                    min_value: 0.0,
+                    max_value: 0.0,
+                    default_value: 0.0,
+                };
+                if !unsafe { (params.get_info)(self.plugin, index, &mut info) } {
+                    return None;
+                }
+
+                let mut value = info.default_value;
+                unsafe { (params.get_value)(self.plugin, info.id, &mut value) };
+                let value = self
+                    .pending
+                    .iter()
+                    .rev()
+                    .find(|event| event.param_id == info.id)
+                    .map(|event| event.value)
+                    .unwrap_or(value);
+
+                Some(ClapParam {
+                    id: info.id,
+                    name: unsafe { string_from_raw(info.name.as_ptr()) },
+                    min: info.min_value,
+                    max: info.max_value,
+                    default: info.default_value,
+                    value,
+                })
+            })
+            .collect()
+    }
+
+    /// Queues a parameter change. An active plugin receives it with the
+    /// next processed block, since flushing is only allowed while inactive.
+    pub fn set_param(&mut self, param_id: u32, value: f64) {
+        self.pending.push(param_event(param_id, value));
+        if self.sample_rate.is_none() {
+            self.flush();
+        }
+    }
+
+    pub fn save_state(&self) -> Option<Vec<u8>> {
+        if self.state.is_null() {
+            return None;
+        }
+
+        let mut data = Vec::new();
+        let stream = ClapOutputStream {
+            ctx: &mut data as *mut Vec<u8> as *mut c_void,
+            write: state_write,
+        };
+        unsafe { ((*self.state).save)(self.plugin, &stream) }.then_some(data)
+    }
+
+    pub fn load_state(&mut self, data: &[u8]) -> bool {
+        if self.state.is_null() {
+            return false;
+        }
+
+        let mut remaining = data;
+        let stream = ClapInputStream {
+            ctx: &mut remaining as *mut &[u8] as *mut c_void,
+            read: state_read,
+        };
+        unsafe { ((*self.state).load)(self.plugin, &stream) }
+    }
+
+    pub fn activate(&mut self, sample_rate: u32) -> bool {
+        if self.sample_rate == Some(sample_rate) {
+            return true;
+        }
+
+        self.deactivate();
+        let activated = unsafe {
+            ((*self.plugin).activate)(self.plugin, sample_rate as f64, 1, BLOCK_FRAMES as u32)
+        };
+        if activated {
+            self.sample_rate = Some(sample_rate);
+        }
+        activated
+    }
+
+    fn deactivate(&mut self) {
+        if self.processing {
+            unsafe { ((*self.plugin).stop_processing)(self.plugin) };
+            self.processing = false;
+        }
+        if self.sample_rate.take().is_some() {
+            unsafe { ((*self.plugin).deactivate)(self.plugin) };
+        }
+    }
+
+    fn flush(&mut self) {
+        if self.params.is_null() {
+            self.pending.clear();
+            return;
+        }
+
+        let (in_events, out_events) = self.event_lists();
+        unsafe { ((*self.params).flush)(self.plugin, &in_events, &out_events) };
+        self.pending.clear();
+    }
+
+    fn event_lists(&mut self) -> (ClapInputEvents, ClapOutputEvents) {
+        (
+            ClapInputEvents {
+                ctx: &mut self.pending as *mut Vec<ClapEventParamValue> as *mut c_void,
+                size: input_events_size,
+                get: input_events_get,
+            },
+            ClapOutputEvents {
+                ctx: ptr::null_mut(),
+                try_push: output_events_try_push,
+            },
+        )
+    }
+
+    /// Processes planar stereo audio from `input` into `output`. Returns
+    /// false if the plugin is not active or failed, leaving `output` as is.
+    pub fn process(
+        &mut self,
+        input: &mut [Vec<f32>; 2],
+        output: &mut [Vec<f32>; 2],
+        frames: usize,
+    ) -> bool {
+        if self.sample_rate.is_none() {
+            return false;
+        }
+        if !self.processing {
+            self.processing = unsafe { ((*self.plugin).start_processing)(self.plugin) };
+            if !self.processing {
+                return false;
+            }
+        }
+
+        let mut input_channels = input.each_mut().map(|channel| channel.as_mut_ptr());
+        let mut output_channels = output.each_mut().map(|channel| channel.as_mut_ptr());
+        let audio_input = ClapAudioBuffer {
+            data32: input_channels.as_mut_ptr(),
+            data64: ptr::null_mut(),
+            channel_count: CHANNELS as u32,
+            latency: 0,
+            constant_mask: 0,
+        };
+        let mut audio_output = ClapAudioBuffer {
+            data32: output_channels.as_mut_ptr(),
+            data64: ptr::null_mut(),
+            channel_count: CHANNELS as u32,
+            latency: 0,
+            constant_mask: 0,
+        };
+
+        let (in_events, out_events) = self.event_lists();
+        let process = ClapProcess {
+            steady_time: self.steady_time,
+            frames_count: frames as u32,
+            transport: ptr::null(),
+            audio_inputs: &audio_input,
+            audio_outputs: &mut audio_output,
+            audio_inputs_count: 1,
+            audio_outputs_count: 1,
+            in_events: &in_events,
+            out_events: &out_events,
+        };
+
+        let status = unsafe { ((*self.plugin).process)(self.plugin, &process) };
+        self.pending.clear();
+        self.steady_time += frames as i64;
+        status != PROCESS_ERROR
+    }
+
+    fn reset(&mut self) {
+        if self.sample_rate.is_some() {
+            unsafe { ((*self.plugin).reset)(self.plugin) };
+        }
+    }
+}
+
+impl Drop for ClapPlugin {
+    fn drop(&mut self) {
+        self.deactivate();
+        unsafe { ((*self.plugin).destroy)(self.plugin) };
+    }
+}
+
+#[derive(Clone)]
+pub struct ClapChainEntry {
+    pub position: u32,
+    /// Inactive instance holding the state and parameter values that each
+    /// source's instance starts from.
+    pub plugin: Arc<Mutex<ClapPlugin>>,
+}
+
+impl ClapChainEntry {
+    pub fn new(position: u32, plugin: ClapPlugin) -> Self {
+        Self {
+            position,
+            plugin: Arc::new(Mutex::new(plugin)),
+        }
+    }
+
+    /// Loads a saved plugin and restores its state and parameter values.
+    pub fn restore(saved: &DatabaseClapPlugin) -> Result<Self> {
+        let mut plugin = ClapPlugin::load(&saved.path, &saved.plugin_id)?;
+        if let Some(state) = &saved.state {
+            plugin.load_state(state);
+        }
+        for param in &saved.params {
+            plugin.set_param(param.id, param.value);
+        }
+
+        Ok(Self::new(saved.position, plugin))
+    }
+}
+
+/// Plugin instance activated for a single source, so tracks at different
+/// sample rates never share one.
+struct ActiveInstance {
+    position: u32,
+    /// Only locked by the audio thread while the source is alive.
+    plugin: Mutex<ClapPlugin>,
+    /// Parameter changes waiting for the next processed block.
+    events: Mutex<Vec<(u32, f64)>>,
+}
+
+/// Insert chain shared between the player, which edits it, and the sources
+/// it builds for each track.
+#[derive(Clone, Default)]
+pub struct ClapChain {
+    entries: Arc<RwLock<Vec<ClapChainEntry>>>,
+    /// Instances handed to sources. Kept here so parameter changes reach them
+    /// and so they are destroyed off the audio thread once their source is.
+    instances: Arc<Mutex<Vec<Arc<ActiveInstance>>>>,
+}
+
+impl ClapChain {
+    pub fn set_entries(&self, entries: Vec<ClapChainEntry>) {
+        *self.entries.write() = entries;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.read().is_empty()
+    }
+
+    /// Sets a parameter of the plugin at `position` and of every source
+    /// playing it. Returns the plugin's state and parameters to save.
+    pub fn set_param(
+        &self,
+        position: u32,
+        param_id: u32,
+        value: f64,
+    ) -> Option<(Option<Vec<u8>>, Vec<ClapParam>)> {
+        let plugin = self
+            .entries
+            .read()
+            .iter()
+            .find(|entry| entry.position == position)
+            .map(|entry| entry.plugin.clone())?;
+
+        for instance in self
+            .instances
+            .lock()
+            .iter()
+            .filter(|instance| instance.position == position)
+        {
+            instance.events.lock().push((param_id, value));
+        }
+
+        let mut plugin = plugin.lock();
+        plugin.set_param(param_id, value);
+        Some((plugin.save_state(), plugin.params()))
+    }
+
+    /// Creates and activates an instance of every plugin for a new source.
+    fn activate(&self, sample_rate: u32) -> Vec<Arc<ActiveInstance>> {
+        let mut instances = self.instances.lock();
+        instances.retain(|instance| Arc::strong_count(instance) > 1);
+
+        let entries = self.entries.read().clone();
+        let activated = entries
+            .iter()
+            .filter_map(|entry| {
+                let plugin = entry.plugin.lock();
+                let mut instance = match plugin.duplicate() {
+                    Ok(instance) => instance,
+                    Err(e) => {
+                        tracing::warn!("{}", e);
+                        return None;
+                    }
+                };
+                if !instance.activate(sample_rate) {
+                    tracing::warn!("Unable to activate plugin {}", plugin.descriptor().name);
+                    return None;
+                }
+
+                Some(Arc::new(ActiveInstance {
+                    position: entry.position,
+                    plugin: Mutex::new(instance),
+                    events: Default::default(),
+                }))
+            })
+            .collect::<Vec<_>>();
+
+        instances.extend(activated.iter().cloned());
+        activated
+    }
+}
+
+pub struct ClapChainSource<S> {
+    inner: S,
+    instances: Vec<Arc<ActiveInstance>>,
+    input: [Vec<f32>; 2],
+    output: [Vec<f32>; 2],
+    frames: usize,
+    index: usize,
+    exhausted: bool,
+}
+
+impl<S> ClapChainSource<S>
+where
+    S: Source<Item = f32>,
+{
+    pub fn new(inner: S, chain: &ClapChain) -> Self {
+        let instances = chain.activate(inner.sample_rate());
+
+        Self {
+            inner,
+            instances,
+            input: [vec![0.0; BLOCK_FRAMES], vec![0.0; BLOCK_FRAMES]],
+            output: [vec![0.0; BLOCK_FRAMES], vec![0.0; BLOCK_FRAMES]],
+            frames: 0,
+            index: 0,
+            exhausted: false,
+        }
+    }
+
+    fn fill(&mut self) {
+        self.frames = 0;
+        self.index = 0;
+
+        while self.frames < BLOCK_FRAMES {
+            let (Some(left), Some(right)) = (self.inner.next(), self.inner.next()) else {
+                self.exhausted = true;
+                break;
+            };
+            self.input[0][self.frames] = left;
+            self.input[1][self.frames] = right;
+            self.frames += 1;
+        }
+
+        for instance in &self.instances {
+            // Never wait on the audio thread; the block passes through instead
+            let Some(mut plugin) = instance.plugin.try_lock() else {
+                continue;
+            };
+            if let Some(mut events) = instance.events.try_lock() {
+                plugin.pending.extend(
+                    events
+                        .drain(..)
+                        .map(|(param_id, value)| param_event(param_id, value)),
+                );
+            }
+            if plugin.process(&mut self.input, &mut self.output, self.frames) {
+                std::mem::swap(&mut self.input, &mut self.output);
+            }
+        }
+    }
+}
+
+impl<S> Iterator for ClapChainSource<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.instances.is_empty() || self.inner.channels() as usize != CHANNELS {
+            return self.inner.next();
+        }
+
+        if self.index >= self.frames * CHANNELS {
+            if self.exhausted {
+                return None;
+            }
+            self.fill();
+            if self.frames == 0 {
+                return None;
+            }
+        }
+
+        let sample = self.input[self.index % CHANNELS][self.index / CHANNELS];
+        self.index += 1;
+        Some(sample)
+    }
+}
+
+impl<S> Source for ClapChainSource<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_span_len(&self) -> Option<usize> {
+        self.inner.current_span_len()
+    }
+
+    fn channels(&self) -> ChannelCount {
+        self.inner.channels()
+    }
+
+    fn sample_rate(&self) -> SampleRate {
+        self.inner.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.inner.try_seek(pos)?;
+        self.frames = 0;
+        self.index = 0;
+        self.exhausted = false;
+        for instance in &self.instances {
+            if let Some(mut plugin) = instance.plugin.try_lock() {
+                plugin.reset();
+            }
+        }
+        Ok(())
+    }
+}
+
+fn clap_error(path: &Path, message: &str) -> Error {
+    Error::Plugin {
+        message: format!("{}: {message}", path.to_string_lossy()),
+    }
+}
@@ -137,6 +137,15 @@ impl Client {
         Ok(client.track_url(track_id).await?)
     }
 
+    pub async fn track_url_with_quality(
+        &self,
+        track_id: u32,
+        audio_quality: &AudioQuality,
+    ) -> Result<TrackURL> {
+        let client = self.get_client().await?;
+        Ok(client.track_url_with_quality(track_id, audio_quality).await?)
+    }
+
     pub async fn album(&self, id: &str) -> Result<Album> {
         if let Some(cache) = self.album_cache.get(id).await {
             return Ok(cache);
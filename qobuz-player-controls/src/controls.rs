@@ -1,5 +1,23 @@
 use std::time::Duration;
 
+use tokio::sync::oneshot;
+
+use crate::{
+    Result, channel_mix::UpmixPreset, crossfeed::CrossfeedPreset, error::Error,
+    output::OutputSampleFormat, pitch::PitchProbe, tracklist::RepeatMode,
+};
+
+/// Longest sleep timer accepted.
+pub const MAX_SLEEP_TIMER: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SleepTimer {
+    /// Pause once this much time has passed.
+    After(Duration),
+    /// Pause when the last track in the tracklist ends.
+    EndOfTracklist,
+}
+
 #[derive(Debug)]
 pub enum ControlCommand {
     Album {
@@ -11,6 +29,11 @@ pub enum ControlCommand {
         index: usize,
         shuffle: bool,
     },
+    LocalPlaylist {
+        id: i64,
+        index: usize,
+        shuffle: bool,
+    },
     ArtistTopTracks {
         artist_id: u32,
         index: usize,
@@ -47,12 +70,62 @@ pub enum ControlCommand {
     ReorderQueue {
         new_order: Vec<usize>,
     },
+    SetRepeatMode {
+        mode: RepeatMode,
+    },
+    SetShuffle {
+        enabled: bool,
+    },
+    SetSleepTimer {
+        timer: Option<SleepTimer>,
+    },
+    RampUpVolume {
+        duration: Duration,
+    },
+    SetStopAfter {
+        position: Option<usize>,
+    },
+    SaveQueue {
+        name: String,
+        respond: oneshot::Sender<()>,
+    },
+    LoadSavedQueue {
+        name: String,
+        append: bool,
+    },
     SetAudioDevice {
         device_name: Option<String>,
     },
     SetTimeStretch { ratio: f32 },
     SetPitch { semitones: i16 },
     SetPitchCents { cents: i16 },
+    SetBitPerfect {
+        enabled: bool,
+        respond: oneshot::Sender<bool>,
+    },
+    SetOutputSampleFormat {
+        device_name: Option<String>,
+        sample_format: Option<OutputSampleFormat>,
+    },
+    SetUpmixPreset { preset: UpmixPreset },
+    SetCrossfeedDevice {
+        device_name: String,
+        enabled: Option<bool>,
+    },
+    SetCrossfeedPreset {
+        preset: CrossfeedPreset,
+        strength: f32,
+    },
+    ReloadClapChain,
+    SetClapParameter {
+        position: u32,
+        param_id: u32,
+        value: f64,
+        respond: oneshot::Sender<Result<()>>,
+    },
+    ProbePitch {
+        respond: oneshot::Sender<Result<PitchProbe>>,
+    },
 }
 
 #[derive(Debug, Clone)]
@@ -100,6 +173,12 @@ impl Controls {
             .expect("infallible");
     }
 
+    pub fn play_local_playlist(&self, id: i64, index: usize, shuffle: bool) {
+        self.tx
+            .send(ControlCommand::LocalPlaylist { id, index, shuffle })
+            .expect("infallible");
+    }
+
     pub fn play_track(&self, id: u32) {
         self.tx
             .send(ControlCommand::Track { id })
@@ -169,6 +248,61 @@ impl Controls {
             .expect("infallible");
     }
 
+    pub fn set_repeat_mode(&self, mode: RepeatMode) {
+        self.tx
+            .send(ControlCommand::SetRepeatMode { mode })
+            .expect("infallible");
+    }
+
+    pub fn set_shuffle(&self, enabled: bool) {
+        self.tx
+            .send(ControlCommand::SetShuffle { enabled })
+            .expect("infallible");
+    }
+
+    pub fn set_sleep_timer(&self, timer: Option<SleepTimer>) {
+        self.tx
+            .send(ControlCommand::SetSleepTimer { timer })
+            .expect("infallible");
+    }
+
+    /// Raises the volume from silence to the set volume.
+    pub fn ramp_up_volume(&self, duration: Duration) {
+        self.tx
+            .send(ControlCommand::RampUpVolume { duration })
+            .expect("infallible");
+    }
+
+    /// Pauses once the track at `position` in the queue finishes.
+    pub fn set_stop_after(&self, position: Option<usize>) {
+        self.tx
+            .send(ControlCommand::SetStopAfter { position })
+            .expect("infallible");
+    }
+
+    /// Saves the current queue under a name, returning once it is stored.
+    pub async fn save_queue(&self, name: &str) {
+        let (respond, response) = oneshot::channel();
+        self.tx
+            .send(ControlCommand::SaveQueue {
+                name: name.to_string(),
+                respond,
+            })
+            .expect("infallible");
+
+        _ = response.await;
+    }
+
+    /// Replaces the queue with a saved one, or adds its tracks to the end.
+    pub fn load_saved_queue(&self, name: &str, append: bool) {
+        self.tx
+            .send(ControlCommand::LoadSavedQueue {
+                name: name.to_string(),
+                append,
+            })
+            .expect("infallible");
+    }
+
     pub fn set_audio_device(&self, device_name: Option<String>) {
         self.tx
             .send(ControlCommand::SetAudioDevice { device_name })
@@ -192,4 +326,83 @@ impl Controls {
             .send(ControlCommand::SetPitchCents { cents })
             .expect("infallible");
     }
+
+    /// Returns whether playback is bit-perfect once the setting is applied.
+    pub async fn set_bit_perfect(&self, enabled: bool) -> bool {
+        let (respond, response) = oneshot::channel();
+        self.tx
+            .send(ControlCommand::SetBitPerfect { enabled, respond })
+            .expect("infallible");
+
+        response.await.unwrap_or(false)
+    }
+
+    pub fn set_upmix_preset(&self, preset: UpmixPreset) {
+        self.tx
+            .send(ControlCommand::SetUpmixPreset { preset })
+            .expect("infallible");
+    }
+
+    pub fn set_crossfeed_device(&self, device_name: String, enabled: Option<bool>) {
+        self.tx
+            .send(ControlCommand::SetCrossfeedDevice {
+                device_name,
+                enabled,
+            })
+            .expect("infallible");
+    }
+
+    pub fn set_crossfeed_preset(&self, preset: CrossfeedPreset, strength: f32) {
+        self.tx
+            .send(ControlCommand::SetCrossfeedPreset { preset, strength })
+            .expect("infallible");
+    }
+
+    pub fn reload_clap_chain(&self) {
+        self.tx
+            .send(ControlCommand::ReloadClapChain)
+            .expect("infallible");
+    }
+
+    /// Sets a plugin parameter, returning once the player has saved it.
+    pub async fn set_clap_parameter(&self, position: u32, param_id: u32, value: f64) -> Result<()> {
+        let (respond, response) = oneshot::channel();
+        self.tx
+            .send(ControlCommand::SetClapParameter {
+                position,
+                param_id,
+                value,
+                respond,
+            })
+            .expect("infallible");
+
+        response.await.map_err(|_| Error::Plugin {
+            message: "player stopped".into(),
+        })?
+    }
+
+    /// Names the pitches sounding around the current playback position.
+    pub async fn probe_pitch(&self) -> Result<PitchProbe> {
+        let (respond, response) = oneshot::channel();
+        self.tx
+            .send(ControlCommand::ProbePitch { respond })
+            .expect("infallible");
+
+        response.await.map_err(|_| Error::Analysis {
+            message: "player stopped".into(),
+        })?
+    }
+
+    pub fn set_output_sample_format(
+        &self,
+        device_name: Option<String>,
+        sample_format: Option<OutputSampleFormat>,
+    ) {
+        self.tx
+            .send(ControlCommand::SetOutputSampleFormat {
+                device_name,
+                sample_format,
+            })
+            .expect("infallible");
+    }
 }
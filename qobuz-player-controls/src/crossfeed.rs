@@ -0,0 +1,269 @@
+use std::{collections::HashMap, f32::consts::PI, str::FromStr, time::Duration};
+
+use rodio::{ChannelCount, SampleRate, Source, source::SeekError};
+
+const HEADPHONE_KEYWORDS: [&str; 6] = [
+    "headphone",
+    "headset",
+    "earphone",
+    "earbud",
+    "airpods",
+    "buds",
+];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CrossfeedPreset {
+    /// Bauer stereophonic-to-binaural: 700 Hz, 4.5 dB.
+    #[default]
+    Bauer,
+    /// Jan Meier: 650 Hz, 9.5 dB, closer to speakers in a room.
+    Meier,
+}
+
+impl CrossfeedPreset {
+    pub const ALL: [Self; 2] = [Self::Bauer, Self::Meier];
+
+    fn cutoff_hz(self) -> f32 {
+        match self {
+            Self::Bauer => 700.0,
+            Self::Meier => 650.0,
+        }
+    }
+
+    fn feed_db(self) -> f32 {
+        match self {
+            Self::Bauer => 4.5,
+            Self::Meier => 9.5,
+        }
+    }
+}
+
+impl std::fmt::Display for CrossfeedPreset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Bauer => "bauer",
+            Self::Meier => "meier",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for CrossfeedPreset {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|preset| preset.to_string() == value)
+            .ok_or_else(|| format!("Unknown crossfeed preset '{value}'. Use bauer or meier"))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrossfeedSettings {
+    /// Explicit per-output choices. Outputs without one use headphone detection.
+    pub devices: HashMap<String, bool>,
+    pub preset: CrossfeedPreset,
+    pub strength: f32,
+}
+
+impl Default for CrossfeedSettings {
+    fn default() -> Self {
+        Self {
+            devices: Default::default(),
+            preset: Default::default(),
+            strength: 1.0,
+        }
+    }
+}
+
+impl CrossfeedSettings {
+    pub fn enabled_for(&self, device_name: &str) -> bool {
+        self.devices
+            .get(device_name)
+            .copied()
+            .unwrap_or_else(|| is_headphone_device(device_name))
+    }
+}
+
+pub fn is_headphone_device(device_name: &str) -> bool {
+    let device_name = device_name.to_lowercase();
+    HEADPHONE_KEYWORDS
+        .iter()
+        .any(|keyword| device_name.contains(keyword))
+}
+
+/// Stereo crossfeed after Bauer's bs2b filter: the opposite channel is fed
+/// through a low-pass while the direct channel gets a matching high shelf.
+pub struct CrossfeedSource<S> {
+    inner: S,
+    strength: f32,
+    a0_lo: f32,
+    b1_lo: f32,
+    a0_hi: f32,
+    a1_hi: f32,
+    b1_hi: f32,
+    gain: f32,
+    lo: [f32; 2],
+    hi: [f32; 2],
+    last: [f32; 2],
+    frame: [f32; 2],
+    output_index: usize,
+}
+
+impl<S> CrossfeedSource<S>
+where
+    S: Source<Item = f32>,
+{
+    pub fn new(inner: S, preset: CrossfeedPreset, strength: f32) -> Self {
+        let sample_rate = inner.sample_rate() as f32;
+        let level = preset.feed_db();
+        let cutoff_lo = preset.cutoff_hz();
+
+        let gain_lo_db = level * -5.0 / 6.0 - 3.0;
+        let gain_hi_db = level / 6.0 - 3.0;
+        let gain_lo = 10f32.powf(gain_lo_db / 20.0);
+        let gain_hi = 1.0 - 10f32.powf(gain_hi_db / 20.0);
+        let cutoff_hi = cutoff_lo * 2f32.powf((gain_lo_db - 20.0 * gain_hi.log10()) / 12.0);
+
+        let x_lo = (-2.0 * PI * cutoff_lo / sample_rate).exp();
+        let x_hi = (-2.0 * PI * cutoff_hi / sample_rate).exp();
+
+        Self {
+            inner,
+            strength: strength.clamp(0.0, 1.0),
+            a0_lo: gain_lo * (1.0 - x_lo),
+            b1_lo: x_lo,
+            a0_hi: 1.0 - gain_hi * (1.0 - x_hi),
+            a1_hi: -x_hi,
+            b1_hi: x_hi,
+            gain: 1.0 / (1.0 - gain_hi + gain_lo),
+            lo: [0.0; 2],
+            hi: [0.0; 2],
+            last: [0.0; 2],
+            frame: [0.0; 2],
+            output_index: 2,
+        }
+    }
+
+    fn process_frame(&mut self, input: [f32; 2]) {
+        let lo = std::array::from_fn(|channel| {
+            self.a0_lo * input[channel] + self.b1_lo * self.lo[channel]
+        });
+        let hi = std::array::from_fn(|channel| {
+            self.a0_hi * input[channel]
+                + self.a1_hi * self.last[channel]
+                + self.b1_hi * self.hi[channel]
+        });
+        self.lo = lo;
+        self.hi = hi;
+        self.last = input;
+
+        self.frame = std::array::from_fn(|channel| {
+            let crossfed = (self.hi[channel] + self.lo[1 - channel]) * self.gain;
+            input[channel] + (crossfed - input[channel]) * self.strength
+        });
+    }
+
+    fn reset(&mut self) {
+        self.lo = [0.0; 2];
+        self.hi = [0.0; 2];
+        self.last = [0.0; 2];
+        self.output_index = 2;
+    }
+}
+
+impl<S> Iterator for CrossfeedSource<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.inner.channels() != 2 {
+            return self.inner.next();
+        }
+
+        if self.output_index >= 2 {
+            let left = self.inner.next()?;
+            let right = self.inner.next()?;
+            self.process_frame([left, right]);
+            self.output_index = 0;
+        }
+
+        let sample = self.frame[self.output_index];
+        self.output_index += 1;
+        Some(sample)
+    }
+}
+
+impl<S> Source for CrossfeedSource<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_span_len(&self) -> Option<usize> {
+        self.inner.current_span_len()
+    }
+
+    fn channels(&self) -> ChannelCount {
+        self.inner.channels()
+    }
+
+    fn sample_rate(&self) -> SampleRate {
+        self.inner.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.inner.try_seek(pos)?;
+        self.reset();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    fn hard_left(frames: usize) -> SamplesBuffer {
+        let samples = (0..frames).flat_map(|_| [0.5f32, 0.0]).collect::<Vec<_>>();
+        SamplesBuffer::new(2, 44_100, samples)
+    }
+
+    #[test]
+    fn feeds_low_frequencies_to_opposite_channel() {
+        let output = CrossfeedSource::new(hard_left(4410), CrossfeedPreset::Bauer, 1.0)
+            .collect::<Vec<_>>();
+        let (left, right) = (output[output.len() - 2], output[output.len() - 1]);
+
+        assert!(right > 0.05, "right channel got {right}");
+        assert!(left > right);
+        assert!(left <= 0.5);
+    }
+
+    #[test]
+    fn zero_strength_is_transparent() {
+        let output = CrossfeedSource::new(hard_left(100), CrossfeedPreset::Meier, 0.0)
+            .collect::<Vec<_>>();
+
+        assert_eq!(output, hard_left(100).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn detects_headphone_outputs() {
+        let settings = CrossfeedSettings::default();
+        assert!(settings.enabled_for("Headphones (Realtek(R) Audio)"));
+        assert!(!settings.enabled_for("Speakers (Realtek(R) Audio)"));
+
+        let settings = CrossfeedSettings {
+            devices: HashMap::from([("Headphones".to_string(), false)]),
+            ..Default::default()
+        };
+        assert!(!settings.enabled_for("Headphones"));
+    }
+}
@@ -1,8 +1,21 @@
-use crate::{AudioQuality, Error, Result, Tracklist};
+use crate::{
+    AudioQuality, Error, Result, Tracklist,
+    audio_cache::FileStamp,
+    channel_mix::UpmixPreset,
+    chords::ChordTimeline,
+    clap::ClapParam,
+    crossfeed::{CrossfeedPreset, CrossfeedSettings},
+    output::OutputSampleFormat,
+    schedule::{Schedule, ScheduleTarget, TimeOfDay},
+    tracklist::RepeatMode,
+};
+use qobuz_player_models::{Track, TrackStatus};
 use serde_json::to_string;
+use std::collections::HashMap;
 use sqlx::types::Json;
 use sqlx::{Pool, Row, Sqlite, SqlitePool, sqlite::SqliteConnectOptions};
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 pub struct Database {
     pool: Pool<Sqlite>,
@@ -130,6 +143,31 @@ impl Database {
         Ok(())
     }
 
+    /// Locks a file next to the database for as long as the returned file is
+    /// open, marking that a running player owns the stored tracklist.
+    pub fn lock_player(&self) -> Option<std::fs::File> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.player_lock_path())
+            .ok()?;
+        file.try_lock().ok()?;
+        Some(file)
+    }
+
+    /// Whether a player holds the lock taken by `lock_player`.
+    pub fn player_running(&self) -> bool {
+        let Ok(file) = std::fs::File::open(self.player_lock_path()) else {
+            return false;
+        };
+        matches!(file.try_lock(), Err(std::fs::TryLockError::WouldBlock))
+    }
+
+    fn player_lock_path(&self) -> PathBuf {
+        self.database_path.with_extension("lock")
+    }
+
     pub async fn get_tracklist(&self) -> Option<Tracklist> {
         let row = sqlx::query_as!(
             TracklistDb,
@@ -143,6 +181,212 @@ impl Database {
         row.ok().map(|x| x.tracklist.0)
     }
 
+    pub async fn save_queue(&self, name: &str, tracklist: &Tracklist) -> Result<()> {
+        sqlx::query("INSERT OR REPLACE INTO saved_queues (name, tracklist) VALUES (?1, ?2)")
+            .bind(name)
+            .bind(to_string(tracklist)?)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_saved_queues(&self) -> Result<Vec<SavedQueue>> {
+        let rows = sqlx::query("SELECT name, tracklist FROM saved_queues ORDER BY name")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| {
+                let tracklist = serde_json::from_str(row.get("tracklist")).ok()?;
+                Some(SavedQueue {
+                    name: row.get("name"),
+                    tracklist,
+                })
+            })
+            .collect())
+    }
+
+    pub async fn get_saved_queue(&self, name: &str) -> Result<Option<Tracklist>> {
+        let row = sqlx::query("SELECT tracklist FROM saved_queues WHERE name = ?1")
+            .bind(name)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row.and_then(|row| serde_json::from_str(row.get("tracklist")).ok()))
+    }
+
+    pub async fn remove_saved_queue(&self, name: &str) -> Result<()> {
+        sqlx::query("DELETE FROM saved_queues WHERE name = ?1")
+            .bind(name)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn create_local_playlist(&self, title: &str) -> Result<i64> {
+        let id: i64 = sqlx::query("INSERT INTO local_playlists (title) VALUES (?1) RETURNING id")
+            .bind(title)
+            .fetch_one(&self.pool)
+            .await?
+            .get("id");
+        Ok(id)
+    }
+
+    pub async fn rename_local_playlist(&self, id: i64, title: &str) -> Result<()> {
+        sqlx::query("UPDATE local_playlists SET title = ?1 WHERE id = ?2")
+            .bind(title)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn remove_local_playlist(&self, id: i64) -> Result<()> {
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query("DELETE FROM local_playlist_entries WHERE playlist_id = ?1")
+            .bind(id)
+            .execute(&mut *transaction)
+            .await?;
+        sqlx::query("DELETE FROM local_playlists WHERE id = ?1")
+            .bind(id)
+            .execute(&mut *transaction)
+            .await?;
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    pub async fn get_local_playlists(&self) -> Result<Vec<LocalPlaylist>> {
+        let rows =
+            sqlx::query("SELECT id, title FROM local_playlists ORDER BY title COLLATE NOCASE")
+                .fetch_all(&self.pool)
+                .await?;
+
+        let mut playlists = Vec::with_capacity(rows.len());
+        for row in rows {
+            let id = row.get("id");
+            playlists.push(LocalPlaylist {
+                id,
+                title: row.get("title"),
+                entries: self.local_playlist_entries(id).await?,
+            });
+        }
+        Ok(playlists)
+    }
+
+    pub async fn get_local_playlist(&self, id: i64) -> Result<Option<LocalPlaylist>> {
+        let Some(row) = sqlx::query("SELECT title FROM local_playlists WHERE id = ?1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        Ok(Some(LocalPlaylist {
+            id,
+            title: row.get("title"),
+            entries: self.local_playlist_entries(id).await?,
+        }))
+    }
+
+    pub async fn add_local_playlist_track(&self, id: i64, track: &Track) -> Result<()> {
+        let mut entries = self.local_playlist_entries(id).await?;
+        entries.push(LocalPlaylistEntry {
+            track: track.clone(),
+            note: None,
+        });
+        self.set_local_playlist_entries(id, entries).await
+    }
+
+    pub async fn remove_local_playlist_entry(&self, id: i64, position: usize) -> Result<()> {
+        let mut entries = self.local_playlist_entries(id).await?;
+        if position < entries.len() {
+            entries.remove(position);
+        }
+        self.set_local_playlist_entries(id, entries).await
+    }
+
+    pub async fn move_local_playlist_entry(&self, id: i64, from: usize, to: usize) -> Result<()> {
+        let mut entries = self.local_playlist_entries(id).await?;
+        if from >= entries.len() {
+            return Ok(());
+        }
+        let entry = entries.remove(from);
+        entries.insert(to.min(entries.len()), entry);
+        self.set_local_playlist_entries(id, entries).await
+    }
+
+    pub async fn set_local_playlist_note(
+        &self,
+        id: i64,
+        position: usize,
+        note: Option<&str>,
+    ) -> Result<()> {
+        sqlx::query(
+            "UPDATE local_playlist_entries SET note = ?1 WHERE playlist_id = ?2 AND position = ?3",
+        )
+        .bind(note.map(str::trim).filter(|note| !note.is_empty()))
+        .bind(id)
+        .bind(position as i64)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn local_playlist_entries(&self, id: i64) -> Result<Vec<LocalPlaylistEntry>> {
+        let rows = sqlx::query(
+            "SELECT track, note FROM local_playlist_entries WHERE playlist_id = ?1 ORDER BY position",
+        )
+        .bind(id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| {
+                let track = serde_json::from_str(row.get("track")).ok()?;
+                Some(LocalPlaylistEntry {
+                    track,
+                    note: row.get("note"),
+                })
+            })
+            .collect())
+    }
+
+    async fn set_local_playlist_entries(
+        &self,
+        id: i64,
+        entries: Vec<LocalPlaylistEntry>,
+    ) -> Result<()> {
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query("DELETE FROM local_playlist_entries WHERE playlist_id = ?1")
+            .bind(id)
+            .execute(&mut *transaction)
+            .await?;
+
+        for (position, mut entry) in entries.into_iter().enumerate() {
+            entry.track.status = TrackStatus::Unplayed;
+            entry.track.playlist_track_id = None;
+            sqlx::query(
+                r#"
+                INSERT INTO local_playlist_entries (playlist_id, position, track_id, track, note)
+                VALUES (?1, ?2, ?3, ?4, ?5)
+                "#,
+            )
+            .bind(id)
+            .bind(position as i64)
+            .bind(entry.track.id as i64)
+            .bind(to_string(&entry.track)?)
+            .bind(entry.note)
+            .execute(&mut *transaction)
+            .await?;
+        }
+
+        transaction.commit().await?;
+        Ok(())
+    }
+
     pub async fn set_volume(&self, volume: f32) -> Result<()> {
         sqlx::query!(
             r#"
@@ -164,6 +408,34 @@ impl Database {
         Ok(())
     }
 
+    pub async fn set_playback_position(&self, position: Option<PlaybackPosition>) -> Result<()> {
+        sqlx::query("DELETE FROM playback_position")
+            .execute(&self.pool)
+            .await?;
+
+        if let Some(position) = position {
+            sqlx::query("INSERT INTO playback_position (track_id, position_ms) VALUES (?1, ?2)")
+                .bind(position.track_id as i64)
+                .bind(position.position.as_millis() as i64)
+                .execute(&self.pool)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn get_playback_position(&self) -> Option<PlaybackPosition> {
+        let row = sqlx::query("SELECT track_id, position_ms FROM playback_position")
+            .fetch_one(&self.pool)
+            .await
+            .ok()?;
+
+        Some(PlaybackPosition {
+            track_id: row.get::<i64, _>("track_id") as u32,
+            position: Duration::from_millis(row.get::<i64, _>("position_ms").max(0) as u64),
+        })
+    }
+
     pub async fn get_volume(&self) -> Option<f32> {
         let row = sqlx::query_as!(
             VolumeDb,
@@ -209,7 +481,7 @@ impl Database {
     pub async fn get_configuration(&self) -> Result<DatabaseConfiguration> {
         let row = sqlx::query(
             r#"
-            SELECT max_audio_quality, audio_device_name, preferred_genre_id, time_stretch_ratio, pitch_semitones, pitch_cents FROM configuration
+            SELECT max_audio_quality, audio_device_name, preferred_genre_id, time_stretch_ratio, pitch_semitones, pitch_cents, export_directory, export_template, bit_perfect, output_sample_formats, upmix_preset, crossfeed_devices, crossfeed_preset, crossfeed_strength, clap_plugin_path, repeat_mode, resume_playback FROM configuration
             WHERE ROWID = 1;
             "#
         )
@@ -225,6 +497,25 @@ impl Database {
         let pitch_cents: i16 = row
             .get::<Option<i32>, _>("pitch_cents")
             .unwrap_or(0) as i16;
+        let output_sample_formats = row
+            .get::<Option<String>, _>("output_sample_formats")
+            .and_then(|formats| serde_json::from_str(&formats).ok())
+            .unwrap_or_default();
+        let upmix_preset = row
+            .get::<Option<String>, _>("upmix_preset")
+            .and_then(|preset| preset.parse().ok())
+            .unwrap_or_default();
+        let crossfeed = CrossfeedSettings {
+            devices: row
+                .get::<Option<String>, _>("crossfeed_devices")
+                .and_then(|devices| serde_json::from_str(&devices).ok())
+                .unwrap_or_default(),
+            preset: row
+                .get::<Option<String>, _>("crossfeed_preset")
+                .and_then(|preset| preset.parse().ok())
+                .unwrap_or_default(),
+            strength: row.get::<Option<f64>, _>("crossfeed_strength").unwrap_or(1.0) as f32,
+        };
         Ok(DatabaseConfiguration {
             max_audio_quality: row.get("max_audio_quality"),
             audio_device_name: row.get("audio_device_name"),
@@ -232,6 +523,18 @@ impl Database {
             time_stretch_ratio,
             pitch_semitones,
             pitch_cents,
+            export_directory: row.get("export_directory"),
+            export_template: row.get("export_template"),
+            bit_perfect: row.get("bit_perfect"),
+            output_sample_formats,
+            upmix_preset,
+            crossfeed,
+            clap_plugin_path: row.get("clap_plugin_path"),
+            repeat_mode: row
+                .get::<Option<String>, _>("repeat_mode")
+                .and_then(|mode| mode.parse().ok())
+                .unwrap_or_default(),
+            resume_playback: row.get("resume_playback"),
         })
     }
 
@@ -249,6 +552,334 @@ impl Database {
         Ok(())
     }
 
+    pub async fn set_output_sample_format(
+        &self,
+        device_name: Option<&str>,
+        sample_format: Option<OutputSampleFormat>,
+    ) -> Result<()> {
+        let mut formats = self.get_configuration().await?.output_sample_formats;
+        let device_name = device_name.unwrap_or_default().to_string();
+        match sample_format {
+            Some(sample_format) => formats.insert(device_name, sample_format),
+            None => formats.remove(&device_name),
+        };
+
+        sqlx::query(
+            r#"
+            UPDATE configuration
+            SET output_sample_formats=?1
+            WHERE ROWID = 1
+            "#,
+        )
+        .bind(to_string(&formats)?)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn set_upmix_preset(&self, preset: UpmixPreset) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE configuration
+            SET upmix_preset=?1
+            WHERE ROWID = 1
+            "#,
+        )
+        .bind(preset.to_string())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn set_repeat_mode(&self, mode: RepeatMode) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE configuration
+            SET repeat_mode=?1
+            WHERE ROWID = 1
+            "#,
+        )
+        .bind(mode.to_string())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn set_crossfeed_device(&self, device_name: &str, enabled: Option<bool>) -> Result<()> {
+        let mut devices = self.get_configuration().await?.crossfeed.devices;
+        match enabled {
+            Some(enabled) => devices.insert(device_name.to_string(), enabled),
+            None => devices.remove(device_name),
+        };
+
+        sqlx::query(
+            r#"
+            UPDATE configuration
+            SET crossfeed_devices=?1
+            WHERE ROWID = 1
+            "#,
+        )
+        .bind(to_string(&devices)?)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn set_crossfeed_preset(&self, preset: CrossfeedPreset, strength: f32) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE configuration
+            SET crossfeed_preset=?1, crossfeed_strength=?2
+            WHERE ROWID = 1
+            "#,
+        )
+        .bind(preset.to_string())
+        .bind(strength.clamp(0.0, 1.0) as f64)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn set_export_settings(
+        &self,
+        directory: Option<String>,
+        template: Option<String>,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE configuration
+            SET export_directory=?1, export_template=?2
+            WHERE ROWID = 1
+            "#,
+        )
+        .bind(directory)
+        .bind(template)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn set_clap_plugin_path(&self, path: Option<String>) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE configuration
+            SET clap_plugin_path=?1
+            WHERE ROWID = 1
+            "#,
+        )
+        .bind(path)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get_clap_plugins(&self, chain: &str) -> Result<Vec<DatabaseClapPlugin>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT position, path, plugin_id, state, params FROM clap_plugins
+            WHERE chain = ?1
+            ORDER BY position
+            "#,
+        )
+        .bind(chain)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| DatabaseClapPlugin {
+                position: row.get::<i64, _>("position") as u32,
+                path: PathBuf::from(row.get::<String, _>("path")),
+                plugin_id: row.get("plugin_id"),
+                state: row.get("state"),
+                params: row
+                    .get::<Option<String>, _>("params")
+                    .and_then(|params| serde_json::from_str(&params).ok())
+                    .unwrap_or_default(),
+            })
+            .collect())
+    }
+
+    pub async fn add_clap_plugin(
+        &self,
+        chain: &str,
+        path: &Path,
+        plugin_id: &str,
+        state: Option<&[u8]>,
+        params: &[ClapParam],
+    ) -> Result<u32> {
+        let position: i64 = sqlx::query(
+            r#"
+            INSERT INTO clap_plugins (chain, position, path, plugin_id, state, params)
+            VALUES (?1, (SELECT COALESCE(MAX(position) + 1, 0) FROM clap_plugins WHERE chain = ?1), ?2, ?3, ?4, ?5)
+            RETURNING position
+            "#,
+        )
+        .bind(chain)
+        .bind(path.to_string_lossy().into_owned())
+        .bind(plugin_id)
+        .bind(state)
+        .bind(to_string(params)?)
+        .fetch_one(&self.pool)
+        .await?
+        .get("position");
+        Ok(position as u32)
+    }
+
+    pub async fn remove_clap_plugin(&self, chain: &str, position: u32) -> Result<()> {
+        sqlx::query("DELETE FROM clap_plugins WHERE chain = ?1 AND position = ?2")
+            .bind(chain)
+            .bind(position as i64)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn set_clap_plugin_state(
+        &self,
+        chain: &str,
+        position: u32,
+        state: Option<&[u8]>,
+        params: &[ClapParam],
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE clap_plugins
+            SET state=?3, params=?4
+            WHERE chain = ?1 AND position = ?2
+            "#,
+        )
+        .bind(chain)
+        .bind(position as i64)
+        .bind(state)
+        .bind(to_string(params)?)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get_schedules(&self) -> Result<Vec<Schedule>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, target_kind, target_id, hour, minute, weekdays, ramp_up_seconds FROM schedules
+            ORDER BY hour, minute, id
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| {
+                let target = ScheduleTarget::from_parts(
+                    row.get::<String, _>("target_kind").as_str(),
+                    row.get::<String, _>("target_id").as_str(),
+                )?;
+                Some(Schedule {
+                    id: row.get("id"),
+                    target,
+                    time: TimeOfDay {
+                        hour: row.get::<i64, _>("hour") as u8,
+                        minute: row.get::<i64, _>("minute") as u8,
+                    },
+                    weekdays: row.get::<i64, _>("weekdays") as u8,
+                    ramp_up: row
+                        .get::<Option<i64>, _>("ramp_up_seconds")
+                        .map(|seconds| Duration::from_secs(seconds.max(0) as u64)),
+                })
+            })
+            .collect())
+    }
+
+    pub async fn add_schedule(
+        &self,
+        target: &ScheduleTarget,
+        time: TimeOfDay,
+        weekdays: u8,
+        ramp_up: Option<Duration>,
+    ) -> Result<i64> {
+        let id: i64 = sqlx::query(
+            r#"
+            INSERT INTO schedules (target_kind, target_id, hour, minute, weekdays, ramp_up_seconds)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            RETURNING id
+            "#,
+        )
+        .bind(target.kind())
+        .bind(target.id())
+        .bind(time.hour as i64)
+        .bind(time.minute as i64)
+        .bind(weekdays as i64)
+        .bind(ramp_up.map(|duration| duration.as_secs() as i64))
+        .fetch_one(&self.pool)
+        .await?
+        .get("id");
+        Ok(id)
+    }
+
+    pub async fn remove_schedule(&self, id: i64) -> Result<()> {
+        sqlx::query("DELETE FROM schedules WHERE id = ?1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_chord_timeline(&self, track_id: u32) -> Result<Option<ChordTimeline>> {
+        let row = sqlx::query("SELECT segments FROM chord_timelines WHERE track_id = ?1")
+            .bind(track_id as i64)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row.and_then(|row| {
+            serde_json::from_str(&row.get::<String, _>("segments"))
+                .ok()
+                .map(|segments| ChordTimeline { segments })
+        }))
+    }
+
+    pub async fn set_chord_timeline(&self, track_id: u32, timeline: &ChordTimeline) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO chord_timelines (track_id, segments) VALUES (?1, ?2)
+            ON CONFLICT(track_id) DO UPDATE SET segments = excluded.segments
+            "#,
+        )
+        .bind(track_id as i64)
+        .bind(to_string(&timeline.segments)?)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn set_bit_perfect(&self, enabled: bool) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE configuration
+            SET bit_perfect=?1
+            WHERE ROWID = 1
+            "#,
+        )
+        .bind(enabled)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn set_resume_playback(&self, enabled: bool) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE configuration
+            SET resume_playback=?1
+            WHERE ROWID = 1
+            "#,
+        )
+        .bind(enabled)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
     pub async fn set_preferred_genre_id(&self, genre_id: Option<i64>) -> Result<()> {
         sqlx::query(
             r#"
@@ -389,29 +1020,102 @@ impl Database {
         Ok(paths)
     }
 
-    pub async fn set_cache_entry(&self, path: &Path) {
+    pub async fn set_cache_entry(&self, path: &Path, track_id: u32) {
         let now = time::OffsetDateTime::now_utc()
             .format(&time::format_description::well_known::Rfc3339)
             .expect("infallible");
 
         let path_str: String = path.to_string_lossy().into_owned();
 
-        sqlx::query!(
+        sqlx::query(
             r#"
-                INSERT INTO cache_entries (path, last_opened)
-                VALUES (?, ?)
+                INSERT INTO cache_entries (path, last_opened, track_id)
+                VALUES (?1, ?2, ?3)
                 ON CONFLICT(path) DO UPDATE SET
-                    path = excluded.path,
-                    last_opened = excluded.last_opened
+                    last_opened = excluded.last_opened,
+                    track_id = excluded.track_id
             "#,
-            path_str,
-            now
         )
+        .bind(path_str)
+        .bind(now)
+        .bind(track_id)
         .execute(&self.pool)
         .await
         .expect("infallible");
     }
 
+    pub async fn cache_entry_paths(&self) -> Result<Vec<PathBuf>> {
+        let rows = sqlx::query("SELECT path FROM cache_entries")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| PathBuf::from(row.get::<String, _>("path")))
+            .collect())
+    }
+
+    /// Track a cached file was downloaded for, if it was recorded.
+    pub async fn cache_entry_track_id(&self, path: &Path) -> Result<Option<u32>> {
+        let path_str: String = path.to_string_lossy().into_owned();
+
+        let row = sqlx::query("SELECT track_id FROM cache_entries WHERE path = ?1")
+            .bind(path_str)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row.and_then(|row| row.get::<Option<u32>, _>("track_id")))
+    }
+
+    /// Size and modification time of each cache file when it last decoded
+    /// cleanly.
+    pub async fn verified_cache_files(&self) -> Result<HashMap<PathBuf, FileStamp>> {
+        let rows = sqlx::query("SELECT path, size, modified FROM verified_cache_files")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let path = PathBuf::from(row.get::<String, _>("path"));
+                let stamp = FileStamp {
+                    size: row.get::<i64, _>("size") as u64,
+                    modified: row.get("modified"),
+                };
+                (path, stamp)
+            })
+            .collect())
+    }
+
+    pub async fn set_verified_cache_files(&self, files: &HashMap<PathBuf, FileStamp>) -> Result<()> {
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query("DELETE FROM verified_cache_files")
+            .execute(&mut *transaction)
+            .await?;
+        for (path, stamp) in files {
+            sqlx::query(
+                "INSERT INTO verified_cache_files (path, size, modified) VALUES (?1, ?2, ?3)",
+            )
+            .bind(path.to_string_lossy().into_owned())
+            .bind(stamp.size as i64)
+            .bind(stamp.modified)
+            .execute(&mut *transaction)
+            .await?;
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    pub async fn remove_cache_entry(&self, path: &Path) -> Result<()> {
+        let path_str: String = path.to_string_lossy().into_owned();
+
+        sqlx::query("DELETE FROM cache_entries WHERE path = ?1")
+            .bind(path_str)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
     pub async fn refresh_database(&self) -> Result<()> {
         self.pool.close().await;
         delete_database_files(&self.database_path)?;
@@ -466,6 +1170,67 @@ pub struct DatabaseConfiguration {
     pub time_stretch_ratio: f32,
     pub pitch_semitones: i16,
     pub pitch_cents: i16,
+    pub export_directory: Option<String>,
+    pub export_template: Option<String>,
+    pub bit_perfect: bool,
+    pub output_sample_formats: HashMap<String, OutputSampleFormat>,
+    pub upmix_preset: UpmixPreset,
+    pub crossfeed: CrossfeedSettings,
+    pub clap_plugin_path: Option<String>,
+    pub repeat_mode: RepeatMode,
+    /// Start playing the restored queue on startup.
+    pub resume_playback: bool,
+}
+
+/// Queue saved under a name, independent of Qobuz playlists.
+pub struct SavedQueue {
+    pub name: String,
+    pub tracklist: Tracklist,
+}
+
+/// Playlist kept in the database, editable without reaching Qobuz.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct LocalPlaylist {
+    pub id: i64,
+    pub title: String,
+    pub entries: Vec<LocalPlaylistEntry>,
+}
+
+impl LocalPlaylist {
+    pub fn duration_seconds(&self) -> u32 {
+        self.entries
+            .iter()
+            .map(|entry| entry.track.duration_seconds)
+            .sum()
+    }
+
+    pub fn image(&self) -> Option<String> {
+        self.entries
+            .iter()
+            .find_map(|entry| entry.track.image.clone())
+    }
+}
+
+/// Track in a local playlist, with metadata cached when it was added.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct LocalPlaylistEntry {
+    pub track: Track,
+    pub note: Option<String>,
+}
+
+/// Position within the current track, checkpointed to survive restarts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlaybackPosition {
+    pub track_id: u32,
+    pub position: Duration,
+}
+
+pub struct DatabaseClapPlugin {
+    pub position: u32,
+    pub path: PathBuf,
+    pub plugin_id: String,
+    pub state: Option<Vec<u8>>,
+    pub params: Vec<ClapParam>,
 }
 
 #[derive(Debug, sqlx::FromRow, serde::Deserialize)]
@@ -536,8 +1301,8 @@ mod tests {
         let old_path = Path::new(old_path_str);
         let new_path_str = "path/new";
         let new_path = Path::new(new_path_str);
-        db.set_cache_entry(old_path).await;
-        db.set_cache_entry(new_path).await;
+        db.set_cache_entry(old_path, 1).await;
+        db.set_cache_entry(new_path, 2).await;
 
         let old_time = OffsetDateTime::now_utc() - Duration::days(10);
         let old_time = old_time
@@ -566,4 +1331,23 @@ mod tests {
         assert_eq!(remaining, vec![new_path_str]);
         assert_eq!(deleted, vec![old_path]);
     }
+
+    #[tokio::test]
+    async fn player_lock_marks_a_running_player() {
+        let db = Database {
+            pool: SqlitePool::connect("sqlite::memory:").await.unwrap(),
+            database_path: std::env::temp_dir()
+                .join(format!("qobuz-player-lock-{}.db", std::process::id())),
+        };
+        assert!(!db.player_running());
+
+        let lock = db.lock_player();
+        assert!(lock.is_some());
+        assert!(db.player_running());
+        assert!(db.lock_player().is_none());
+
+        drop(lock);
+        assert!(!db.player_running());
+        _ = std::fs::remove_file(db.player_lock_path());
+    }
 }
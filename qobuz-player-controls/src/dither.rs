@@ -0,0 +1,117 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicU32, Ordering},
+    },
+    time::Duration,
+};
+
+use rodio::{ChannelCount, SampleRate, Source, source::SeekError};
+
+/// Quantizes f32 samples to `bits` with TPDF dither so the final
+/// conversion to an integer output format is exact. A bit depth of 0
+/// passes samples through untouched.
+pub struct DitherSource<S> {
+    inner: S,
+    bits: Arc<AtomicU32>,
+    seed: u32,
+}
+
+impl<S> DitherSource<S>
+where
+    S: Source<Item = f32>,
+{
+    pub fn new(inner: S, bits: Arc<AtomicU32>) -> Self {
+        Self {
+            inner,
+            bits,
+            seed: 0x9e37_79b9,
+        }
+    }
+
+    fn uniform(&mut self) -> f32 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 17;
+        self.seed ^= self.seed << 5;
+        (self.seed >> 8) as f32 / (1 << 24) as f32
+    }
+}
+
+impl<S> Iterator for DitherSource<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let sample = self.inner.next()?;
+        let bits = self.bits.load(Ordering::Relaxed);
+
+        if bits == 0 || bits >= 32 || sample == 0.0 {
+            return Some(sample);
+        }
+
+        let scale = (1u32 << (bits - 1)) as f32;
+        let noise = self.uniform() - self.uniform();
+        let quantized = (sample * scale + noise).round();
+
+        Some(quantized.clamp(-scale, scale - 1.0) / scale)
+    }
+}
+
+impl<S> Source for DitherSource<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_span_len(&self) -> Option<usize> {
+        self.inner.current_span_len()
+    }
+
+    fn channels(&self) -> ChannelCount {
+        self.inner.channels()
+    }
+
+    fn sample_rate(&self) -> SampleRate {
+        self.inner.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.inner.try_seek(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    #[test]
+    fn quantizes_to_bit_depth() {
+        let samples = vec![0.123_456_79f32; 1000];
+        let bits = Arc::new(AtomicU32::new(16));
+        let dithered = DitherSource::new(SamplesBuffer::new(1, 44_100, samples), bits);
+
+        let mut total = 0.0;
+        for sample in dithered {
+            let scaled = sample * 32768.0;
+            assert_eq!(scaled, scaled.round());
+            assert!((sample - 0.123_456_79).abs() <= 2.0 / 32768.0);
+            total += sample;
+        }
+
+        assert!((total / 1000.0 - 0.123_456_79).abs() < 0.5 / 32768.0);
+    }
+
+    #[test]
+    fn passes_through_when_disabled() {
+        let samples = vec![0.123_456_79f32; 10];
+        let bits = Arc::new(AtomicU32::new(0));
+        let dithered = DitherSource::new(SamplesBuffer::new(1, 44_100, samples.clone()), bits);
+
+        assert_eq!(dithered.collect::<Vec<_>>(), samples);
+    }
+}
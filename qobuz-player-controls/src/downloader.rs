@@ -2,9 +2,11 @@ use std::{
     fs,
     path::{Path, PathBuf},
     sync::Arc,
+    time::{Duration, Instant},
 };
 
-use crate::{database::Database, notification::NotificationBroadcast};
+use crate::{AudioQuality, database::Database, notification::NotificationBroadcast};
+use parking_lot::Mutex;
 use qobuz_player_client::qobuz_models::TrackURL;
 use qobuz_player_models::Track;
 use tokio::{
@@ -18,8 +20,11 @@ pub struct Downloader {
     broadcast: Arc<NotificationBroadcast>,
     done_buffering_tx: Sender<PathBuf>,
     download_handle: Option<JoinHandle<()>>,
+    bytes_per_second: Arc<Mutex<Option<f64>>>,
 }
 
+const MIN_MEASURED_BYTES: usize = 512 * 1024;
+
 impl Downloader {
     pub fn new(
         audio_cache_dir: PathBuf,
@@ -34,7 +39,50 @@ impl Downloader {
             database,
             broadcast,
             download_handle: None,
+            bytes_per_second: Default::default(),
+        }
+    }
+
+    pub fn audio_cache_dir(&self) -> &Path {
+        &self.audio_cache_dir
+    }
+
+    pub fn is_cached(&self, track_url: &TrackURL, track: &Track) -> bool {
+        cache_path(track, track_url, &self.audio_cache_dir).exists()
+    }
+
+    pub fn fallback_quality(
+        &self,
+        max_audio_quality: &AudioQuality,
+        track: &Track,
+        time_available: Duration,
+    ) -> Option<AudioQuality> {
+        let bytes_per_second = (*self.bytes_per_second.lock())?;
+
+        let requested = match max_audio_quality {
+            AudioQuality::HIFI96 | AudioQuality::HIFI192 if !track.hires_available => {
+                AudioQuality::CD
+            }
+            quality => quality.clone(),
+        };
+
+        let mut quality = requested.clone();
+        loop {
+            let estimated_size =
+                estimated_bytes_per_second(&quality) * track.duration_seconds as f64;
+            let download_time = Duration::from_secs_f64(estimated_size / bytes_per_second);
+
+            if download_time <= time_available {
+                break;
+            }
+
+            match quality.lower() {
+                Some(lower) => quality = lower,
+                None => break,
+            }
         }
+
+        (quality != requested).then_some(quality)
     }
 
     pub fn done_buffering(&self) -> Receiver<PathBuf> {
@@ -51,8 +99,10 @@ impl Downloader {
             self.download_handle = None;
         };
 
-        let cache_path = cache_path(track, &track_url.mime_type, &self.audio_cache_dir);
-        self.database.set_cache_entry(cache_path.as_path()).await;
+        let cache_path = cache_path(track, &track_url, &self.audio_cache_dir);
+        self.database
+            .set_cache_entry(cache_path.as_path(), track.id)
+            .await;
 
         if cache_path.exists() {
             return Some(cache_path);
@@ -60,9 +110,11 @@ impl Downloader {
 
         let done_buffering = self.done_buffering_tx.clone();
         let broadcast = self.broadcast.clone();
+        let bytes_per_second = self.bytes_per_second.clone();
 
         tracing::info!("Downloading: {}", track.title);
         let handle = tokio::spawn(async move {
+            let started = Instant::now();
             let Ok(resp) = reqwest::get(&track_url.url).await else {
                 broadcast.send_error("Unable to get track audio file".to_string());
                 return;
@@ -75,6 +127,12 @@ impl Downloader {
 
             let bytes = body.to_vec();
 
+            if bytes.len() >= MIN_MEASURED_BYTES {
+                let measured = bytes.len() as f64 / started.elapsed().as_secs_f64();
+                tracing::info!("Download throughput: {:.0} kB/s", measured / 1000.0);
+                *bytes_per_second.lock() = Some(measured);
+            }
+
             if let Some(parent) = cache_path.parent()
                 && let Err(e) = fs::create_dir_all(parent)
             {
@@ -97,39 +155,87 @@ impl Downloader {
     }
 }
 
-fn cache_path(track: &Track, mime: &str, audio_cache_dir: &Path) -> PathBuf {
+pub fn find_cached_track(track: &Track, audio_cache_dir: &Path) -> Option<PathBuf> {
+    let album_dir = album_dir_name(track);
+    let legacy_stem = track_file_prefix(track);
+    let prefix = format!("{legacy_stem}_");
+
+    fs::read_dir(audio_cache_dir)
+        .ok()?
+        .filter_map(|artist_dir| artist_dir.ok())
+        .filter_map(|artist_dir| fs::read_dir(artist_dir.path().join(&album_dir)).ok())
+        .flatten()
+        .filter_map(|file| file.ok().map(|file| file.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension != "partial"))
+        .filter_map(|path| {
+            let stem = path.file_stem()?.to_str()?;
+            // Files cached before the format id was added rank below any known format
+            let format_id = match stem.strip_prefix(&prefix) {
+                Some(format_id) => format_id.parse::<u32>().ok()?,
+                None if stem == legacy_stem => 0,
+                None => return None,
+            };
+            Some((format_id, path))
+        })
+        .max_by_key(|(format_id, _)| *format_id)
+        .map(|(_, path)| path)
+}
+
+/// Path of the cached file, preferring one from before file names carried the format id.
+fn cache_path(track: &Track, track_url: &TrackURL, audio_cache_dir: &Path) -> PathBuf {
+    let path = format_cache_path(track, track_url, audio_cache_dir);
+    if path.exists() {
+        return path;
+    }
+
+    let legacy = path.with_file_name(format!(
+        "{}.{}",
+        track_file_prefix(track),
+        guess_extension(&track_url.mime_type)
+    ));
+    if legacy.exists() { legacy } else { path }
+}
+
+fn format_cache_path(track: &Track, track_url: &TrackURL, audio_cache_dir: &Path) -> PathBuf {
     let artist_name = track.artist_name.as_deref().unwrap_or("unknown");
     let artist_id = track
         .artist_id
         .map(|id| id.to_string())
         .unwrap_or_else(|| "unknown".to_string());
-    let album_title = track.album_title.as_deref().unwrap_or("unknown");
-    let album_id = track.album_id.as_deref().unwrap_or("unknown");
-    let track_title = &track.title;
 
     let artist_dir = format!(
         "{} ({})",
         sanitize_name(artist_name),
         sanitize_name(&artist_id),
     );
-    let album_dir = format!(
-        "{} ({})",
-        sanitize_name(album_title),
-        sanitize_name(album_id),
-    );
-    let extension = guess_extension(mime);
+    let extension = guess_extension(&track_url.mime_type);
     let track_file = format!(
         "{}_{}.{extension}",
-        track.number,
-        sanitize_name(track_title)
+        track_file_prefix(track),
+        track_url.format_id
     );
 
     audio_cache_dir
         .join(artist_dir)
-        .join(album_dir)
+        .join(album_dir_name(track))
         .join(track_file)
 }
 
+fn album_dir_name(track: &Track) -> String {
+    let album_title = track.album_title.as_deref().unwrap_or("unknown");
+    let album_id = track.album_id.as_deref().unwrap_or("unknown");
+
+    format!(
+        "{} ({})",
+        sanitize_name(album_title),
+        sanitize_name(album_id),
+    )
+}
+
+fn track_file_prefix(track: &Track) -> String {
+    format!("{}_{}", track.number, sanitize_name(&track.title))
+}
+
 fn sanitize_name(input: &str) -> String {
     let mut s: String = input
         .chars()
@@ -165,6 +271,15 @@ fn sanitize_name(input: &str) -> String {
     out.chars().take(MAX).collect()
 }
 
+fn estimated_bytes_per_second(quality: &AudioQuality) -> f64 {
+    match quality {
+        AudioQuality::Mp3 => 40_000.0,
+        AudioQuality::CD => 110_000.0,
+        AudioQuality::HIFI96 => 400_000.0,
+        AudioQuality::HIFI192 => 750_000.0,
+    }
+}
+
 fn guess_extension(mime: &str) -> String {
     match mime {
         m if m.contains("mp4") => "mp4".to_string(),
@@ -173,3 +288,30 @@ fn guess_extension(mime: &str) -> String {
         _ => "unknown".to_string(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_files_cached_before_format_ids() {
+        let dir =
+            std::env::temp_dir().join(format!("qobuz-player-downloader-{}", std::process::id()));
+        let track = Track {
+            number: 3,
+            title: "Song".into(),
+            ..Default::default()
+        };
+        let album_dir = dir.join("unknown (unknown)").join(album_dir_name(&track));
+        fs::create_dir_all(&album_dir).unwrap();
+
+        fs::write(album_dir.join("3_Song.flac"), b"").unwrap();
+        let legacy = find_cached_track(&track, &dir);
+        fs::write(album_dir.join("3_Song_6.flac"), b"").unwrap();
+        let current = find_cached_track(&track, &dir);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(legacy, Some(album_dir.join("3_Song.flac")));
+        assert_eq!(current, Some(album_dir.join("3_Song_6.flac")));
+    }
+}
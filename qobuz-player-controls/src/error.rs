@@ -59,6 +59,26 @@ pub enum Error {
     DatabaseFileError {
         message: String,
     },
+    #[snafu(display("Unable to export: {message}"))]
+    Export {
+        message: String,
+    },
+    #[snafu(display("Unable to import: {message}"))]
+    Import {
+        message: String,
+    },
+    #[snafu(display("Plugin error: {message}"))]
+    Plugin {
+        message: String,
+    },
+    #[snafu(display("Unable to render: {message}"))]
+    Render {
+        message: String,
+    },
+    #[snafu(display("Unable to analyse: {message}"))]
+    Analysis {
+        message: String,
+    },
 }
 
 impl From<sqlx::migrate::MigrateError> for Error {
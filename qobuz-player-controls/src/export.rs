@@ -0,0 +1,164 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use qobuz_player_models::{Album, Track};
+
+use crate::{
+    Result,
+    client::Client,
+    downloader::find_cached_track,
+    error::Error,
+    tags::{TrackTags, is_taggable, write_tags},
+};
+
+pub const DEFAULT_EXPORT_TEMPLATE: &str = "{artist}/{album}/{number} - {title}";
+
+#[derive(Debug, Default)]
+pub struct ExportReport {
+    pub exported: Vec<PathBuf>,
+    pub not_cached: Vec<String>,
+    pub untagged: Vec<PathBuf>,
+}
+
+pub async fn export_album(
+    client: &Client,
+    audio_cache_dir: &Path,
+    album_id: &str,
+    export_dir: &Path,
+    template: &str,
+) -> Result<ExportReport> {
+    let album = client.album(album_id).await?;
+
+    let cover = match reqwest::get(&album.image).await {
+        Ok(response) => response.bytes().await.ok().map(|bytes| bytes.to_vec()),
+        Err(e) => {
+            tracing::warn!("Unable to download album cover: {e}");
+            None
+        }
+    };
+
+    let audio_cache_dir = audio_cache_dir.to_path_buf();
+    let export_dir = export_dir.to_path_buf();
+    let template = template.to_string();
+
+    tokio::task::spawn_blocking(move || {
+        export_cached_tracks(&album, cover, &audio_cache_dir, &export_dir, &template)
+    })
+    .await
+    .expect("infallible")
+}
+
+fn export_cached_tracks(
+    album: &Album,
+    cover: Option<Vec<u8>>,
+    audio_cache_dir: &Path,
+    export_dir: &Path,
+    template: &str,
+) -> Result<ExportReport> {
+    let mut report = ExportReport::default();
+
+    let cached_tracks: Vec<(&Track, Option<PathBuf>)> = album
+        .tracks
+        .iter()
+        .map(|track| (track, find_cached_track(track, audio_cache_dir)))
+        .collect();
+
+    if let Some((track, _)) = cached_tracks
+        .iter()
+        .find(|(_, cached)| cached.as_deref().is_some_and(|cached| !is_taggable(cached)))
+    {
+        return Err(Error::Export {
+            message: format!(
+                "{} is cached in a format that cannot be tagged",
+                track.title
+            ),
+        });
+    }
+
+    for (track, cached) in cached_tracks {
+        let Some(cached) = cached else {
+            report.not_cached.push(track.title.clone());
+            continue;
+        };
+
+        let mut target = export_dir.join(render_template(template, album, track));
+        if let Some(extension) = cached.extension() {
+            let file_name = target
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            target.set_file_name(format!("{file_name}.{}", extension.to_string_lossy()));
+        }
+
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).map_err(|e| export_error(parent, e))?;
+        }
+        fs::copy(&cached, &target).map_err(|e| export_error(&target, e))?;
+
+        let tags = TrackTags {
+            title: track.title.clone(),
+            artist: track.artist_name.clone(),
+            album_artist: Some(album.artist.name.clone()),
+            album: Some(album.title.clone()),
+            track_number: track.number,
+            year: Some(album.release_year).filter(|year| *year != 0),
+            isrc: track.isrc.clone(),
+            cover: cover.clone(),
+        };
+
+        if let Err(e) = write_tags(&target, &tags) {
+            tracing::warn!("Unable to tag {}: {e}", target.to_string_lossy());
+            fs::remove_file(&target).map_err(|e| export_error(&target, e))?;
+            report.untagged.push(target);
+            continue;
+        }
+
+        report.exported.push(target);
+    }
+
+    Ok(report)
+}
+
+fn render_template(template: &str, album: &Album, track: &Track) -> PathBuf {
+    template
+        .split('/')
+        .filter(|component| !component.trim().is_empty())
+        .map(|component| {
+            let rendered = component
+                .replace("{artist}", &album.artist.name)
+                .replace("{album}", &album.title)
+                .replace("{year}", &album.release_year.to_string())
+                .replace("{number}", &format!("{:02}", track.number))
+                .replace("{title}", &track.title);
+
+            sanitize_path_component(&rendered)
+        })
+        .collect()
+}
+
+pub(crate) fn sanitize_path_component(input: &str) -> String {
+    let sanitized: String = input
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' | '\0' => '_',
+            c if c.is_control() => '_',
+            _ => c,
+        })
+        .collect();
+
+    let sanitized = sanitized.trim_matches([' ', '.']);
+
+    if sanitized.is_empty() {
+        "unknown".to_string()
+    } else {
+        sanitized.to_string()
+    }
+}
+
+fn export_error(path: &Path, error: std::io::Error) -> Error {
+    Error::Export {
+        message: format!("{}: {error}", path.to_string_lossy()),
+    }
+}
@@ -0,0 +1,371 @@
+use std::{
+    ffi::{CStr, CString, c_char, c_int, c_ulong, c_void},
+    ptr,
+    sync::{
+        OnceLock,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use libloading::Library;
+use parking_lot::Mutex;
+use rodio::mixer::{Mixer, MixerSource, mixer};
+
+use crate::{Result, error::Error};
+
+#[cfg(target_os = "macos")]
+const LIBRARY_NAMES: &[&str] = &["libjack.0.dylib", "libjack.dylib"];
+#[cfg(target_os = "windows")]
+const LIBRARY_NAMES: &[&str] = &["libjack64.dll", "libjack.dll"];
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+const LIBRARY_NAMES: &[&str] = &["libjack.so.0", "libjack.so"];
+
+const CLIENT_NAME: &str = "qobuz-player";
+const PORT_NAMES: [&str; 2] = ["out_left", "out_right"];
+const AUDIO_PORT_TYPE: &CStr = c"32 bit float mono audio";
+
+const JACK_NO_START_SERVER: c_int = 0x01;
+const JACK_PORT_IS_INPUT: c_ulong = 0x1;
+const JACK_PORT_IS_OUTPUT: c_ulong = 0x2;
+
+/// How long to wait before trying to reach a JACK server that was not running.
+const DEVICE_CLIENT_RETRY: Duration = Duration::from_secs(30);
+
+pub const JACK_DEVICE_PREFIX: &str = "jack:";
+
+type ClientOpen = unsafe extern "C" fn(*const c_char, c_int, *mut c_int, ...) -> *mut c_void;
+type ClientClose = unsafe extern "C" fn(*mut c_void) -> c_int;
+type PortRegister =
+    unsafe extern "C" fn(*mut c_void, *const c_char, *const c_char, c_ulong, c_ulong) -> *mut c_void;
+type ProcessCallback = unsafe extern "C" fn(u32, *mut c_void) -> c_int;
+type SetProcessCallback = unsafe extern "C" fn(*mut c_void, ProcessCallback, *mut c_void) -> c_int;
+type ShutdownCallback = unsafe extern "C" fn(*mut c_void);
+type OnShutdown = unsafe extern "C" fn(*mut c_void, ShutdownCallback, *mut c_void);
+type Activate = unsafe extern "C" fn(*mut c_void) -> c_int;
+type GetSampleRate = unsafe extern "C" fn(*mut c_void) -> u32;
+type PortGetBuffer = unsafe extern "C" fn(*mut c_void, u32) -> *mut c_void;
+type GetPorts =
+    unsafe extern "C" fn(*mut c_void, *const c_char, *const c_char, c_ulong) -> *mut *const c_char;
+type Free = unsafe extern "C" fn(*mut c_void);
+type Connect = unsafe extern "C" fn(*mut c_void, *const c_char, *const c_char) -> c_int;
+type PortName = unsafe extern "C" fn(*const c_void) -> *const c_char;
+
+struct JackLibrary {
+    _library: Library,
+    client_open: ClientOpen,
+    client_close: ClientClose,
+    port_register: PortRegister,
+    set_process_callback: SetProcessCallback,
+    on_shutdown: OnShutdown,
+    activate: Activate,
+    deactivate: Activate,
+    get_sample_rate: GetSampleRate,
+    port_get_buffer: PortGetBuffer,
+    get_ports: GetPorts,
+    free: Free,
+    connect: Connect,
+    port_name: PortName,
+}
+
+impl JackLibrary {
+    fn load() -> Option<Self> {
+        let library = LIBRARY_NAMES
+            .iter()
+            .find_map(|name| unsafe { Library::new(name) }.ok())?;
+
+        unsafe {
+            Some(Self {
+                client_open: *library.get(b"jack_client_open\0").ok()?,
+                client_close: *library.get(b"jack_client_close\0").ok()?,
+                port_register: *library.get(b"jack_port_register\0").ok()?,
+                set_process_callback: *library.get(b"jack_set_process_callback\0").ok()?,
+                on_shutdown: *library.get(b"jack_on_shutdown\0").ok()?,
+                activate: *library.get(b"jack_activate\0").ok()?,
+                deactivate: *library.get(b"jack_deactivate\0").ok()?,
+                get_sample_rate: *library.get(b"jack_get_sample_rate\0").ok()?,
+                port_get_buffer: *library.get(b"jack_port_get_buffer\0").ok()?,
+                get_ports: *library.get(b"jack_get_ports\0").ok()?,
+                free: *library.get(b"jack_free\0").ok()?,
+                connect: *library.get(b"jack_connect\0").ok()?,
+                port_name: *library.get(b"jack_port_name\0").ok()?,
+                _library: library,
+            })
+        }
+    }
+
+    fn open_client(&self, name: &str) -> Option<*mut c_void> {
+        let name = CString::new(name).ok()?;
+        let mut status = 0;
+        let client =
+            unsafe { (self.client_open)(name.as_ptr(), JACK_NO_START_SERVER, &mut status) };
+
+        (!client.is_null()).then_some(client)
+    }
+
+    fn input_ports(&self, client: *mut c_void) -> Vec<String> {
+        let ports = unsafe {
+            (self.get_ports)(
+                client,
+                ptr::null(),
+                AUDIO_PORT_TYPE.as_ptr(),
+                JACK_PORT_IS_INPUT,
+            )
+        };
+        if ports.is_null() {
+            return vec![];
+        }
+
+        let mut names = vec![];
+        let mut index = 0;
+        loop {
+            let port = unsafe { *ports.add(index) };
+            if port.is_null() {
+                break;
+            }
+            names.push(unsafe { CStr::from_ptr(port) }.to_string_lossy().into_owned());
+            index += 1;
+        }
+
+        unsafe { (self.free)(ports as *mut c_void) };
+        names
+    }
+}
+
+fn library() -> Option<&'static JackLibrary> {
+    static LIBRARY: OnceLock<Option<JackLibrary>> = OnceLock::new();
+    LIBRARY.get_or_init(JackLibrary::load).as_ref()
+}
+
+/// Client kept open for listing ports, since the device watcher lists them
+/// every few seconds.
+struct DeviceClient {
+    client: Option<*mut c_void>,
+    last_attempt: Option<Instant>,
+}
+
+// The client is only used while the mutex is held.
+unsafe impl Send for DeviceClient {}
+
+static DEVICE_CLIENT: Mutex<DeviceClient> = Mutex::new(DeviceClient {
+    client: None,
+    last_attempt: None,
+});
+static DEVICE_CLIENT_SHUTDOWN: AtomicBool = AtomicBool::new(false);
+
+unsafe extern "C" fn device_client_shutdown(_arg: *mut c_void) {
+    DEVICE_CLIENT_SHUTDOWN.store(true, Ordering::Relaxed);
+}
+
+impl DeviceClient {
+    fn get(&mut self, library: &JackLibrary) -> Option<*mut c_void> {
+        if DEVICE_CLIENT_SHUTDOWN.swap(false, Ordering::Relaxed)
+            && let Some(client) = self.client.take()
+        {
+            unsafe { (library.client_close)(client) };
+        }
+
+        if self.client.is_none()
+            && self
+                .last_attempt
+                .is_none_or(|attempt| attempt.elapsed() >= DEVICE_CLIENT_RETRY)
+        {
+            self.last_attempt = Some(Instant::now());
+            self.client = library
+                .open_client(&format!("{CLIENT_NAME}-devices"))
+                .inspect(|&client| unsafe {
+                    // Shutdown is only reported to active clients
+                    (library.on_shutdown)(client, device_client_shutdown, ptr::null_mut());
+                    (library.activate)(client);
+                });
+        }
+
+        self.client
+    }
+}
+
+pub fn list_jack_devices() -> Vec<String> {
+    let Some(library) = library() else {
+        return vec![];
+    };
+    let mut device_client = DEVICE_CLIENT.lock();
+    let Some(client) = device_client.get(library) else {
+        return vec![];
+    };
+
+    let mut clients: Vec<String> = vec![];
+    for port in library.input_ports(client) {
+        if let Some((name, _)) = port.split_once(':')
+            && !clients.iter().any(|client| client == name)
+        {
+            clients.push(name.to_string());
+        }
+    }
+    drop(device_client);
+
+    std::iter::once(JACK_DEVICE_PREFIX.to_string())
+        .chain(
+            clients
+                .into_iter()
+                .map(|client| format!("{JACK_DEVICE_PREFIX}{client}")),
+        )
+        .collect()
+}
+
+struct ProcessState {
+    source: Mutex<MixerSource>,
+    ports: [*mut c_void; 2],
+    port_get_buffer: PortGetBuffer,
+}
+
+unsafe extern "C" fn process(frames: u32, arg: *mut c_void) -> c_int {
+    let state = unsafe { &*(arg as *const ProcessState) };
+    let buffers = state.ports.map(|port| unsafe {
+        std::slice::from_raw_parts_mut(
+            (state.port_get_buffer)(port, frames) as *mut f32,
+            frames as usize,
+        )
+    });
+    let [left, right] = buffers;
+
+    match state.source.try_lock() {
+        Some(mut source) => {
+            for (left, right) in left.iter_mut().zip(right.iter_mut()) {
+                *left = source.next().unwrap_or(0.0);
+                *right = source.next().unwrap_or(0.0);
+            }
+        }
+        None => {
+            left.fill(0.0);
+            right.fill(0.0);
+        }
+    }
+
+    0
+}
+
+pub struct JackStream {
+    library: &'static JackLibrary,
+    client: *mut c_void,
+    state: *mut ProcessState,
+    mixer: Mixer,
+    sample_rate: u32,
+}
+
+// The client and process state are only touched by the JACK process thread
+// between activation and close.
+unsafe impl Send for JackStream {}
+
+impl JackStream {
+    pub fn open(target: &str) -> Result<Self> {
+        let library = library().ok_or_else(|| jack_error("JACK library not found"))?;
+        let client = library
+            .open_client(CLIENT_NAME)
+            .ok_or_else(|| jack_error("JACK server not running"))?;
+
+        let mut ports = [ptr::null_mut(); 2];
+        for (port, name) in ports.iter_mut().zip(PORT_NAMES) {
+            let name = CString::new(name).expect("infallible");
+            *port = unsafe {
+                (library.port_register)(
+                    client,
+                    name.as_ptr(),
+                    AUDIO_PORT_TYPE.as_ptr(),
+                    JACK_PORT_IS_OUTPUT,
+                    0,
+                )
+            };
+            if port.is_null() {
+                unsafe { (library.client_close)(client) };
+                return Err(jack_error("Unable to register JACK ports"));
+            }
+        }
+
+        let sample_rate = unsafe { (library.get_sample_rate)(client) };
+        let (mixer, source) = mixer(PORT_NAMES.len() as u16, sample_rate);
+        let state = Box::into_raw(Box::new(ProcessState {
+            source: Mutex::new(source),
+            ports,
+            port_get_buffer: library.port_get_buffer,
+        }));
+
+        let stream = Self {
+            library,
+            client,
+            state,
+            mixer,
+            sample_rate,
+        };
+
+        unsafe {
+            if (library.set_process_callback)(client, process, state as *mut c_void) != 0
+                || (library.activate)(client) != 0
+            {
+                return Err(jack_error("Unable to activate JACK client"));
+            }
+        }
+
+        stream.connect(target, ports);
+        Ok(stream)
+    }
+
+    pub fn mixer(&self) -> &Mixer {
+        &self.mixer
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn connect(&self, target: &str, ports: [*mut c_void; 2]) {
+        let destinations = if target.contains(',') {
+            target.split(',').map(|port| port.trim().to_string()).collect()
+        } else if target.is_empty() {
+            vec![]
+        } else {
+            let prefix = format!("{target}:");
+            self.library
+                .input_ports(self.client)
+                .into_iter()
+                .filter(|port| port.starts_with(&prefix))
+                .take(2)
+                .collect::<Vec<_>>()
+        };
+
+        if !target.is_empty() && destinations.is_empty() {
+            tracing::warn!("No JACK input ports found for '{}'", target);
+        }
+
+        for (port, destination) in ports.iter().zip(destinations.iter().cycle()) {
+            let Ok(destination) = CString::new(destination.as_str()) else {
+                continue;
+            };
+            let result = unsafe {
+                let source = (self.library.port_name)(*port);
+                (self.library.connect)(self.client, source, destination.as_ptr())
+            };
+            if result != 0 {
+                tracing::warn!(
+                    "Unable to connect JACK port to {}",
+                    destination.to_string_lossy()
+                );
+            }
+        }
+    }
+}
+
+impl Drop for JackStream {
+    fn drop(&mut self) {
+        unsafe {
+            (self.library.deactivate)(self.client);
+            (self.library.client_close)(self.client);
+            drop(Box::from_raw(self.state));
+        }
+    }
+}
+
+fn jack_error(message: &str) -> Error {
+    Error::StreamError {
+        message: format!("{message} (JACK device unavailable)"),
+    }
+}
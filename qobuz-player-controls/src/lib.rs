@@ -5,26 +5,46 @@ use tokio::sync::{broadcast, watch};
 
 pub use qobuz_player_client::client::AudioQuality;
 
+pub mod audio_cache;
+pub mod channel_mix;
+pub mod chords;
+pub mod clap;
 pub mod client;
 pub mod controls;
+pub mod crossfeed;
 pub mod database;
+pub mod dither;
 pub mod downloader;
 pub mod error;
+pub mod export;
+pub mod jack;
+pub mod meter;
 pub mod notification;
+pub mod output;
+pub mod pitch;
 pub mod player;
+pub mod playlist_file;
+pub mod render;
+pub mod schedule;
 pub mod simple_cache;
 pub mod sink;
 pub mod stretch_source_signalsmith;
+pub mod tags;
 pub mod tracklist;
+pub mod waveform;
 
 pub use sink::{list_audio_devices, get_default_device_name, AudioDevice};
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
 pub type PositionReceiver = watch::Receiver<Duration>;
+pub type LevelsReceiver = watch::Receiver<meter::Levels>;
 pub type VolumeReceiver = watch::Receiver<f32>;
 pub type StatusReceiver = watch::Receiver<Status>;
 pub type TracklistReceiver = watch::Receiver<Tracklist>;
+pub type RepeatModeReceiver = watch::Receiver<tracklist::RepeatMode>;
+/// Time left before the sleep timer pauses playback.
+pub type SleepTimerReceiver = watch::Receiver<Option<Duration>>;
 
 #[derive(Default, Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
 pub enum Status {
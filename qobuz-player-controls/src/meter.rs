@@ -0,0 +1,296 @@
+use std::{sync::Arc, thread, time::Duration};
+
+use rodio::{ChannelCount, SampleRate, Source, source::SeekError};
+use rtrb::{Consumer, Producer, RingBuffer};
+use rustfft::{Fft, FftPlanner, num_complex::Complex};
+use tokio::sync::watch;
+
+pub const FRAMES_PER_SECOND: u32 = 15;
+pub const SPECTRUM_BANDS: usize = 24;
+
+const FFT_SIZE: usize = 2048;
+const LOWEST_BAND_HZ: f32 = 40.0;
+const HIGHEST_BAND_HZ: f32 = 16_000.0;
+const SPECTRUM_FLOOR_DB: f32 = -72.0;
+/// Samples waiting for the analysis thread, over a second of 96 kHz stereo.
+const RING_CAPACITY: usize = 1 << 18;
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Output levels for one metering frame. Peak and RMS are linear per
+/// channel, spectrum bands are log spaced and scaled from 0.0 at -72 dBFS to
+/// 1.0 at full scale.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Levels {
+    pub peak: Vec<f32>,
+    pub rms: Vec<f32>,
+    pub spectrum: Vec<f32>,
+}
+
+/// Passes samples through untouched while copying them to a thread that
+/// publishes levels of the final output. The audio thread only writes to a
+/// preallocated ring buffer, dropping whole frames when it is full.
+pub struct MeterSource<S> {
+    inner: S,
+    producer: Producer<f32>,
+    frame: Vec<f32>,
+}
+
+impl<S> MeterSource<S>
+where
+    S: Source<Item = f32>,
+{
+    pub fn new(inner: S, sender: watch::Sender<Levels>) -> Self {
+        let channels = inner.channels().max(1) as usize;
+        let (producer, consumer) = RingBuffer::new(RING_CAPACITY);
+        let analysis = Analysis::new(channels, inner.sample_rate(), sender);
+        thread::spawn(move || analysis.run(consumer));
+
+        Self {
+            inner,
+            producer,
+            frame: Vec::with_capacity(channels),
+        }
+    }
+}
+
+impl<S> Iterator for MeterSource<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let sample = self.inner.next()?;
+
+        self.frame.push(sample);
+        if self.frame.len() == self.frame.capacity() {
+            if self.producer.slots() >= self.frame.len() {
+                for sample in self.frame.drain(..) {
+                    _ = self.producer.push(sample);
+                }
+            } else {
+                self.frame.clear();
+            }
+        }
+
+        Some(sample)
+    }
+}
+
+impl<S> Source for MeterSource<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_span_len(&self) -> Option<usize> {
+        self.inner.current_span_len()
+    }
+
+    fn channels(&self) -> ChannelCount {
+        self.inner.channels()
+    }
+
+    fn sample_rate(&self) -> SampleRate {
+        self.inner.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.inner.try_seek(pos)
+    }
+}
+
+/// Levels accumulated from the ring buffer. Analysis is skipped while
+/// nobody is subscribed.
+struct Analysis {
+    sender: watch::Sender<Levels>,
+    channels: usize,
+    sample_rate: u32,
+    frame_len: usize,
+    channel: usize,
+    frames: usize,
+    peak: Vec<f32>,
+    sum_squares: Vec<f32>,
+    mono: f32,
+    window: Vec<f32>,
+    window_position: usize,
+    ordered_window: Vec<f32>,
+    analyzer: SpectrumAnalyzer,
+}
+
+impl Analysis {
+    fn new(channels: usize, sample_rate: u32, sender: watch::Sender<Levels>) -> Self {
+        Self {
+            sender,
+            channels,
+            sample_rate,
+            frame_len: (sample_rate / FRAMES_PER_SECOND).max(1) as usize,
+            channel: 0,
+            frames: 0,
+            peak: vec![0.0; channels],
+            sum_squares: vec![0.0; channels],
+            mono: 0.0,
+            window: vec![0.0; FFT_SIZE],
+            window_position: 0,
+            ordered_window: vec![0.0; FFT_SIZE],
+            analyzer: SpectrumAnalyzer::new(),
+        }
+    }
+
+    /// Runs until the source is dropped and every sample is analysed.
+    fn run(mut self, mut consumer: Consumer<f32>) {
+        loop {
+            while let Ok(sample) = consumer.pop() {
+                self.add(sample);
+            }
+            if consumer.is_abandoned() && consumer.is_empty() {
+                break;
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    fn add(&mut self, sample: f32) {
+        self.peak[self.channel] = self.peak[self.channel].max(sample.abs());
+        self.sum_squares[self.channel] += sample * sample;
+        self.mono += sample;
+        self.channel += 1;
+
+        if self.channel == self.channels {
+            self.window[self.window_position] = self.mono / self.channels as f32;
+            self.window_position = (self.window_position + 1) % FFT_SIZE;
+            self.channel = 0;
+            self.mono = 0.0;
+            self.frames += 1;
+
+            if self.frames == self.frame_len {
+                self.publish();
+            }
+        }
+    }
+
+    fn publish(&mut self) {
+        if self.sender.receiver_count() > 0 {
+            let (newest, oldest) = self.window.split_at(self.window_position);
+            self.ordered_window[..oldest.len()].copy_from_slice(oldest);
+            self.ordered_window[oldest.len()..].copy_from_slice(newest);
+
+            self.sender.send_replace(Levels {
+                peak: self.peak.clone(),
+                rms: self
+                    .sum_squares
+                    .iter()
+                    .map(|sum| (sum / self.frames as f32).sqrt())
+                    .collect(),
+                spectrum: self
+                    .analyzer
+                    .spectrum(&self.ordered_window, self.sample_rate),
+            });
+        }
+
+        self.frames = 0;
+        self.peak.fill(0.0);
+        self.sum_squares.fill(0.0);
+    }
+}
+
+struct SpectrumAnalyzer {
+    fft: Arc<dyn Fft<f32>>,
+    hann: Vec<f32>,
+    buffer: Vec<Complex<f32>>,
+}
+
+impl SpectrumAnalyzer {
+    fn new() -> Self {
+        let hann = (0..FFT_SIZE)
+            .map(|i| {
+                let phase = 2.0 * std::f32::consts::PI * i as f32 / (FFT_SIZE - 1) as f32;
+                0.5 - 0.5 * phase.cos()
+            })
+            .collect();
+
+        Self {
+            fft: FftPlanner::new().plan_fft_forward(FFT_SIZE),
+            hann,
+            buffer: vec![Complex::default(); FFT_SIZE],
+        }
+    }
+
+    fn spectrum(&mut self, window: &[f32], sample_rate: u32) -> Vec<f32> {
+        for ((bin, sample), hann) in self.buffer.iter_mut().zip(window).zip(&self.hann) {
+            *bin = Complex::new(sample * hann, 0.0);
+        }
+        self.fft.process(&mut self.buffer);
+
+        // A full scale sine through a Hann window peaks at a quarter of the
+        // FFT size.
+        let reference = FFT_SIZE as f32 / 4.0;
+        let bin_hz = sample_rate as f32 / FFT_SIZE as f32;
+        let highest = HIGHEST_BAND_HZ.min(sample_rate as f32 / 2.0);
+
+        (0..SPECTRUM_BANDS)
+            .map(|band| {
+                let low = band_edge(band, highest);
+                let high = band_edge(band + 1, highest);
+                let first = ((low / bin_hz).ceil() as usize).clamp(1, FFT_SIZE / 2 - 1);
+                let last = ((high / bin_hz).ceil() as usize).clamp(first + 1, FFT_SIZE / 2);
+
+                let magnitude = self.buffer[first..last]
+                    .iter()
+                    .map(|bin| bin.norm())
+                    .fold(0.0, f32::max);
+                let db = 20.0 * (magnitude / reference).max(1e-6).log10();
+                ((db - SPECTRUM_FLOOR_DB) / -SPECTRUM_FLOOR_DB).clamp(0.0, 1.0)
+            })
+            .collect()
+    }
+}
+
+fn band_edge(band: usize, highest: f32) -> f32 {
+    LOWEST_BAND_HZ * (highest / LOWEST_BAND_HZ).powf(band as f32 / SPECTRUM_BANDS as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    #[test]
+    fn measures_levels_and_spectrum() {
+        let sample_rate = 48_000;
+        let samples = (0..sample_rate)
+            .flat_map(|i| {
+                let sample = 0.5
+                    * (2.0 * std::f32::consts::PI * 1_000.0 * i as f32 / sample_rate as f32).sin();
+                [sample, 0.0]
+            })
+            .collect::<Vec<_>>();
+
+        let (sender, receiver) = watch::channel(Levels::default());
+        let metered = MeterSource::new(SamplesBuffer::new(2, sample_rate, samples.clone()), sender);
+        assert_eq!(metered.collect::<Vec<_>>(), samples);
+
+        // The analysis thread publishes the last frame and stops once the
+        // source is gone
+        while receiver.has_changed().is_ok() {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        let levels = receiver.borrow().clone();
+        assert!((levels.peak[0] - 0.5).abs() < 0.01);
+        assert!((levels.rms[0] - 0.5 / 2f32.sqrt()).abs() < 0.01);
+        assert_eq!(levels.peak[1], 0.0);
+
+        let loudest = levels
+            .spectrum
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map(|(band, _)| band)
+            .unwrap();
+        let highest = HIGHEST_BAND_HZ.min(sample_rate as f32 / 2.0);
+        assert!(band_edge(loudest, highest) <= 1_000.0);
+        assert!(band_edge(loudest + 1, highest) >= 1_000.0);
+    }
+}
@@ -0,0 +1,597 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use rodio::{
+    cpal::{
+        SampleFormat,
+        traits::{DeviceTrait, HostTrait},
+    },
+    mixer::{Mixer, MixerSource, mixer},
+};
+
+use crate::{
+    Result,
+    error::Error,
+    jack::{JACK_DEVICE_PREFIX, JackStream},
+};
+
+const HEADLESS_CHANNELS: u16 = 2;
+const HEADLESS_CHUNK: Duration = Duration::from_millis(10);
+const NULL_DEVICE_NAME: &str = "null";
+const WAV_DEVICE_PREFIX: &str = "wav:";
+const WAV_HEADER_LEN: usize = 44;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum OutputBackend {
+    #[default]
+    Cpal,
+    Null,
+    Wav(PathBuf),
+    Jack(String),
+}
+
+impl OutputBackend {
+    pub fn from_device_name(device_name: &str) -> Option<Self> {
+        if device_name == NULL_DEVICE_NAME {
+            return Some(Self::Null);
+        }
+
+        if let Some(target) = device_name.strip_prefix(JACK_DEVICE_PREFIX) {
+            return Some(Self::Jack(target.to_string()));
+        }
+
+        device_name
+            .strip_prefix(WAV_DEVICE_PREFIX)
+            .filter(|path| !path.is_empty())
+            .map(|path| Self::Wav(PathBuf::from(path)))
+    }
+
+    pub fn is_headless(&self) -> bool {
+        matches!(self, Self::Null | Self::Wav(_))
+    }
+
+    pub fn is_headless_device(device_name: &str) -> bool {
+        Self::from_device_name(device_name).is_some_and(|backend| backend.is_headless())
+    }
+
+    /// Opens a stream on the backend. With `append`, the wav backend continues
+    /// the recording it wrote earlier instead of starting a new file.
+    pub fn open(
+        &self,
+        sample_rate: u32,
+        device_name: Option<&str>,
+        sample_format: Option<OutputSampleFormat>,
+        append: bool,
+    ) -> Result<OutputStream> {
+        match self {
+            Self::Cpal => {
+                let mut stream = open_cpal_stream(sample_rate, device_name, sample_format)?;
+                stream.log_on_drop(false);
+                Ok(OutputStream::Cpal(stream))
+            }
+            Self::Null => Ok(OutputStream::Headless(HeadlessStream::start(
+                sample_rate,
+                None,
+            ))),
+            Self::Wav(path) => {
+                let writer = WavWriter::open(path, sample_rate, HEADLESS_CHANNELS, append)
+                    .map_err(|e| Error::StreamError {
+                        message: format!("Failed to open {}: {e}", path.to_string_lossy()),
+                    })?;
+                Ok(OutputStream::Headless(HeadlessStream::start(
+                    sample_rate,
+                    Some(writer),
+                )))
+            }
+            Self::Jack(target) => Ok(OutputStream::Jack(JackStream::open(target)?)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputSampleFormat {
+    I16,
+    I24,
+    I32,
+    F32,
+}
+
+impl OutputSampleFormat {
+    pub const ALL: [Self; 4] = [Self::I16, Self::I24, Self::I32, Self::F32];
+
+    /// 24-bit output is carried in a 32-bit container, as rodio has no
+    /// packed 24-bit stream.
+    fn cpal_format(self) -> SampleFormat {
+        match self {
+            Self::I16 => SampleFormat::I16,
+            Self::I24 | Self::I32 => SampleFormat::I32,
+            Self::F32 => SampleFormat::F32,
+        }
+    }
+
+    fn dither_bits(self) -> Option<u32> {
+        match self {
+            Self::I16 => Some(16),
+            Self::I24 => Some(24),
+            Self::I32 | Self::F32 => None,
+        }
+    }
+}
+
+impl std::fmt::Display for OutputSampleFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::I16 => "i16",
+            Self::I24 => "i24",
+            Self::I32 => "i32",
+            Self::F32 => "f32",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for OutputSampleFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|format| format.to_string() == value)
+            .ok_or_else(|| format!("Unknown sample format '{value}'. Use i16, i24, i32 or f32"))
+    }
+}
+
+impl FromStr for OutputBackend {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value {
+            "cpal" => Ok(Self::Cpal),
+            _ => Self::from_device_name(value)
+                .ok_or_else(|| {
+                    format!("Unknown output '{value}'. Use cpal, null, wav:<path> or jack:<client>")
+                }),
+        }
+    }
+}
+
+pub enum OutputStream {
+    Cpal(rodio::OutputStream),
+    Headless(HeadlessStream),
+    Jack(JackStream),
+}
+
+impl OutputStream {
+    pub fn mixer(&self) -> &Mixer {
+        match self {
+            Self::Cpal(stream) => stream.mixer(),
+            Self::Headless(stream) => &stream.mixer,
+            Self::Jack(stream) => stream.mixer(),
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        match self {
+            Self::Cpal(stream) => stream.config().sample_rate(),
+            Self::Headless(stream) => stream.sample_rate,
+            Self::Jack(stream) => stream.sample_rate(),
+        }
+    }
+
+    pub fn channels(&self) -> u16 {
+        match self {
+            Self::Cpal(stream) => stream.config().channel_count(),
+            Self::Headless(_) | Self::Jack(_) => HEADLESS_CHANNELS,
+        }
+    }
+
+    /// Headless and JACK streams run at a rate of their own and resample
+    /// whatever is played, so a new track rate never requires reopening them.
+    pub fn has_fixed_rate(&self) -> bool {
+        matches!(self, Self::Headless(_) | Self::Jack(_))
+    }
+
+    /// Bit depth samples are reduced to on the way out, if any.
+    pub fn dither_bits(&self, sample_format: Option<OutputSampleFormat>) -> Option<u32> {
+        let Self::Cpal(stream) = self else {
+            return None;
+        };
+
+        match (sample_format, stream.config().sample_format()) {
+            (Some(format), SampleFormat::I32) => format.dither_bits(),
+            (_, SampleFormat::I16) => Some(16),
+            (_, SampleFormat::I8) => Some(8),
+            _ => None,
+        }
+    }
+}
+
+pub struct HeadlessStream {
+    mixer: Mixer,
+    sample_rate: u32,
+    stop: Arc<AtomicBool>,
+    /// Frames taken from the mixer and written out so far.
+    frames: Arc<AtomicU64>,
+    handle: Option<thread::JoinHandle<()>>,
+}
+
+impl HeadlessStream {
+    fn start(sample_rate: u32, writer: Option<WavWriter>) -> Self {
+        let sample_rate = writer
+            .as_ref()
+            .map_or(sample_rate, |writer| writer.sample_rate);
+        let (mixer, source) = mixer(HEADLESS_CHANNELS, sample_rate);
+        let stop = Arc::new(AtomicBool::new(false));
+        let frames = Arc::new(AtomicU64::new(0));
+
+        let thread_stop = stop.clone();
+        let thread_frames = frames.clone();
+        let handle = thread::spawn(move || {
+            consume_in_real_time(source, sample_rate, writer, thread_stop, thread_frames);
+        });
+
+        Self {
+            mixer,
+            sample_rate,
+            stop,
+            frames,
+            handle: Some(handle),
+        }
+    }
+
+    pub fn frames_consumed(&self) -> u64 {
+        self.frames.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for HeadlessStream {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            _ = handle.join();
+        }
+    }
+}
+
+fn consume_in_real_time(
+    mut source: MixerSource,
+    sample_rate: u32,
+    mut writer: Option<WavWriter>,
+    stop: Arc<AtomicBool>,
+    frames: Arc<AtomicU64>,
+) {
+    let chunk_frames = (sample_rate as f64 * HEADLESS_CHUNK.as_secs_f64()).ceil() as u64;
+    let chunk_samples = chunk_frames as usize * HEADLESS_CHANNELS as usize;
+    let mut buffer = Vec::with_capacity(chunk_samples);
+    let started = Instant::now();
+    let mut frames_consumed = 0u64;
+
+    while !stop.load(Ordering::Relaxed) {
+        buffer.clear();
+        buffer.extend((0..chunk_samples).map(|_| source.next().unwrap_or(0.0)));
+        frames_consumed += chunk_frames;
+
+        if let Some(wav) = writer.as_mut()
+            && let Err(e) = wav.write_samples(&buffer)
+        {
+            tracing::error!("Failed to write wav output: {}", e);
+            writer = None;
+        }
+        frames.store(frames_consumed, Ordering::Relaxed);
+
+        let due = started + Duration::from_secs_f64(frames_consumed as f64 / sample_rate as f64);
+        if let Some(wait) = due.checked_duration_since(Instant::now()) {
+            thread::sleep(wait);
+        }
+    }
+
+    if let Some(wav) = writer
+        && let Err(e) = wav.finalize()
+    {
+        tracing::error!("Failed to finalize wav output: {}", e);
+    }
+}
+
+struct WavWriter {
+    file: BufWriter<File>,
+    sample_rate: u32,
+    data_len: u32,
+}
+
+impl WavWriter {
+    fn open(path: &Path, sample_rate: u32, channels: u16, append: bool) -> io::Result<Self> {
+        if append && let Some(writer) = Self::reopen(path, channels)? {
+            return Ok(writer);
+        }
+
+        Self::create(path, sample_rate, channels)
+    }
+
+    /// Continues a recording made by [`WavWriter::create`], keeping its sample rate.
+    fn reopen(path: &Path, channels: u16) -> io::Result<Option<Self>> {
+        let mut file = match OpenOptions::new().read(true).write(true).open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        let mut header = [0; WAV_HEADER_LEN];
+        if file.read_exact(&mut header).is_err()
+            || &header[0..4] != b"RIFF"
+            || &header[8..16] != b"WAVEfmt "
+            || header[20..22] != 3u16.to_le_bytes()
+            || header[22..24] != channels.to_le_bytes()
+        {
+            return Ok(None);
+        }
+
+        let sample_rate = u32::from_le_bytes(header[24..28].try_into().expect("four bytes"));
+        let block_align = channels as u64 * 4;
+        let written = file.metadata()?.len().saturating_sub(WAV_HEADER_LEN as u64);
+        let data_len = (written - written % block_align).min(u32::MAX as u64 / block_align * block_align);
+
+        // Drops a partial frame left by an interrupted write
+        file.set_len(WAV_HEADER_LEN as u64 + data_len)?;
+        file.seek(SeekFrom::End(0))?;
+
+        Ok(Some(Self {
+            file: BufWriter::new(file),
+            sample_rate,
+            data_len: data_len as u32,
+        }))
+    }
+
+    fn create(path: &Path, sample_rate: u32, channels: u16) -> io::Result<Self> {
+        if let Some(parent) = path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let mut file = BufWriter::new(File::create(path)?);
+        let block_align = channels * 4;
+
+        file.write_all(b"RIFF")?;
+        file.write_all(&0u32.to_le_bytes())?;
+        file.write_all(b"WAVEfmt ")?;
+        file.write_all(&16u32.to_le_bytes())?;
+        // IEEE float
+        file.write_all(&3u16.to_le_bytes())?;
+        file.write_all(&channels.to_le_bytes())?;
+        file.write_all(&sample_rate.to_le_bytes())?;
+        file.write_all(&(sample_rate * block_align as u32).to_le_bytes())?;
+        file.write_all(&block_align.to_le_bytes())?;
+        file.write_all(&32u16.to_le_bytes())?;
+        file.write_all(b"data")?;
+        file.write_all(&0u32.to_le_bytes())?;
+
+        Ok(Self {
+            file,
+            sample_rate,
+            data_len: 0,
+        })
+    }
+
+    fn write_samples(&mut self, samples: &[f32]) -> io::Result<()> {
+        for sample in samples {
+            self.file.write_all(&sample.to_le_bytes())?;
+        }
+        self.data_len = self
+            .data_len
+            .saturating_add(u32::try_from(samples.len() * 4).unwrap_or(u32::MAX));
+        Ok(())
+    }
+
+    fn finalize(mut self) -> io::Result<()> {
+        self.file.seek(SeekFrom::Start(4))?;
+        self.file
+            .write_all(&self.data_len.saturating_add(36).to_le_bytes())?;
+        self.file.seek(SeekFrom::Start(40))?;
+        self.file.write_all(&self.data_len.to_le_bytes())?;
+        self.file.flush()
+    }
+}
+
+pub fn open_bit_perfect_stream(
+    sample_rate: u32,
+    channels: u16,
+    bit_depth: u32,
+    device_name: Option<&str>,
+) -> Result<OutputStream> {
+    let host = rodio::cpal::default_host();
+    let device = match device_name {
+        Some(device_name) => host.output_devices().ok().and_then(|mut devices| {
+            devices.find(|device| device.name().is_ok_and(|name| name == device_name))
+        }),
+        None => host.default_output_device(),
+    }
+    .ok_or_else(|| Error::StreamError {
+        message: format!(
+            "Audio device {} no longer available",
+            device_name.unwrap_or("default")
+        ),
+    })?;
+
+    let sample_formats: &[SampleFormat] = match bit_depth {
+        0..=16 => &[SampleFormat::I16, SampleFormat::I32],
+        _ => &[SampleFormat::I32],
+    };
+
+    for sample_format in sample_formats {
+        let stream = rodio::OutputStreamBuilder::from_device(device.clone()).and_then(|builder| {
+            builder
+                .with_sample_rate(sample_rate)
+                .with_channels(channels)
+                .with_sample_format(*sample_format)
+                .open_stream()
+        });
+
+        if let Ok(mut stream) = stream {
+            tracing::info!(
+                "Opened bit-perfect stream: {} Hz, {} channels, {:?}",
+                sample_rate,
+                channels,
+                sample_format
+            );
+            stream.log_on_drop(false);
+            return Ok(OutputStream::Cpal(stream));
+        }
+    }
+
+    Err(Error::StreamError {
+        message: format!(
+            "Bit-perfect playback refused: output cannot play {bit_depth}-bit / {} kHz without resampling",
+            sample_rate as f32 / 1000.0
+        ),
+    })
+}
+
+fn open_device(
+    device: rodio::cpal::Device,
+    sample_rate: u32,
+    sample_format: Option<OutputSampleFormat>,
+    fallback: bool,
+) -> std::result::Result<rodio::OutputStream, rodio::StreamError> {
+    if let Some(sample_format) = sample_format {
+        let stream = rodio::OutputStreamBuilder::from_device(device.clone()).and_then(|x| {
+            x.with_sample_rate(sample_rate)
+                .with_sample_format(sample_format.cpal_format())
+                .open_stream()
+        });
+
+        match stream {
+            Ok(stream) => return Ok(stream),
+            Err(e) => tracing::warn!("Output does not support {}: {}", sample_format, e),
+        }
+    }
+
+    let builder = rodio::OutputStreamBuilder::from_device(device)?.with_sample_rate(sample_rate);
+    if fallback {
+        builder.open_stream_or_fallback()
+    } else {
+        builder.open_stream()
+    }
+}
+
+fn open_cpal_stream(
+    sample_rate: u32,
+    device_name: Option<&str>,
+    sample_format: Option<OutputSampleFormat>,
+) -> Result<rodio::OutputStream> {
+    tracing::info!("Opening audio stream with device: {:?}", device_name);
+    
+    let host = rodio::cpal::default_host();
+    
+    if let Some(device_name) = device_name {
+        tracing::info!("Looking for device: {}", device_name);
+        let devices = host.output_devices().map_err(|e| {
+            tracing::error!("Failed to enumerate output devices: {}", e);
+            Error::StreamError {
+                message: format!("Failed to enumerate output devices: {}", e),
+            }
+        })?;
+        
+        for device in devices {
+            let name = device.name().unwrap_or_else(|_| "Unknown".to_string());
+            tracing::debug!("Found device: {}", name);
+            
+            if name == device_name {
+                tracing::info!("Using selected device: {}", name);
+                return open_device(device, sample_rate, sample_format, true)
+                    .map_err(|e| {
+                        tracing::error!("Failed to open selected device {}: {}", device_name, e);
+                        Error::StreamError {
+                            message: format!("Failed to open device {}: {}", device_name, e),
+                        }
+                    });
+            }
+        }
+        
+        tracing::warn!("Selected device '{}' not found, falling back to default", device_name);
+    }
+    
+    host.default_output_device()
+        .ok_or(rodio::StreamError::NoDevice)
+        .and_then(|device| open_device(device, sample_rate, sample_format, false))
+        .or_else(|original_err| {
+            tracing::warn!("Failed to open default device, trying any available device");
+            let mut devices = match host.output_devices() {
+                Ok(devices) => devices,
+                Err(e) => {
+                    tracing::error!("Failed to enumerate output devices: {}", e);
+                    return Err(original_err);
+                }
+            };
+
+            devices
+                .find_map(|d| {
+                    let name = d.name().unwrap_or_else(|_| "Unknown".to_string());
+                    tracing::debug!("Trying device: {}", name);
+                    rodio::OutputStreamBuilder::from_device(d)
+                        .and_then(|x| x.with_sample_rate(sample_rate).open_stream_or_fallback())
+                        .ok()
+                })
+                .ok_or(original_err)
+        })
+        .map_err(|e: rodio::StreamError| {
+            tracing::error!("Failed to open any audio device: {}", e);
+            Error::StreamError {
+                message: format!("Failed to open audio device: {}", e),
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wav_backend_records_mixer_output() {
+        let path = std::env::temp_dir().join(format!("qobuz-player-{}.wav", std::process::id()));
+        let backend = OutputBackend::from_device_name(&format!("wav:{}", path.to_string_lossy()))
+            .expect("wav device name");
+
+        let stream = backend.open(8_000, None, None, false).unwrap();
+        let OutputStream::Headless(headless) = &stream else {
+            panic!("wav output is headless");
+        };
+        // Frames already written are silence from before the buffer was added
+        let written_before = headless.frames_consumed();
+        stream.mixer().add(rodio::buffer::SamplesBuffer::new(
+            2,
+            8_000,
+            vec![0.5f32; 2 * 800],
+        ));
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while headless.frames_consumed() < written_before + 2 * 800 {
+            assert!(Instant::now() < deadline, "wav output stalled");
+            thread::yield_now();
+        }
+        drop(stream);
+
+        let data = std::fs::read(&path).unwrap();
+        _ = std::fs::remove_file(&path);
+
+        assert_eq!(&data[0..4], b"RIFF");
+        assert_eq!(u32::from_le_bytes(data[24..28].try_into().unwrap()), 8_000);
+        let data_len = u32::from_le_bytes(data[40..44].try_into().unwrap()) as usize;
+        assert_eq!(data_len, data.len() - 44);
+
+        let samples = data[44..]
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(samples.iter().filter(|sample| **sample == 0.5).count(), 2 * 800);
+    }
+}
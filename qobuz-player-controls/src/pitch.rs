@@ -0,0 +1,275 @@
+use std::{fs, path::Path, time::Duration};
+
+use qobuz_player_models::Track;
+use rodio::{Source, decoder::DecoderBuilder};
+use rustfft::{FftPlanner, num_complex::Complex};
+
+use crate::{
+    Result, downloader::find_cached_track, error::Error, sink::PlaybackStretchConfig,
+    stretch_source_signalsmith::normalize_ratio,
+};
+
+pub const MAX_PITCHES: usize = 6;
+
+const NOTE_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+const WINDOW_SECONDS: f64 = 0.4;
+const LOWEST_HZ: f32 = 50.0;
+const HIGHEST_HZ: f32 = 5_000.0;
+const PEAK_FLOOR_DB: f32 = -40.0;
+const HARMONIC_TOLERANCE_CENTS: f32 = 30.0;
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ProbedPitch {
+    /// Note name with octave, e.g. `A4`.
+    pub note: String,
+    /// Offset from the named note in cents, -50 to 50.
+    pub cents: i16,
+    /// Frequency as heard, after the pitch shift.
+    pub frequency: f32,
+    /// Magnitude relative to the strongest pitch.
+    pub strength: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct PitchProbe {
+    /// Centre of the analysed window in track time.
+    pub position: Duration,
+    /// Strongest pitches first.
+    pub pitches: Vec<ProbedPitch>,
+}
+
+/// Analyses a short window of the cached file around the playback position.
+/// Without a stretch config, playback runs at the original tempo and pitch.
+pub async fn probe_track(
+    track: &Track,
+    audio_cache_dir: &Path,
+    position: Duration,
+    stretch: Option<PlaybackStretchConfig>,
+) -> Result<PitchProbe> {
+    let cached = find_cached_track(track, audio_cache_dir).ok_or_else(|| Error::Analysis {
+        message: format!("'{}' is not cached yet", track.title),
+    })?;
+
+    let (ratio, shift_cents) = stretch
+        .map(|stretch| {
+            (
+                normalize_ratio(stretch.time_stretch_ratio) as f64,
+                stretch.pitch_semitones as f32 * 100.0 + stretch.pitch_cents as f32,
+            )
+        })
+        .unwrap_or((1.0, 0.0));
+    let track_position = Duration::from_secs_f64(position.as_secs_f64() * ratio);
+
+    tokio::task::spawn_blocking(move || probe_file(&cached, track_position, shift_cents))
+        .await
+        .expect("infallible")
+}
+
+pub fn probe_file(track_path: &Path, position: Duration, shift_cents: f32) -> Result<PitchProbe> {
+    let file = fs::File::open(track_path).map_err(|e| analysis_error(track_path, e))?;
+    let mut decoded = DecoderBuilder::new()
+        .with_data(file)
+        .with_seekable(true)
+        .build()?;
+
+    let channels = decoded.channels().max(1) as usize;
+    let sample_rate = decoded.sample_rate();
+    let fft_size = ((sample_rate as f64 * WINDOW_SECONDS) as usize).next_power_of_two();
+    let half_window = Duration::from_secs_f64(fft_size as f64 / 2.0 / sample_rate as f64);
+    decoded.try_seek(position.saturating_sub(half_window))?;
+
+    let samples = decoded.take(fft_size * channels).collect::<Vec<_>>();
+    let mono = samples
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect::<Vec<_>>();
+
+    Ok(PitchProbe {
+        position,
+        pitches: prominent_pitches(&mono, sample_rate, fft_size, shift_cents),
+    })
+}
+
+/// Finds spectral peaks in a mono window and names them. Peaks sitting on a
+/// harmonic of a stronger peak are dropped, so a doubled octave only shows
+/// its lower note.
+pub fn prominent_pitches(
+    mono: &[f32],
+    sample_rate: u32,
+    fft_size: usize,
+    shift_cents: f32,
+) -> Vec<ProbedPitch> {
+    let spectrum = magnitude_spectrum(mono, fft_size);
+    let bin_hz = sample_rate as f32 / fft_size as f32;
+    let first = ((LOWEST_HZ / bin_hz) as usize).max(1);
+    let last = ((HIGHEST_HZ / bin_hz) as usize).min(spectrum.len() - 2);
+
+    let loudest = spectrum[first..=last].iter().copied().fold(0.0, f32::max);
+    if loudest <= f32::EPSILON {
+        return vec![];
+    }
+    let floor = loudest * 10f32.powf(PEAK_FLOOR_DB / 20.0);
+
+    let mut peaks = (first..=last)
+        .filter(|&bin| {
+            spectrum[bin] > floor
+                && spectrum[bin] > spectrum[bin - 1]
+                && spectrum[bin] >= spectrum[bin + 1]
+        })
+        .map(|bin| {
+            let (offset, magnitude) = interpolate_peak(&spectrum[bin - 1..=bin + 1]);
+            ((bin as f32 + offset) * bin_hz, magnitude)
+        })
+        .collect::<Vec<_>>();
+    peaks.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    let mut kept: Vec<(f32, f32)> = vec![];
+    for (frequency, magnitude) in peaks {
+        let is_harmonic = kept.iter().any(|(fundamental, _)| {
+            (2..=6).any(|n| {
+                cents_between(frequency, fundamental * n as f32).abs() < HARMONIC_TOLERANCE_CENTS
+            })
+        });
+        if !is_harmonic {
+            kept.push((frequency, magnitude));
+        }
+    }
+
+    let strongest = kept.first().map(|(_, magnitude)| *magnitude).unwrap_or(1.0);
+    let shift = 2f32.powf(shift_cents / 1200.0);
+    let mut pitches: Vec<ProbedPitch> = vec![];
+    for (frequency, magnitude) in kept {
+        let frequency = frequency * shift;
+        let (note, cents) = note_for_frequency(frequency);
+        if pitches.iter().any(|pitch| pitch.note == note) {
+            continue;
+        }
+
+        pitches.push(ProbedPitch {
+            note,
+            cents,
+            frequency,
+            strength: magnitude / strongest,
+        });
+        if pitches.len() == MAX_PITCHES {
+            break;
+        }
+    }
+
+    pitches
+}
+
+/// Nearest equal tempered note for a frequency, with A4 at 440 Hz.
+pub fn note_for_frequency(frequency: f32) -> (String, i16) {
+    let midi = 69.0 + 12.0 * (frequency / 440.0).log2();
+    let nearest = midi.round();
+    let cents = ((midi - nearest) * 100.0).round() as i16;
+    let nearest = nearest as i32;
+
+    (note_name(nearest), cents)
+}
+
+pub fn note_name(midi: i32) -> String {
+    format!(
+        "{}{}",
+        NOTE_NAMES[midi.rem_euclid(12) as usize],
+        midi.div_euclid(12) - 1
+    )
+}
+
+fn magnitude_spectrum(mono: &[f32], fft_size: usize) -> Vec<f32> {
+    let mut buffer = (0..fft_size)
+        .map(|i| {
+            let phase = 2.0 * std::f32::consts::PI * i as f32 / (fft_size - 1) as f32;
+            let hann = 0.5 - 0.5 * phase.cos();
+            Complex::new(mono.get(i).copied().unwrap_or(0.0) * hann, 0.0)
+        })
+        .collect::<Vec<_>>();
+    FftPlanner::new()
+        .plan_fft_forward(fft_size)
+        .process(&mut buffer);
+
+    buffer[..fft_size / 2]
+        .iter()
+        .map(|bin| bin.norm())
+        .collect()
+}
+
+/// Parabolic interpolation over three bins around a peak. Returns the bin
+/// offset of the true peak and its magnitude.
+fn interpolate_peak(bins: &[f32]) -> (f32, f32) {
+    let [left, centre, right] = [bins[0], bins[1], bins[2]].map(|x| x.max(f32::EPSILON).ln());
+    let denominator = left - 2.0 * centre + right;
+    if denominator.abs() < f32::EPSILON {
+        return (0.0, bins[1]);
+    }
+
+    let offset = (0.5 * (left - right) / denominator).clamp(-0.5, 0.5);
+    let magnitude = (centre - 0.25 * (left - right) * offset).exp();
+    (offset, magnitude)
+}
+
+fn cents_between(a: f32, b: f32) -> f32 {
+    1200.0 * (a / b).log2()
+}
+
+fn analysis_error(path: &Path, error: std::io::Error) -> Error {
+    Error::Analysis {
+        message: format!("{}: {error}", path.to_string_lossy()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tone(frequencies: &[(f32, f32)], sample_rate: u32, length: usize) -> Vec<f32> {
+        (0..length)
+            .map(|i| {
+                frequencies
+                    .iter()
+                    .map(|(frequency, amplitude)| {
+                        amplitude
+                            * (2.0 * std::f32::consts::PI * frequency * i as f32
+                                / sample_rate as f32)
+                                .sin()
+                    })
+                    .sum()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn names_notes_and_cents() {
+        assert_eq!(note_for_frequency(440.0), ("A4".to_string(), 0));
+        assert_eq!(note_for_frequency(261.63), ("C4".to_string(), 0));
+        assert_eq!(note_for_frequency(446.0), ("A4".to_string(), 23));
+        assert_eq!(note_name(21), "A0");
+    }
+
+    #[test]
+    fn finds_chord_tones_with_shift() {
+        let sample_rate = 44_100;
+        let fft_size = 16_384;
+        let mono = tone(
+            &[(440.0, 0.5), (880.0, 0.2), (554.37, 0.4), (659.26, 0.3)],
+            sample_rate,
+            fft_size,
+        );
+
+        let notes = prominent_pitches(&mono, sample_rate, fft_size, 0.0)
+            .into_iter()
+            .map(|pitch| (pitch.note, pitch.cents))
+            .collect::<Vec<_>>();
+        assert_eq!(notes.len(), 3);
+        for (note, cents) in &notes {
+            assert!(["A4", "C#5", "E5"].contains(&note.as_str()), "{note}");
+            assert!(cents.abs() <= 3, "{note} {cents}");
+        }
+
+        let shifted = prominent_pitches(&mono, sample_rate, fft_size, -200.0);
+        assert_eq!(shifted[0].note, "G4");
+    }
+}
@@ -1,4 +1,5 @@
-use qobuz_player_models::{Album, Track, TrackStatus};
+use qobuz_player_client::qobuz_models::TrackURL;
+use qobuz_player_models::{Album, StreamFormat, Track, TrackStatus};
 use rand::seq::SliceRandom;
 use tokio::{
     select,
@@ -9,17 +10,26 @@ use tokio::{
 };
 
 use crate::{
-    ExitReceiver, PositionReceiver, Result, Status, StatusReceiver, TracklistReceiver,
-    VolumeReceiver,
-    controls::{ControlCommand, Controls},
-    database::Database,
+    AudioQuality, ExitReceiver, LevelsReceiver, PositionReceiver, RepeatModeReceiver, Result,
+    SleepTimerReceiver, Status, StatusReceiver, TracklistReceiver, VolumeReceiver,
+    clap::{ClapChainEntry, DEFAULT_CHAIN},
+    controls::{ControlCommand, Controls, MAX_SLEEP_TIMER, SleepTimer},
+    database::{Database, PlaybackPosition},
+    error::Error,
     downloader::Downloader,
     notification::{Notification, NotificationBroadcast},
-    sink::{PlaybackStretchConfig, QueryTrackResult, list_audio_devices},
-    tracklist::{SingleTracklist, TracklistType},
+    output::OutputBackend,
+    pitch::probe_track,
+    sink::{PlaybackStretchConfig, QueryTrackResult, get_default_device_name, list_audio_devices},
+    tracklist::{RepeatMode, SingleTracklist, TracklistType},
 };
 use parking_lot::RwLock;
-use std::{path::PathBuf, sync::Arc, time::Duration};
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use crate::{
     client::Client,
@@ -28,6 +38,15 @@ use crate::{
 };
 
 const INTERVAL_MS: u64 = 500;
+const DEVICE_WATCH_INTERVAL_SECS: u64 = 5;
+/// The sleep timer fades the volume out over this long before pausing.
+const SLEEP_FADE: Duration = Duration::from_secs(60);
+const POSITION_CHECKPOINT_INTERVAL: Duration = Duration::from_secs(10);
+
+enum ActiveSleepTimer {
+    Until(Instant),
+    EndOfTracklist,
+}
 
 pub struct Player {
     broadcast: Arc<NotificationBroadcast>,
@@ -37,7 +56,16 @@ pub struct Player {
     client: Arc<Client>,
     sink: Sink,
     volume: Sender<f32>,
+    repeat_mode: Sender<RepeatMode>,
+    sleep_timer: Option<ActiveSleepTimer>,
+    sleep_timer_remaining: Sender<Option<Duration>>,
+    /// Time played and total length of a running volume ramp.
+    ramp_up: Option<(Duration, Duration)>,
     position: Sender<Duration>,
+    /// Where to start the current track when it is first loaded after a restart.
+    resume_position: Option<PlaybackPosition>,
+    checkpointed_position: Option<PlaybackPosition>,
+    last_checkpoint: Instant,
     track_finished: Receiver<()>,
     done_buffering: Receiver<PathBuf>,
     controls_rx: mpsc::UnboundedReceiver<ControlCommand>,
@@ -47,6 +75,14 @@ pub struct Player {
     next_track_in_sink_queue: bool,
     downloader: Downloader,
     playback_stretch: Arc<RwLock<PlaybackStretchConfig>>,
+    stream_formats: HashMap<u32, StreamFormat>,
+    known_audio_devices: Option<Vec<String>>,
+    default_audio_device: Option<String>,
+    /// Track and target status a device error paused, resumed once the
+    /// preferred device is back.
+    device_interruption: Option<(u32, Status)>,
+    /// Held while the player runs, see `Database::lock_player`.
+    _player_lock: Option<std::fs::File>,
 }
 
 impl Player {
@@ -57,17 +93,21 @@ impl Player {
         broadcast: Arc<NotificationBroadcast>,
         audio_cache_dir: PathBuf,
         database: Arc<Database>,
+        output_backend: OutputBackend,
     ) -> Result<Self> {
         let (volume, volume_receiver) = watch::channel(volume);
         let playback_stretch = Arc::new(RwLock::new(PlaybackStretchConfig::default()));
-        let sink = Sink::new(volume_receiver, playback_stretch.clone())?;
+        let sink = Sink::new(volume_receiver, playback_stretch.clone(), output_backend)?;
 
         let downloader = Downloader::new(audio_cache_dir, broadcast.clone(), database.clone());
+        let player_lock = database.lock_player();
 
         let track_finished = sink.track_finished();
         let done_buffering = downloader.done_buffering();
 
         let (position, _) = watch::channel(Default::default());
+        let (repeat_mode, _) = watch::channel(Default::default());
+        let (sleep_timer_remaining, _) = watch::channel(None);
         let (target_status, _) = watch::channel(Default::default());
         let (tracklist_tx, tracklist_rx) = watch::channel(tracklist);
 
@@ -84,7 +124,14 @@ impl Player {
             client,
             sink,
             volume,
+            repeat_mode,
+            sleep_timer: None,
+            sleep_timer_remaining,
+            ramp_up: None,
             position,
+            resume_position: None,
+            checkpointed_position: None,
+            last_checkpoint: Instant::now(),
             track_finished,
             done_buffering,
             database,
@@ -92,6 +139,11 @@ impl Player {
             next_track_is_queried: false,
             downloader,
             playback_stretch,
+            stream_formats: Default::default(),
+            known_audio_devices: None,
+            default_audio_device: None,
+            device_interruption: None,
+            _player_lock: player_lock,
         })
     }
 
@@ -107,10 +159,22 @@ impl Player {
         self.volume.subscribe()
     }
 
+    pub fn repeat_mode(&self) -> RepeatModeReceiver {
+        self.repeat_mode.subscribe()
+    }
+
+    pub fn sleep_timer(&self) -> SleepTimerReceiver {
+        self.sleep_timer_remaining.subscribe()
+    }
+
     pub fn position(&self) -> PositionReceiver {
         self.position.subscribe()
     }
 
+    pub fn levels(&self) -> LevelsReceiver {
+        self.sink.levels()
+    }
+
     pub fn tracklist(&self) -> TracklistReceiver {
         self.tracklist_tx.subscribe()
     }
@@ -128,7 +192,7 @@ impl Player {
             if !devices.iter().any(|d| &d.name == name) {
                 tracing::warn!("Player: Device '{}' not found, falling back to default", name);
                 self.broadcast.send(Notification::Warning(
-                    format!("Audio device '{}' not found. Using default device.", name)
+                    format!("Audio device '{}' not found. Using default device until it is connected.", name)
                 ));
                 self.sink.set_device(None);
                 return Ok(());
@@ -147,7 +211,7 @@ impl Player {
                 let current_position = self.sink.position();
                 let position_ms = current_position.as_millis() as u64;
                 
-                let track_url = self.client.track_url(current_track.id).await?;
+                let track_url = self.stream_url(current_track).await?;
                 if let Some(track_path) = self
                     .downloader
                     .ensure_track_is_downloaded(track_url, current_track)
@@ -286,14 +350,45 @@ impl Player {
         if next_track {
             self.next_track_is_queried = true;
         }
+        self.stream_formats.remove(&track.id);
+
+        let mut track_url = self.client.track_url(track.id).await?;
+
+        if next_track
+            && !self.downloader.is_cached(&track_url, track)
+            && let Some(time_left) = self.remaining_playback_time()
+            && let Some(quality) = self.downloader.fallback_quality(
+                &self.client.max_audio_quality(),
+                track,
+                time_left,
+            )
+        {
+            tracing::info!("Throughput too low, requesting {}: {quality:?}", &track.title);
+            track_url = self
+                .client
+                .track_url_with_quality(track.id, &quality)
+                .await?;
+            self.broadcast.send(Notification::Warning(format!(
+                "Slow connection. Playing {} in lower quality",
+                track.title
+            )));
+        }
+
+        self.set_stream_format(track.id, &track_url);
+
+        let resume_position = match next_track {
+            true => None,
+            false => self.resume_position.take(),
+        }
+        .filter(|resume_position| resume_position.track_id == track.id);
 
-        let track_url = self.client.track_url(track.id).await?;
         if let Some(track_path) = self
             .downloader
             .ensure_track_is_downloaded(track_url, track)
             .await
         {
-            match self.sink.query_track(&track_path, None) {
+            let start_at = resume_position.map(|resume_position| resume_position.position);
+            match self.sink.query_track(&track_path, start_at) {
                 Ok(query_result) => {
                     if next_track {
                         self.next_track_in_sink_queue = match query_result {
@@ -317,17 +412,15 @@ impl Player {
                     tracing::error!("Failed to query track: {}", error_msg);
                     
                     if error_msg.contains("device") || error_msg.contains("no longer available") {
-                        tracing::warn!("Audio device error detected during play, pausing and resetting to default");
+                        tracing::warn!("Audio device error detected during play, pausing and using default device");
+                        let start_at = start_at.unwrap_or_default();
+                        self.interrupt_for_device_error((!next_track).then_some(start_at))?;
                         self.set_target_status(Status::Paused);
                         
                         self.sink.set_device(None);
                         
-                        if let Err(db_err) = self.database.set_audio_device(None).await {
-                            tracing::error!("Failed to update database after device error: {}", db_err);
-                        }
-                        
                         self.broadcast.send(Notification::Warning(
-                            "Audio device error. Resetting to default device.".to_string()
+                            "Audio device error. Using default device until it is connected again.".to_string()
                         ));
                         return Ok(());
                     }
@@ -336,12 +429,81 @@ impl Player {
             }
         } else {
             tracing::info!("Buffering track: {}", &track.title);
+            self.resume_position = resume_position;
             self.set_target_status(Status::Buffering);
         }
 
         Ok(())
     }
 
+    async fn stream_url(&self, track: &Track) -> Result<TrackURL> {
+        match track
+            .stream_format
+            .as_ref()
+            .and_then(|format| AudioQuality::try_from(format.format_id as i64).ok())
+        {
+            Some(quality) => self.client.track_url_with_quality(track.id, &quality).await,
+            None => self.client.track_url(track.id).await,
+        }
+    }
+
+    fn set_stream_format(&mut self, track_id: u32, track_url: &TrackURL) {
+        let stream_format = StreamFormat {
+            format_id: track_url.format_id as u32,
+            mime_type: track_url.mime_type.clone(),
+            sampling_rate: track_url.sampling_rate,
+            bit_depth: track_url.bit_depth as u32,
+            bit_perfect: false,
+        };
+        self.stream_formats.insert(track_id, stream_format.clone());
+
+        self.tracklist_tx.send_if_modified(|tracklist| {
+            let mut modified = false;
+            for track in tracklist.queue.iter_mut().filter(|t| t.id == track_id) {
+                modified |= track.stream_format.as_ref() != Some(&stream_format);
+                track.stream_format = Some(stream_format.clone());
+            }
+            modified
+        });
+    }
+
+    /// Forgets the format a track was streamed in, so a quality fallback only lasts one play.
+    fn clear_stream_format(&mut self, tracklist: &mut Tracklist, track_id: u32) {
+        self.stream_formats.remove(&track_id);
+        for track in tracklist.queue.iter_mut().filter(|t| t.id == track_id) {
+            track.stream_format = None;
+        }
+    }
+
+    fn update_bit_perfect(&mut self) {
+        let bit_perfect = self.sink.is_bit_perfect();
+        let Some(track_id) = self.tracklist_rx.borrow().current_track().map(|track| track.id) else {
+            return;
+        };
+        let Some(stream_format) = self.stream_formats.get_mut(&track_id) else {
+            return;
+        };
+        if stream_format.bit_perfect == bit_perfect {
+            return;
+        }
+
+        stream_format.bit_perfect = bit_perfect;
+        let stream_format = stream_format.clone();
+        self.tracklist_tx.send_if_modified(|tracklist| {
+            let mut modified = false;
+            for track in tracklist.queue.iter_mut().filter(|t| t.id == track_id) {
+                modified |= track.stream_format.as_ref() != Some(&stream_format);
+                track.stream_format = Some(stream_format.clone());
+            }
+            modified
+        });
+    }
+
+    fn remaining_playback_time(&self) -> Option<Duration> {
+        self.current_display_duration()
+            .map(|duration| duration.saturating_sub(self.sink.position()))
+    }
+
     async fn set_volume(&self, volume: f32) -> Result<()> {
         self.volume.send(volume)?;
         self.sink.sync_volume();
@@ -349,13 +511,27 @@ impl Player {
         Ok(())
     }
 
-    async fn broadcast_tracklist(&self, tracklist: Tracklist) -> Result<()> {
+    async fn broadcast_tracklist(&self, mut tracklist: Tracklist) -> Result<()> {
+        for track in tracklist.queue.iter_mut() {
+            if let Some(stream_format) = self.stream_formats.get(&track.id) {
+                track.stream_format = Some(stream_format.clone());
+            }
+        }
+
         self.database.set_tracklist(&tracklist).await?;
         self.tracklist_tx.send(tracklist)?;
         Ok(())
     }
 
     fn seek(&mut self, duration: Duration) -> Result<()> {
+        if self.sink.is_empty()
+            && let Some(resume_position) = self.resume_position.as_mut()
+        {
+            resume_position.position = duration;
+            self.position.send_replace(duration);
+            return Ok(());
+        }
+
         self.sink.seek(duration)?;
         self.position.send(self.sink.position())?;
         Ok(())
@@ -433,9 +609,15 @@ impl Player {
     }
 
     async fn next(&mut self) -> Result<()> {
-        let current_position = self.tracklist_rx.borrow().current_position();
-        self.skip_to_position((current_position + 1) as i32, true)
-            .await
+        let next_position = {
+            let tracklist = self.tracklist_rx.borrow();
+            match *self.repeat_mode.borrow() {
+                RepeatMode::Queue => tracklist.next_position(RepeatMode::Queue),
+                RepeatMode::Off | RepeatMode::Track => None,
+            }
+            .unwrap_or(tracklist.current_position() + 1)
+        };
+        self.skip_to_position(next_position as i32, true).await
     }
 
     async fn previous(&mut self) -> Result<()> {
@@ -445,6 +627,7 @@ impl Player {
     }
 
     async fn new_queue(&mut self, tracklist: Tracklist) -> Result<()> {
+        self.resume_position = None;
         self.sink.clear()?;
         self.next_track_is_queried = false;
         self.next_track_in_sink_queue = false;
@@ -477,6 +660,8 @@ impl Player {
                 image: track.image.clone(),
             }),
             queue: vec![track],
+            unshuffled: None,
+            stop_after: None,
         };
 
         self.new_queue(tracklist).await
@@ -499,6 +684,8 @@ impl Player {
                 id: album.id,
                 image: Some(album.image),
             }),
+            unshuffled: None,
+            stop_after: None,
         };
 
         tracklist.skip_to_track(index as i32 - unstreamable_tracks_to_index);
@@ -518,6 +705,8 @@ impl Player {
                 id: artist_id,
                 image: artist.image,
             }),
+            unshuffled: None,
+            stop_after: None,
         };
 
         tracklist.skip_to_track(index as i32 - unstreamable_tracks_to_index);
@@ -540,6 +729,7 @@ impl Player {
             .filter(|t| t.available)
             .collect();
 
+        let unshuffled = shuffle.then(|| tracks.clone());
         if shuffle {
             tracks.shuffle(&mut rand::rng());
         }
@@ -551,6 +741,53 @@ impl Player {
                 id: playlist.id,
                 image: playlist.image,
             }),
+            unshuffled,
+            stop_after: None,
+        };
+
+        tracklist.skip_to_track(index as i32 - unstreamable_tracks_to_index);
+        self.new_queue(tracklist).await
+    }
+
+    async fn play_local_playlist(&mut self, id: i64, index: usize, shuffle: bool) -> Result<()> {
+        let Some(playlist) = self.database.get_local_playlist(id).await? else {
+            self.broadcast
+                .send(Notification::Warning("Local playlist not found".into()));
+            return Ok(());
+        };
+
+        let image = playlist.image();
+        let tracks: Vec<Track> = playlist
+            .entries
+            .into_iter()
+            .map(|entry| entry.track)
+            .collect();
+        let unstreamable_tracks_to_index =
+            tracks.iter().take(index).filter(|t| !t.available).count() as i32;
+
+        let mut tracks: Vec<Track> = tracks.into_iter().filter(|t| t.available).collect();
+        if tracks.is_empty() {
+            self.broadcast.send(Notification::Warning(format!(
+                "{} has no playable tracks",
+                playlist.title
+            )));
+            return Ok(());
+        }
+
+        let unshuffled = shuffle.then(|| tracks.clone());
+        if shuffle {
+            tracks.shuffle(&mut rand::rng());
+        }
+
+        let mut tracklist = Tracklist {
+            queue: tracks,
+            list_type: TracklistType::LocalPlaylist(tracklist::LocalPlaylistTracklist {
+                title: playlist.title,
+                id,
+                image,
+            }),
+            unshuffled,
+            stop_after: None,
         };
 
         tracklist.skip_to_track(index as i32 - unstreamable_tracks_to_index);
@@ -560,7 +797,7 @@ impl Player {
     async fn remove_index_from_queue(&mut self, index: usize) -> Result<()> {
         let mut tracklist = self.tracklist_rx.borrow().clone();
 
-        tracklist.queue.remove(index);
+        tracklist.remove(index);
         self.update_queue(tracklist).await?;
         let notification = Notification::Info("Queue updated".into());
         self.broadcast.send(notification);
@@ -586,7 +823,7 @@ impl Player {
         let notification = Notification::Info(format!("{} playing next", track.title.clone()));
 
         let current_index = tracklist.current_position();
-        tracklist.queue.insert(current_index + 1, track);
+        tracklist.insert(current_index + 1, track);
         self.update_queue(tracklist).await?;
         self.broadcast.send(notification);
         Ok(())
@@ -598,27 +835,242 @@ impl Player {
         }
 
         let mut tracklist = self.tracklist_rx.borrow().clone();
+        tracklist.reorder(&new_order);
 
-        let reordered: Vec<_> = new_order
-            .iter()
-            .map(|&i| tracklist.queue[i].clone())
-            .collect();
+        self.update_queue(tracklist).await?;
+        let notification = Notification::Info("Queue updated".into());
+        self.broadcast.send(notification);
+        Ok(())
+    }
 
-        tracklist.queue = reordered;
+    async fn set_repeat_mode(&mut self, mode: RepeatMode) -> Result<()> {
+        self.database.set_repeat_mode(mode).await?;
+        self.repeat_mode.send_replace(mode);
+
+        // The track queued for gapless playback may no longer be the next one
+        let tracklist = self.tracklist_rx.borrow().clone();
+        self.update_queue(tracklist).await
+    }
+
+    async fn set_shuffle(&mut self, enabled: bool) -> Result<()> {
+        let mut tracklist = self.tracklist_rx.borrow().clone();
+        if tracklist.is_shuffled() == enabled {
+            return Ok(());
+        }
+
+        if enabled {
+            tracklist.shuffle();
+        } else {
+            tracklist.unshuffle();
+        }
 
         self.update_queue(tracklist).await?;
-        let notification = Notification::Info("Queue updated".into());
+        let notification = Notification::Info(if enabled {
+            "Queue shuffled".into()
+        } else {
+            "Queue order restored".into()
+        });
         self.broadcast.send(notification);
         Ok(())
     }
 
+    async fn set_stop_after(&mut self, position: Option<usize>) -> Result<()> {
+        let mut tracklist = self.tracklist_rx.borrow().clone();
+        let position = position.filter(|position| {
+            *position >= tracklist.current_position() && *position < tracklist.total()
+        });
+        if tracklist.stop_after == position {
+            return Ok(());
+        }
+        tracklist.stop_after = position;
+
+        let notification = match position.map(|position| &tracklist.queue[position]) {
+            Some(track) => Notification::Info(format!("Stopping after {}", track.title)),
+            None => Notification::Info("Stop after cancelled".into()),
+        };
+
+        // The next track may already be queued for gapless playback
+        self.update_queue(tracklist).await?;
+        self.broadcast.send(notification);
+        Ok(())
+    }
+
+    async fn save_queue(&mut self, name: String) -> Result<()> {
+        let name = name.trim();
+        let tracklist = self.tracklist_rx.borrow().clone();
+        if name.is_empty() || tracklist.queue.is_empty() {
+            self.broadcast.send(Notification::Warning(
+                "Name the queue and add tracks before saving".into(),
+            ));
+            return Ok(());
+        }
+
+        self.database.save_queue(name, &tracklist).await?;
+        self.broadcast
+            .send(Notification::Info(format!("Queue saved as {name}")));
+        Ok(())
+    }
+
+    async fn load_saved_queue(&mut self, name: String, append: bool) -> Result<()> {
+        let Some(mut saved) = self.database.get_saved_queue(&name).await? else {
+            self.broadcast
+                .send(Notification::Warning(format!("No saved queue named {name}")));
+            return Ok(());
+        };
+
+        if append {
+            let mut tracklist = self.tracklist_rx.borrow().clone();
+            tracklist.append(saved.queue);
+            self.update_queue(tracklist).await?;
+            self.broadcast
+                .send(Notification::Info(format!("{name} added to queue")));
+            return Ok(());
+        }
+
+        saved.stop_after = None;
+        if saved.current_track().is_none() {
+            saved.reset();
+        }
+        self.new_queue(saved).await?;
+        self.broadcast
+            .send(Notification::Info(format!("Playing {name}")));
+        Ok(())
+    }
+
+    fn set_sleep_timer(&mut self, timer: Option<SleepTimer>) {
+        self.sleep_timer = match timer {
+            Some(SleepTimer::After(duration)) => {
+                let deadline = Instant::now().checked_add(duration);
+                match deadline.filter(|_| duration <= MAX_SLEEP_TIMER) {
+                    Some(deadline) => Some(ActiveSleepTimer::Until(deadline)),
+                    None => {
+                        self.broadcast.send(Notification::Warning(format!(
+                            "Sleep timer can be at most {} hours",
+                            MAX_SLEEP_TIMER.as_secs() / 3600
+                        )));
+                        return;
+                    }
+                }
+            }
+            Some(SleepTimer::EndOfTracklist) => Some(ActiveSleepTimer::EndOfTracklist),
+            None => None,
+        };
+
+        if self.sleep_timer.is_some() {
+            self.update_sleep_timer();
+            self.sync_fade();
+            self.broadcast
+                .send(Notification::Info("Sleep timer set".into()));
+        } else {
+            self.finish_sleep_timer();
+            self.broadcast
+                .send(Notification::Info("Sleep timer cancelled".into()));
+        }
+    }
+
+    fn sleep_timer_time_left(&self) -> Option<Duration> {
+        match self.sleep_timer.as_ref()? {
+            ActiveSleepTimer::Until(deadline) => {
+                Some(deadline.saturating_duration_since(Instant::now()))
+            }
+            ActiveSleepTimer::EndOfTracklist => {
+                let ratio = self.playback_stretch.read().time_stretch_ratio.max(0.01) as f64;
+                let current = self
+                    .current_display_duration()
+                    .unwrap_or_default()
+                    .saturating_sub(self.sink.position());
+
+                let tracklist = self.tracklist_rx.borrow();
+                let later_seconds: u32 = tracklist
+                    .queue
+                    .iter()
+                    .skip(tracklist.current_position() + 1)
+                    .map(|track| track.duration_seconds)
+                    .sum();
+
+                Some(current + Duration::from_secs_f64(later_seconds as f64 / ratio))
+            }
+        }
+    }
+
+    /// Publishes the time left. A timer running until the end of the
+    /// tracklist is finished by `track_finished`.
+    fn update_sleep_timer(&mut self) {
+        let Some(remaining) = self.sleep_timer_time_left() else {
+            return;
+        };
+
+        if remaining.is_zero() && matches!(self.sleep_timer, Some(ActiveSleepTimer::Until(_))) {
+            tracing::info!("Sleep timer ended");
+            self.pause();
+            self.finish_sleep_timer();
+            return;
+        }
+
+        let seconds = Some(Duration::from_secs(remaining.as_secs_f64().ceil() as u64));
+        self.sleep_timer_remaining.send_if_modified(|current| {
+            let modified = *current != seconds;
+            *current = seconds;
+            modified
+        });
+    }
+
+    /// Clears the timer and restores the volume for the next playback.
+    fn finish_sleep_timer(&mut self) {
+        self.sleep_timer = None;
+        self.sync_fade();
+        self.sleep_timer_remaining.send_replace(None);
+    }
+
+    fn ramp_up_volume(&mut self, duration: Duration) {
+        self.ramp_up = Some((Duration::ZERO, duration));
+        self.sync_fade();
+    }
+
+    /// Applies the quieter of the sleep timer fade-out and the volume ramp.
+    fn sync_fade(&mut self) {
+        let sleep = self
+            .sleep_timer_time_left()
+            .map(|left| left.as_secs_f32() / SLEEP_FADE.as_secs_f32())
+            .unwrap_or(1.0);
+        let ramp = match self.ramp_up {
+            Some((played, total)) if played < total => played.as_secs_f32() / total.as_secs_f32(),
+            _ => {
+                self.ramp_up = None;
+                1.0
+            }
+        };
+
+        self.sink.set_fade(sleep.min(ramp).min(1.0));
+    }
+
+    /// A sleep timer waiting for the end of the tracklist overrides repeat.
+    fn effective_repeat_mode(&self) -> RepeatMode {
+        match self.sleep_timer {
+            Some(ActiveSleepTimer::EndOfTracklist) => RepeatMode::Off,
+            _ => *self.repeat_mode.borrow(),
+        }
+    }
+
     async fn tick(&mut self) -> Result<()> {
+        self.update_sleep_timer();
+        self.sync_fade();
+
+        if self.last_checkpoint.elapsed() >= POSITION_CHECKPOINT_INTERVAL {
+            self.checkpoint_position().await?;
+        }
+
         if *self.target_status.borrow() != Status::Playing {
             return Ok(());
         }
 
+        if let Some((played, _)) = self.ramp_up.as_mut() {
+            *played += Duration::from_millis(INTERVAL_MS);
+        }
+
         let position = self.sink.position();
         self.position.send(position)?;
+        self.update_bit_perfect();
 
         let duration = self
             .tracklist_rx
@@ -634,12 +1086,15 @@ impl Player {
 
             let track_about_to_finish = (duration as i64 - position as i64) < 60;
 
-            if track_about_to_finish && !self.next_track_is_queried {
+            let stops_after_current = self.tracklist_rx.borrow().stops_after_current();
+
+            if track_about_to_finish && !self.next_track_is_queried && !stops_after_current {
                 tracing::info!("Track about to finish");
 
                 let tracklist = self.tracklist_rx.borrow().clone();
+                let repeat_mode = self.effective_repeat_mode();
 
-                if let Some(next_track) = tracklist.next_track() {
+                if let Some(next_track) = tracklist.next_track(repeat_mode) {
                     tracing::info!("Query next track: {} from tick", &next_track.title);
                     self.query_track(next_track, true).await?;
                 }
@@ -649,6 +1104,49 @@ impl Player {
         Ok(())
     }
 
+    async fn checkpoint_position(&mut self) -> Result<()> {
+        self.last_checkpoint = Instant::now();
+
+        let position = self
+            .tracklist_rx
+            .borrow()
+            .currently_playing()
+            .map(|track_id| PlaybackPosition {
+                track_id,
+                position: *self.position.borrow(),
+            });
+        if position == self.checkpointed_position {
+            return Ok(());
+        }
+
+        self.database.set_playback_position(position).await?;
+        self.checkpointed_position = position;
+        Ok(())
+    }
+
+    /// Cues the current track at its checkpointed position, playing it when
+    /// `resume_playback` is set.
+    async fn restore_position(&mut self, resume_playback: bool) -> Result<()> {
+        let current_track = self.tracklist_rx.borrow().currently_playing();
+        let Some(track_id) = current_track else {
+            return Ok(());
+        };
+
+        if let Some(saved) = self.database.get_playback_position().await
+            && saved.track_id == track_id
+        {
+            tracing::info!("Restoring position {:?}", saved.position);
+            self.resume_position = Some(saved);
+            self.checkpointed_position = Some(saved);
+            self.position.send_replace(saved.position);
+        }
+
+        if resume_playback {
+            self.play().await?;
+        }
+        Ok(())
+    }
+
     async fn handle_message(&mut self, notification: ControlCommand) -> Result<()> {
         match notification {
             ControlCommand::Album { id, index } => {
@@ -657,6 +1155,9 @@ impl Player {
             ControlCommand::Playlist { id, index, shuffle } => {
                 self.play_playlist(id, index, shuffle).await?;
             }
+            ControlCommand::LocalPlaylist { id, index, shuffle } => {
+                self.play_local_playlist(id, index, shuffle).await?;
+            }
             ControlCommand::ArtistTopTracks { artist_id, index } => {
                 self.play_top_tracks(artist_id, index).await?;
             }
@@ -767,12 +1268,147 @@ impl Player {
                     }
                 }
             }
+            ControlCommand::SetBitPerfect { enabled, respond } => {
+                if let Err(e) = self.database.set_bit_perfect(enabled).await {
+                    tracing::error!("Failed to save bit-perfect mode: {}", e);
+                } else {
+                    self.sink.set_bit_perfect(enabled);
+                    self.broadcast.send(Notification::Info(if enabled {
+                        "Bit-perfect playback enabled.".to_string()
+                    } else {
+                        "Bit-perfect playback disabled.".to_string()
+                    }));
+                    self.reload_current_track_with_stretch(None).await?;
+                    self.update_bit_perfect();
+                }
+                _ = respond.send(self.sink.is_bit_perfect());
+            }
+            ControlCommand::SetOutputSampleFormat {
+                device_name,
+                sample_format,
+            } => {
+                self.database
+                    .set_output_sample_format(device_name.as_deref(), sample_format)
+                    .await?;
+                let config = self.database.get_configuration().await?;
+                self.sink.set_output_sample_formats(config.output_sample_formats);
+                self.broadcast.send(Notification::Info(format!(
+                    "Output sample format set to {}.",
+                    sample_format
+                        .map(|format| format.to_string())
+                        .unwrap_or_else(|| "device default".to_string())
+                )));
+                self.reload_current_track_with_stretch(None).await?;
+            }
+            ControlCommand::SetUpmixPreset { preset } => {
+                self.database.set_upmix_preset(preset).await?;
+                self.sink.set_upmix_preset(preset);
+                self.broadcast.send(Notification::Info(format!(
+                    "Upmix preset set to {}.",
+                    preset
+                )));
+                self.reload_current_track_with_stretch(None).await?;
+            }
+            ControlCommand::SetCrossfeedDevice {
+                device_name,
+                enabled,
+            } => {
+                self.database
+                    .set_crossfeed_device(&device_name, enabled)
+                    .await?;
+                let crossfeed = self.database.get_configuration().await?.crossfeed;
+                let state = if crossfeed.enabled_for(&device_name) {
+                    "on"
+                } else {
+                    "off"
+                };
+                self.sink.set_crossfeed(crossfeed);
+                self.broadcast.send(Notification::Info(format!(
+                    "Crossfeed {} for '{}'.",
+                    state, device_name
+                )));
+                self.reload_current_track_with_stretch(None).await?;
+            }
+            ControlCommand::SetCrossfeedPreset { preset, strength } => {
+                self.database.set_crossfeed_preset(preset, strength).await?;
+                self.sink
+                    .set_crossfeed(self.database.get_configuration().await?.crossfeed);
+                self.broadcast.send(Notification::Info(format!(
+                    "Crossfeed set to {} at {:.0}%.",
+                    preset,
+                    strength.clamp(0.0, 1.0) * 100.0
+                )));
+                self.reload_current_track_with_stretch(None).await?;
+            }
+            ControlCommand::ReloadClapChain => {
+                self.load_clap_chain().await?;
+                self.broadcast
+                    .send(Notification::Info("Plugin chain reloaded.".to_string()));
+                self.reload_current_track_with_stretch(None).await?;
+            }
+            ControlCommand::SetClapParameter {
+                position,
+                param_id,
+                value,
+                respond,
+            } => {
+                let result = match self.sink.clap_chain().set_param(position, param_id, value) {
+                    Some((state, params)) => {
+                        self.database
+                            .set_clap_plugin_state(
+                                DEFAULT_CHAIN,
+                                position,
+                                state.as_deref(),
+                                &params,
+                            )
+                            .await
+                    }
+                    None => Err(Error::Plugin {
+                        message: format!("No plugin at position {position}"),
+                    }),
+                };
+                _ = respond.send(result);
+            }
+            ControlCommand::ProbePitch { respond } => {
+                let track = self.tracklist_rx.borrow().current_track().cloned();
+                let stretch = self
+                    .sink
+                    .supports_live_stretch()
+                    .then(|| *self.playback_stretch.read());
+                let position = self.sink.position();
+                let audio_cache_dir = self.downloader.audio_cache_dir().to_path_buf();
+
+                tokio::spawn(async move {
+                    let result = match track {
+                        Some(track) => {
+                            probe_track(&track, &audio_cache_dir, position, stretch).await
+                        }
+                        None => Err(Error::Analysis {
+                            message: "nothing is playing".into(),
+                        }),
+                    };
+                    _ = respond.send(result);
+                });
+            }
             ControlCommand::AddTrackToQueue { id } => self.add_track_to_queue(id).await?,
             ControlCommand::RemoveIndexFromQueue { index } => {
                 self.remove_index_from_queue(index).await?
             }
             ControlCommand::PlayTrackNext { id } => self.play_track_next(id).await?,
             ControlCommand::ReorderQueue { new_order } => self.reorder_queue(new_order).await?,
+            ControlCommand::SetRepeatMode { mode } => self.set_repeat_mode(mode).await?,
+            ControlCommand::SetShuffle { enabled } => self.set_shuffle(enabled).await?,
+            ControlCommand::SetSleepTimer { timer } => self.set_sleep_timer(timer),
+            ControlCommand::RampUpVolume { duration } => self.ramp_up_volume(duration),
+            ControlCommand::SetStopAfter { position } => self.set_stop_after(position).await?,
+            ControlCommand::SaveQueue { name, respond } => {
+                let result = self.save_queue(name).await;
+                _ = respond.send(());
+                result?
+            }
+            ControlCommand::LoadSavedQueue { name, append } => {
+                self.load_saved_queue(name, append).await?
+            }
         }
         Ok(())
     }
@@ -780,10 +1416,20 @@ impl Player {
     async fn track_finished(&mut self) -> Result<()> {
         let mut tracklist = self.tracklist_rx.borrow().clone();
 
-        let current_position = tracklist.current_position();
-        let new_position = current_position + 1;
+        if let Some(finished_id) = tracklist.current_track().map(|track| track.id) {
+            self.clear_stream_format(&mut tracklist, finished_id);
+        }
+
+        let repeat_mode = self.effective_repeat_mode();
 
-        let next_track = tracklist.skip_to_track(new_position as i32);
+        if tracklist.stops_after_current() {
+            return self.stop_at_boundary(tracklist, repeat_mode).await;
+        }
+
+        let next_track = match tracklist.next_position(repeat_mode) {
+            Some(next_position) => tracklist.skip_to_track(next_position as i32),
+            None => None,
+        };
 
         match next_track {
             Some(next_track) => {
@@ -817,6 +1463,9 @@ impl Player {
                 }
                 self.sink.clear()?;
                 self.position.send(Default::default())?;
+                if self.sleep_timer.is_some() {
+                    self.finish_sleep_timer();
+                }
             }
         }
         self.next_track_is_queried = false;
@@ -824,6 +1473,38 @@ impl Player {
         Ok(())
     }
 
+    /// Pauses with the next track cued up, keeping the queue.
+    async fn stop_at_boundary(
+        &mut self,
+        mut tracklist: Tracklist,
+        repeat_mode: RepeatMode,
+    ) -> Result<()> {
+        tracing::info!("Stopping after current track");
+        tracklist.stop_after = None;
+
+        match tracklist.next_position(repeat_mode) {
+            Some(next_position) => {
+                tracklist.skip_to_track(next_position as i32);
+            }
+            None => {
+                tracklist.reset();
+                if self.sleep_timer.is_some() {
+                    self.finish_sleep_timer();
+                }
+            }
+        }
+
+        self.set_target_status(Status::Paused);
+        if let Err(e) = self.sink.pause() {
+            tracing::warn!("Failed to pause sink: {}", e);
+        }
+        self.sink.clear()?;
+        self.next_track_is_queried = false;
+        self.next_track_in_sink_queue = false;
+        self.position.send(Default::default())?;
+        self.broadcast_tracklist(tracklist).await
+    }
+
     async fn reload_current_track_with_stretch(
         &mut self,
         old_time_stretch_ratio: Option<f32>,
@@ -835,7 +1516,7 @@ impl Player {
             Some(t) => t.clone(),
             None => return Ok(()),
         };
-        let track_url = self.client.track_url(track.id).await.ok();
+        let track_url = self.stream_url(&track).await.ok();
         let track_url = match track_url {
             Some(u) => u,
             None => return Ok(()),
@@ -884,7 +1565,14 @@ impl Player {
 
         tracing::info!("Done buffering track: {}", path.to_string_lossy());
 
-        match self.sink.query_track(&path, None) {
+        let current_track = self.tracklist_rx.borrow().currently_playing();
+        let start_at = self
+            .resume_position
+            .take()
+            .filter(|resume_position| Some(resume_position.track_id) == current_track)
+            .map(|resume_position| resume_position.position);
+
+        match self.sink.query_track(&path, start_at) {
             Ok(result) => {
                 self.next_track_in_sink_queue = match result {
                     QueryTrackResult::Queued => true,
@@ -896,23 +1584,17 @@ impl Player {
                 tracing::error!("Failed to query track: {}", error_msg);
                 
                 if error_msg.contains("device") || error_msg.contains("no longer available") {
-                    tracing::warn!("Audio device error detected, pausing playback and resetting to default");
+                    tracing::warn!("Audio device error detected, pausing playback and using default device");
                     if let Err(e) = self.sink.pause() {
                         tracing::warn!("Failed to pause sink: {}", e);
                     }
+                    self.interrupt_for_device_error(Some(start_at.unwrap_or_default()))?;
                     self.set_target_status(Status::Paused);
                     
                     self.sink.set_device(None);
                     
-                    let database = self.database.clone();
-                    tokio::spawn(async move {
-                        if let Err(db_err) = database.set_audio_device(None).await {
-                            tracing::error!("Failed to update database after device error: {}", db_err);
-                        }
-                    });
-                    
                     self.broadcast.send(Notification::Warning(
-                        "Audio device error. Resetting to default device.".to_string()
+                        "Audio device error. Using default device until it is connected again.".to_string()
                     ));
                     return Ok(());
                 }
@@ -922,7 +1604,152 @@ impl Player {
         Ok(())
     }
 
+    /// Remembers what a device error interrupted. `position` is where the
+    /// interrupted track starts when it had not started playing yet.
+    fn interrupt_for_device_error(&mut self, position: Option<Duration>) -> Result<()> {
+        let status = *self.target_status.borrow();
+        let track_id = self.tracklist_rx.borrow().currently_playing();
+        if let Some(position) = position {
+            self.position.send(position)?;
+        }
+        self.device_interruption = track_id
+            .filter(|_| status != Status::Paused)
+            .map(|track_id| (track_id, status));
+        Ok(())
+    }
+
+    /// Continues the track a device error paused where it stopped, unless
+    /// playback has moved on since.
+    async fn resume_after_device_error(&mut self) -> Result<()> {
+        let Some((track_id, status)) = self.device_interruption.take() else {
+            return Ok(());
+        };
+        if *self.target_status.borrow() != Status::Paused {
+            return Ok(());
+        }
+        let Some(track) = self
+            .tracklist_rx
+            .borrow()
+            .current_track()
+            .filter(|track| track.id == track_id)
+            .cloned()
+        else {
+            return Ok(());
+        };
+
+        let track_url = self.stream_url(&track).await?;
+        let Some(path) = self
+            .downloader
+            .ensure_track_is_downloaded(track_url, &track)
+            .await
+        else {
+            return Ok(());
+        };
+
+        let position = *self.position.borrow();
+        self.sink.clear()?;
+        self.next_track_is_queried = false;
+        self.next_track_in_sink_queue = false;
+        self.sink.query_track(&path, Some(position))?;
+        self.position.send(position)?;
+
+        match status {
+            Status::Playing | Status::Buffering => {
+                self.sink.play()?;
+                self.set_target_status(Status::Playing);
+            }
+            Status::Paused => (),
+        }
+        Ok(())
+    }
+
+    async fn watch_audio_devices(&mut self) -> Result<()> {
+        let (devices, default_device) = tokio::task::spawn_blocking(|| {
+            let devices = list_audio_devices()?
+                .into_iter()
+                .map(|device| device.name)
+                .collect::<Vec<_>>();
+            let default_device = get_default_device_name()?;
+            Ok::<_, crate::error::Error>((devices, default_device))
+        })
+        .await
+        .expect("infallible")?;
+
+        let default_changed = self.default_audio_device != default_device;
+        self.default_audio_device = default_device;
+
+        let Some(known_devices) = self.known_audio_devices.replace(devices.clone()) else {
+            return Ok(());
+        };
+
+        for device in devices.iter().filter(|device| !known_devices.contains(device)) {
+            tracing::info!("Audio device connected: {}", device);
+            self.broadcast
+                .send(Notification::Info(format!("Audio device '{}' connected.", device)));
+        }
+        for device in known_devices.iter().filter(|device| !devices.contains(device)) {
+            tracing::info!("Audio device disconnected: {}", device);
+            self.broadcast
+                .send(Notification::Warning(format!("Audio device '{}' disconnected.", device)));
+        }
+
+        let preferred_device = self.database.get_configuration().await?.audio_device_name;
+        let active_device = self.sink.get_device();
+
+        match preferred_device {
+            Some(preferred) if devices.contains(&preferred) => {
+                if active_device.as_ref() != Some(&preferred) {
+                    tracing::info!("Preferred audio device '{}' returned", preferred);
+                    self.set_audio_device(Some(preferred.clone())).await?;
+                    self.broadcast.send(Notification::Success(format!(
+                        "Output changed back to '{}'.",
+                        preferred
+                    )));
+                    self.resume_after_device_error().await?;
+                }
+            }
+            Some(preferred) => {
+                if active_device.as_ref() == Some(&preferred) {
+                    tracing::warn!("Preferred audio device '{}' is unavailable", preferred);
+                    self.set_audio_device(None).await?;
+                    self.broadcast.send(Notification::Warning(format!(
+                        "Audio device '{}' is unavailable. Using default device until it is connected again.",
+                        preferred
+                    )));
+                }
+            }
+            None => {
+                if default_changed {
+                    tracing::info!("Default audio device changed: {:?}", self.default_audio_device);
+                    self.set_audio_device(None).await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn load_clap_chain(&self) -> Result<()> {
+        let entries = self
+            .database
+            .get_clap_plugins(DEFAULT_CHAIN)
+            .await?
+            .into_iter()
+            .filter_map(|saved| match ClapChainEntry::restore(&saved) {
+                Ok(entry) => Some(entry),
+                Err(err) => {
+                    self.broadcast.send_error(err.to_string());
+                    None
+                }
+            })
+            .collect();
+
+        self.sink.clap_chain().set_entries(entries);
+        Ok(())
+    }
+
     pub async fn player_loop(&mut self, mut exit_receiver: ExitReceiver) -> Result<()> {
+        let mut resume_playback = false;
         if let Ok(config) = self.database.get_configuration().await {
             if let Some(device_name) = config.audio_device_name {
                 tracing::info!("Setting initial audio device from database: {}", device_name);
@@ -933,9 +1760,24 @@ impl Player {
                 pitch_semitones: config.pitch_semitones,
                 pitch_cents: config.pitch_cents,
             };
+            self.sink.set_bit_perfect(config.bit_perfect);
+            self.sink.set_output_sample_formats(config.output_sample_formats);
+            self.sink.set_upmix_preset(config.upmix_preset);
+            self.sink.set_crossfeed(config.crossfeed);
+            self.repeat_mode.send_replace(config.repeat_mode);
+            resume_playback = config.resume_playback;
+        }
+        if let Err(err) = self.load_clap_chain().await {
+            tracing::warn!("Failed to load plugin chain: {}", err);
+        }
+
+        if let Err(err) = self.restore_position(resume_playback).await {
+            tracing::warn!("Failed to restore playback: {}", err);
         }
 
         let mut interval = tokio::time::interval(Duration::from_millis(INTERVAL_MS));
+        let mut device_interval =
+            tokio::time::interval(Duration::from_secs(DEVICE_WATCH_INTERVAL_SECS));
 
         loop {
             select! {
@@ -945,6 +1787,12 @@ impl Player {
                     };
                 }
 
+                _ = device_interval.tick() => {
+                    if let Err(err) = self.watch_audio_devices().await {
+                        tracing::warn!("Failed to watch audio devices: {}", err);
+                    };
+                }
+
                 Some(notification) = self.controls_rx.recv() => {
                     if let Err(err) = self.handle_message(notification).await {
                         self.broadcast.send_error(err.to_string());
@@ -973,6 +1821,9 @@ impl Player {
                 }
                 Ok(exit) = exit_receiver.recv() => {
                     if exit {
+                        if let Err(err) = self.checkpoint_position().await {
+                            tracing::warn!("Failed to save playback position: {}", err);
+                        }
                         break Ok(());
                     }
                 }
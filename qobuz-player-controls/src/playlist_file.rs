@@ -0,0 +1,696 @@
+use std::{fmt, path::Path, str::FromStr};
+
+use qobuz_player_models::{Playlist, Track};
+
+use crate::{Result, client::Client, error::Error};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaylistFormat {
+    M3u8,
+    Xspf,
+    Csv,
+}
+
+impl PlaylistFormat {
+    pub const ALL: [Self; 3] = [Self::M3u8, Self::Xspf, Self::Csv];
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::M3u8 => "m3u8",
+            Self::Xspf => "xspf",
+            Self::Csv => "csv",
+        }
+    }
+
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            Self::M3u8 => "audio/x-mpegurl",
+            Self::Xspf => "application/xspf+xml",
+            Self::Csv => "text/csv",
+        }
+    }
+
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_string_lossy().to_lowercase();
+        match extension.as_str() {
+            "m3u" => Some(Self::M3u8),
+            extension => extension.parse().ok(),
+        }
+    }
+}
+
+impl fmt::Display for PlaylistFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.extension())
+    }
+}
+
+impl FromStr for PlaylistFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|format| format.extension() == value.to_lowercase())
+            .ok_or_else(|| format!("Unknown playlist format '{value}'. Use m3u8, xspf or csv"))
+    }
+}
+
+/// Track as written to or read from a playlist file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PlaylistEntry {
+    pub artist: Option<String>,
+    pub title: String,
+    pub album: Option<String>,
+    pub duration_seconds: Option<u32>,
+    pub isrc: Option<String>,
+    pub track_id: Option<u32>,
+}
+
+impl From<&Track> for PlaylistEntry {
+    fn from(track: &Track) -> Self {
+        Self {
+            artist: track.artist_name.clone(),
+            title: track.title.clone(),
+            album: track.album_title.clone(),
+            duration_seconds: Some(track.duration_seconds),
+            isrc: track.isrc.clone(),
+            track_id: Some(track.id),
+        }
+    }
+}
+
+impl fmt::Display for PlaylistEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.artist {
+            Some(artist) => write!(f, "{artist} - {}", self.title),
+            None => f.write_str(&self.title),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PlaylistFile {
+    pub title: Option<String>,
+    pub entries: Vec<PlaylistEntry>,
+}
+
+#[derive(Debug)]
+pub struct ImportReport {
+    pub playlist: Playlist,
+    pub added: usize,
+    pub unmatched: Vec<PlaylistEntry>,
+}
+
+pub fn track_url(id: u32) -> String {
+    format!("https://open.qobuz.com/track/{id}")
+}
+
+fn track_id_from_url(url: &str) -> Option<u32> {
+    let (_, path) = url.split_once("qobuz.com/track/")?;
+    path.split(['/', '?', '#']).next()?.parse().ok()
+}
+
+pub fn export_tracks(format: PlaylistFormat, title: &str, tracks: &[Track]) -> String {
+    let entries: Vec<PlaylistEntry> = tracks.iter().map(PlaylistEntry::from).collect();
+    write_playlist(format, title, &entries)
+}
+
+pub fn write_playlist(format: PlaylistFormat, title: &str, entries: &[PlaylistEntry]) -> String {
+    match format {
+        PlaylistFormat::M3u8 => write_m3u8(title, entries),
+        PlaylistFormat::Xspf => write_xspf(title, entries),
+        PlaylistFormat::Csv => write_csv(entries),
+    }
+}
+
+pub fn read_playlist(format: PlaylistFormat, contents: &str) -> Result<PlaylistFile> {
+    let contents = contents.trim_start_matches('\u{feff}');
+    let file = match format {
+        PlaylistFormat::M3u8 => read_m3u8(contents),
+        PlaylistFormat::Xspf => read_xspf(contents),
+        PlaylistFormat::Csv => read_csv(contents)?,
+    };
+
+    if file.entries.is_empty() {
+        return Err(Error::Import {
+            message: "No tracks found in the playlist file".into(),
+        });
+    }
+    Ok(file)
+}
+
+/// Creates a Qobuz playlist from the entries, searching for tracks without a Qobuz URL.
+pub async fn import_playlist(
+    client: &Client,
+    name: String,
+    entries: &[PlaylistEntry],
+) -> Result<ImportReport> {
+    let mut track_ids = Vec::new();
+    let mut unmatched = Vec::new();
+
+    for entry in entries {
+        match resolve_track(client, entry).await? {
+            Some(id) => track_ids.push(id),
+            None => unmatched.push(entry.clone()),
+        }
+    }
+
+    if track_ids.is_empty() {
+        return Err(Error::Import {
+            message: format!("None of the {} tracks could be matched", entries.len()),
+        });
+    }
+
+    let playlist = client
+        .create_playlist(name, false, String::new(), None)
+        .await?;
+    let playlist = client.playlist_add_track(playlist.id, &track_ids).await?;
+
+    Ok(ImportReport {
+        playlist,
+        added: track_ids.len(),
+        unmatched,
+    })
+}
+
+async fn resolve_track(client: &Client, entry: &PlaylistEntry) -> Result<Option<u32>> {
+    if let Some(id) = entry.track_id {
+        return Ok(Some(id));
+    }
+
+    let query = match &entry.artist {
+        Some(artist) => format!("{artist} {}", entry.title),
+        None => entry.title.clone(),
+    };
+    let results = client.search(query).await?;
+
+    Ok(best_match(entry, &results.tracks).map(|track| track.id))
+}
+
+fn best_match<'a>(entry: &PlaylistEntry, tracks: &'a [Track]) -> Option<&'a Track> {
+    if let Some(isrc) = &entry.isrc
+        && let Some(track) = tracks.iter().find(|track| {
+            track
+                .isrc
+                .as_ref()
+                .is_some_and(|candidate| candidate.eq_ignore_ascii_case(isrc))
+        })
+    {
+        return Some(track);
+    }
+
+    let title = normalize(&entry.title);
+    let artist = entry.artist.as_deref().map(normalize);
+
+    tracks.iter().find(|track| {
+        normalize(&track.title) == title
+            && artist.as_ref().is_none_or(|artist| {
+                track
+                    .artist_name
+                    .as_deref()
+                    .is_some_and(|name| normalize(name) == *artist)
+            })
+    })
+}
+
+fn normalize(value: &str) -> String {
+    value
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn write_m3u8(title: &str, entries: &[PlaylistEntry]) -> String {
+    let mut out = format!("#EXTM3U\n#PLAYLIST:{}\n", single_line(title));
+
+    for entry in entries {
+        let duration = entry.duration_seconds.map_or(-1, i64::from);
+        out.push_str(&format!(
+            "#EXTINF:{duration},{}\n",
+            single_line(&entry.to_string())
+        ));
+        if let Some(album) = &entry.album {
+            out.push_str(&format!("#EXTALB:{}\n", single_line(album)));
+        }
+        if let Some(isrc) = &entry.isrc {
+            out.push_str(&format!("#EXTISRC:{}\n", single_line(isrc)));
+        }
+        match entry.track_id {
+            Some(id) => out.push_str(&track_url(id)),
+            None => out.push_str(&single_line(&entry.title)),
+        }
+        out.push('\n');
+    }
+
+    out
+}
+
+fn read_m3u8(contents: &str) -> PlaylistFile {
+    let mut file = PlaylistFile::default();
+    let mut pending = PlaylistEntry::default();
+
+    for line in contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+    {
+        if let Some(title) = line.strip_prefix("#PLAYLIST:") {
+            file.title = non_empty(title);
+        } else if let Some(info) = line.strip_prefix("#EXTINF:") {
+            let (duration, name) = info.split_once(',').unwrap_or((info, ""));
+            pending.duration_seconds = duration
+                .split_whitespace()
+                .next()
+                .and_then(|duration| duration.parse::<f64>().ok())
+                .filter(|duration| *duration >= 0.0)
+                .map(|duration| duration.round() as u32);
+            match name.split_once(" - ") {
+                Some((artist, title)) => {
+                    pending.artist = non_empty(artist);
+                    pending.title = title.trim().to_string();
+                }
+                None => pending.title = name.trim().to_string(),
+            }
+        } else if let Some(album) = line.strip_prefix("#EXTALB:") {
+            pending.album = non_empty(album);
+        } else if let Some(isrc) = line.strip_prefix("#EXTISRC:") {
+            pending.isrc = non_empty(isrc);
+        } else if !line.starts_with('#') {
+            let mut entry = std::mem::take(&mut pending);
+            entry.track_id = track_id_from_url(line);
+            if entry.title.is_empty() && entry.track_id.is_none() {
+                entry.title = Path::new(line)
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| line.to_string());
+            }
+            file.entries.push(entry);
+        }
+    }
+
+    file
+}
+
+fn write_xspf(title: &str, entries: &[PlaylistEntry]) -> String {
+    let mut out = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n",
+    );
+    out.push_str(&format!(
+        "  <title>{}</title>\n  <trackList>\n",
+        escape_xml(title)
+    ));
+
+    for entry in entries {
+        out.push_str("    <track>\n");
+        if let Some(id) = entry.track_id {
+            out.push_str(&format!("      <location>{}</location>\n", track_url(id)));
+        }
+        if let Some(isrc) = &entry.isrc {
+            out.push_str(&format!(
+                "      <identifier>isrc:{}</identifier>\n",
+                escape_xml(isrc)
+            ));
+        }
+        out.push_str(&format!(
+            "      <title>{}</title>\n",
+            escape_xml(&entry.title)
+        ));
+        if let Some(artist) = &entry.artist {
+            out.push_str(&format!(
+                "      <creator>{}</creator>\n",
+                escape_xml(artist)
+            ));
+        }
+        if let Some(album) = &entry.album {
+            out.push_str(&format!("      <album>{}</album>\n", escape_xml(album)));
+        }
+        if let Some(duration) = entry.duration_seconds {
+            out.push_str(&format!(
+                "      <duration>{}</duration>\n",
+                u64::from(duration) * 1000
+            ));
+        }
+        out.push_str("    </track>\n");
+    }
+
+    out.push_str("  </trackList>\n</playlist>\n");
+    out
+}
+
+fn read_xspf(contents: &str) -> PlaylistFile {
+    let track_list = xml_elements(contents, "trackList")
+        .into_iter()
+        .next()
+        .unwrap_or_default();
+    let header = contents.split("<trackList").next().unwrap_or_default();
+
+    let entries = xml_elements(track_list, "track")
+        .into_iter()
+        .map(|track| {
+            let text = |tag: &str| {
+                xml_elements(track, tag)
+                    .into_iter()
+                    .next()
+                    .and_then(|value| non_empty(&unescape_xml(value)))
+            };
+
+            PlaylistEntry {
+                artist: text("creator"),
+                title: text("title").unwrap_or_default(),
+                album: text("album"),
+                duration_seconds: text("duration")
+                    .and_then(|duration| duration.parse::<u64>().ok())
+                    .map(|milliseconds| (milliseconds / 1000) as u32),
+                isrc: xml_elements(track, "identifier")
+                    .into_iter()
+                    .find_map(|identifier| {
+                        let identifier = unescape_xml(identifier);
+                        identifier.trim().strip_prefix("isrc:").and_then(non_empty)
+                    }),
+                track_id: xml_elements(track, "location")
+                    .into_iter()
+                    .find_map(|location| track_id_from_url(&unescape_xml(location))),
+            }
+        })
+        .filter(|entry| !entry.title.is_empty() || entry.track_id.is_some())
+        .collect();
+
+    PlaylistFile {
+        title: xml_elements(header, "title")
+            .into_iter()
+            .next()
+            .and_then(|title| non_empty(&unescape_xml(title))),
+        entries,
+    }
+}
+
+const CSV_HEADER: [&str; 6] = ["artist", "title", "album", "duration", "isrc", "url"];
+
+fn write_csv(entries: &[PlaylistEntry]) -> String {
+    let mut out = CSV_HEADER.join(",");
+    out.push('\n');
+
+    for entry in entries {
+        let fields = [
+            entry.artist.clone().unwrap_or_default(),
+            entry.title.clone(),
+            entry.album.clone().unwrap_or_default(),
+            entry
+                .duration_seconds
+                .map(|duration| duration.to_string())
+                .unwrap_or_default(),
+            entry.isrc.clone().unwrap_or_default(),
+            entry.track_id.map(track_url).unwrap_or_default(),
+        ];
+        let fields: Vec<String> = fields.iter().map(|field| escape_csv(field)).collect();
+        out.push_str(&fields.join(","));
+        out.push('\n');
+    }
+
+    out
+}
+
+fn read_csv(contents: &str) -> Result<PlaylistFile> {
+    let mut rows = parse_csv(contents).into_iter();
+    let header: Vec<String> = rows
+        .next()
+        .unwrap_or_default()
+        .iter()
+        .map(|column| column.trim().to_lowercase())
+        .collect();
+    let column = |name: &str| header.iter().position(|column| column == name);
+
+    let title_column = column("title").ok_or_else(|| Error::Import {
+        message: "The CSV file needs a title column".into(),
+    })?;
+    let artist_column = column("artist");
+    let album_column = column("album");
+    let duration_column = column("duration");
+    let isrc_column = column("isrc");
+    let url_column = column("url");
+
+    let entries = rows
+        .map(|row| {
+            let field = |index: Option<usize>| {
+                index
+                    .and_then(|index| row.get(index))
+                    .and_then(|value| non_empty(value))
+            };
+
+            PlaylistEntry {
+                artist: field(artist_column),
+                title: field(Some(title_column)).unwrap_or_default(),
+                album: field(album_column),
+                duration_seconds: field(duration_column).and_then(|value| value.parse().ok()),
+                isrc: field(isrc_column),
+                track_id: field(url_column).and_then(|url| track_id_from_url(&url)),
+            }
+        })
+        .filter(|entry| !entry.title.is_empty() || entry.track_id.is_some())
+        .collect();
+
+    Ok(PlaylistFile {
+        title: None,
+        entries,
+    })
+}
+
+fn parse_csv(contents: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = contents.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => quoted = false,
+            (true, c) => field.push(c),
+            (false, '"') => quoted = true,
+            (false, ',') => row.push(std::mem::take(&mut field)),
+            (false, '\r') => {}
+            (false, '\n') => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            (false, c) => field.push(c),
+        }
+    }
+
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+
+    rows.retain(|row| row.iter().any(|field| !field.trim().is_empty()));
+    rows
+}
+
+fn escape_csv(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Inner contents of each `<tag>` element, without nesting support.
+fn xml_elements<'a>(xml: &'a str, tag: &str) -> Vec<&'a str> {
+    let open = format!("<{tag}");
+    let close = format!("</{tag}>");
+    let mut found = Vec::new();
+    let mut rest = xml;
+
+    while let Some(start) = rest.find(&open) {
+        let after = &rest[start + open.len()..];
+        if !after.starts_with(|c: char| c == '>' || c.is_whitespace()) {
+            rest = after;
+            continue;
+        }
+        let Some(tag_end) = after.find('>') else {
+            break;
+        };
+        let content = &after[tag_end + 1..];
+        if after[..tag_end].ends_with('/') {
+            rest = content;
+            continue;
+        }
+        let Some(end) = content.find(&close) else {
+            break;
+        };
+        found.push(&content[..end]);
+        rest = &content[end + close.len()..];
+    }
+
+    found
+}
+
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn unescape_xml(value: &str) -> String {
+    let value = value.trim();
+    if let Some(data) = value
+        .strip_prefix("<![CDATA[")
+        .and_then(|value| value.strip_suffix("]]>"))
+    {
+        return data.to_string();
+    }
+
+    let mut out = String::new();
+    let mut rest = value;
+
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        let entity = &rest[start + 1..];
+        let Some(end) = entity.find(';') else {
+            out.push_str(&rest[start..]);
+            return out;
+        };
+
+        let decoded = match &entity[..end] {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            code => code
+                .strip_prefix("#x")
+                .or_else(|| code.strip_prefix("#X"))
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| code.strip_prefix('#').map(str::parse))
+                .and_then(|code| code.ok())
+                .and_then(char::from_u32),
+        };
+
+        match decoded {
+            Some(c) => {
+                out.push(c);
+                rest = &entity[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = entity;
+            }
+        }
+    }
+
+    out.push_str(rest);
+    out
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+fn single_line(value: &str) -> String {
+    value.replace(['\r', '\n'], " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries() -> Vec<PlaylistEntry> {
+        vec![
+            PlaylistEntry {
+                artist: Some("Simon & Garfunkel".into()),
+                title: "The Boxer, \"Live\"".into(),
+                album: Some("Bridge <Over> Troubled Water".into()),
+                duration_seconds: Some(308),
+                isrc: Some("USSM16900395".into()),
+                track_id: Some(1234),
+            },
+            PlaylistEntry {
+                artist: None,
+                title: "Untitled".into(),
+                album: None,
+                duration_seconds: None,
+                isrc: None,
+                track_id: None,
+            },
+        ]
+    }
+
+    #[test]
+    fn round_trips_every_format() {
+        for format in PlaylistFormat::ALL {
+            let written = write_playlist(format, "Road trip", &entries());
+            let file = read_playlist(format, &written).unwrap();
+
+            assert_eq!(file.entries, entries(), "{format}");
+            if format != PlaylistFormat::Csv {
+                assert_eq!(file.title.as_deref(), Some("Road trip"), "{format}");
+            }
+        }
+    }
+
+    #[test]
+    fn reads_foreign_files() {
+        let m3u = "#EXTM3U\n#EXTINF:61.6 tvg-id=\"x\",Artist - Song\nmusic/song.flac\nother/Track Two.mp3\n";
+        let file = read_playlist(PlaylistFormat::M3u8, m3u).unwrap();
+        assert_eq!(file.entries[0].artist.as_deref(), Some("Artist"));
+        assert_eq!(file.entries[0].duration_seconds, Some(62));
+        assert_eq!(file.entries[1].title, "Track Two");
+
+        let csv = "Title,Artist,URL\r\nSong,Band,https://play.qobuz.com/track/99?x=1\r\n";
+        let file = read_playlist(PlaylistFormat::Csv, csv).unwrap();
+        assert_eq!(file.entries[0].artist.as_deref(), Some("Band"));
+        assert_eq!(file.entries[0].track_id, Some(99));
+        assert!(read_playlist(PlaylistFormat::Csv, "artist\nBand\n").is_err());
+
+        assert_eq!(
+            unescape_xml("a &#38; b &#x3C;c&gt; &bogus"),
+            "a & b <c> &bogus"
+        );
+        assert_eq!(
+            PlaylistFormat::from_path(Path::new("x.M3U")),
+            Some(PlaylistFormat::M3u8)
+        );
+    }
+
+    #[test]
+    fn matches_by_isrc_then_title_and_artist() {
+        let track = |id, title: &str, artist: &str, isrc: Option<&str>| Track {
+            id,
+            title: title.into(),
+            artist_name: Some(artist.into()),
+            isrc: isrc.map(Into::into),
+            ..Default::default()
+        };
+        let tracks = [
+            track(1, "Song (Remastered)", "Band", Some("AAA")),
+            track(2, "Song", "Other", None),
+            track(3, "song!", "The Band", None),
+            track(4, "Song", "band", None),
+        ];
+
+        let mut entry = PlaylistEntry {
+            artist: Some("Band".into()),
+            title: "Song".into(),
+            isrc: Some("aaa".into()),
+            ..Default::default()
+        };
+        assert_eq!(best_match(&entry, &tracks).map(|t| t.id), Some(1));
+
+        entry.isrc = None;
+        assert_eq!(best_match(&entry, &tracks).map(|t| t.id), Some(4));
+
+        entry.artist = Some("Nobody".into());
+        assert_eq!(best_match(&entry, &tracks), None);
+    }
+}
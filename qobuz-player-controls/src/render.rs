@@ -0,0 +1,295 @@
+use std::{
+    fs,
+    io::{BufWriter, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, atomic::AtomicU32},
+    time::Duration,
+};
+
+use parking_lot::RwLock;
+use qobuz_player_models::Track;
+use rodio::{Source, decoder::DecoderBuilder};
+
+use crate::{
+    Result,
+    clap::{ClapChain, ClapChainEntry, DEFAULT_CHAIN},
+    client::Client,
+    database::Database,
+    dither::DitherSource,
+    downloader::find_cached_track,
+    error::Error,
+    export::sanitize_path_component,
+    sink::{PlaybackStretchConfig, effect_source},
+    stretch_source_signalsmith::normalize_ratio,
+};
+
+const BITS_PER_SAMPLE: u16 = 24;
+const WAV_HEADER_LEN: u32 = 44;
+
+/// Part of a track in track time, before tempo is applied. Without an end
+/// the section runs to the end of the track.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RenderSection {
+    pub start: Duration,
+    pub end: Option<Duration>,
+}
+
+impl RenderSection {
+    /// Section between `start` and `end` seconds, rejecting negative,
+    /// infinite and out of order times.
+    pub fn from_secs(start: Option<f64>, end: Option<f64>) -> Result<Self> {
+        let duration = |seconds: f64| {
+            Duration::try_from_secs_f64(seconds).map_err(|_| Error::Render {
+                message: format!("Invalid section time: {seconds}"),
+            })
+        };
+        let section = Self {
+            start: start.map(duration).transpose()?.unwrap_or_default(),
+            end: end.map(duration).transpose()?,
+        };
+
+        if section.end.is_some_and(|end| end <= section.start) {
+            return Err(Error::Render {
+                message: "The section must end after it starts".into(),
+            });
+        }
+
+        Ok(section)
+    }
+}
+
+#[derive(Debug)]
+pub struct RenderReport {
+    pub path: PathBuf,
+    pub duration: Duration,
+}
+
+/// Renders a cached track to a 24-bit WAV file with the saved tempo, pitch
+/// and plugin chain applied.
+pub async fn render_track(
+    client: &Client,
+    database: &Database,
+    audio_cache_dir: &Path,
+    track_id: u32,
+    section: RenderSection,
+    target_dir: &Path,
+) -> Result<RenderReport> {
+    let track = client.track(track_id).await?;
+    let cached = find_cached_track(&track, audio_cache_dir).ok_or_else(|| Error::Render {
+        message: format!("'{}' is not cached. Play it once first", track.title),
+    })?;
+
+    let config = database.get_configuration().await?;
+    let stretch = PlaybackStretchConfig {
+        time_stretch_ratio: config.time_stretch_ratio,
+        pitch_semitones: config.pitch_semitones,
+        pitch_cents: config.pitch_cents,
+    };
+    let plugins = database.get_clap_plugins(DEFAULT_CHAIN).await?;
+    let target = target_dir.join(render_file_name(&track, stretch, section));
+
+    tokio::task::spawn_blocking(move || {
+        let chain = ClapChain::default();
+        chain.set_entries(
+            plugins
+                .iter()
+                .filter_map(|saved| {
+                    ClapChainEntry::restore(saved)
+                        .inspect_err(|e| tracing::warn!("Skipping plugin in render: {e}"))
+                        .ok()
+                })
+                .collect(),
+        );
+
+        let duration = render_to_wav(&cached, &target, section, stretch, &chain)?;
+        Ok(RenderReport {
+            path: target,
+            duration,
+        })
+    })
+    .await
+    .expect("infallible")
+}
+
+/// Runs a file through the same effect chain as playback and writes the
+/// result as fast as it can be computed. Returns the rendered duration.
+pub fn render_to_wav(
+    track_path: &Path,
+    target: &Path,
+    section: RenderSection,
+    stretch: PlaybackStretchConfig,
+    clap_chain: &ClapChain,
+) -> Result<Duration> {
+    let file = fs::File::open(track_path).map_err(|e| render_error(track_path, e))?;
+    let decoded = DecoderBuilder::new()
+        .with_data(file)
+        .with_seekable(true)
+        .build()?;
+
+    let stretched = decoded.channels() == 2;
+    let ratio = if stretched {
+        normalize_ratio(stretch.time_stretch_ratio) as f64
+    } else {
+        1.0
+    };
+    let playback_stretch = stretched.then(|| Arc::new(RwLock::new(stretch)));
+    let mut source = effect_source(decoded, playback_stretch, clap_chain);
+
+    let channels = source.channels();
+    let sample_rate = source.sample_rate();
+    if section.start > Duration::ZERO {
+        source.try_seek(Duration::from_secs_f64(
+            section.start.as_secs_f64() / ratio,
+        ))?;
+    }
+    let frames = section.end.map(|end| {
+        let length = end.saturating_sub(section.start).as_secs_f64() / ratio;
+        (length * sample_rate as f64).round() as usize
+    });
+
+    let samples = DitherSource::new(source, Arc::new(AtomicU32::new(BITS_PER_SAMPLE as u32)));
+    let samples: Box<dyn Iterator<Item = f32>> = match frames {
+        Some(frames) => Box::new(samples.take(frames * channels as usize)),
+        None => Box::new(samples),
+    };
+
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent).map_err(|e| render_error(parent, e))?;
+    }
+    let written = write_wav(target, channels, sample_rate, samples)
+        .map_err(|e| render_error(target, e))?;
+
+    let frames_written = written / (channels as u64 * (BITS_PER_SAMPLE / 8) as u64);
+    Ok(Duration::from_secs_f64(
+        frames_written as f64 / sample_rate as f64,
+    ))
+}
+
+/// Writes 24-bit PCM to a new file and returns the number of data bytes.
+fn write_wav(
+    target: &Path,
+    channels: u16,
+    sample_rate: u32,
+    samples: impl Iterator<Item = f32>,
+) -> std::io::Result<u64> {
+    let bytes_per_sample = BITS_PER_SAMPLE / 8;
+    let block_align = channels * bytes_per_sample;
+    let file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(target)?;
+    let mut writer = BufWriter::new(file);
+
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&0u32.to_le_bytes())?;
+    writer.write_all(b"WAVEfmt ")?;
+    writer.write_all(&16u32.to_le_bytes())?;
+    writer.write_all(&1u16.to_le_bytes())?;
+    writer.write_all(&channels.to_le_bytes())?;
+    writer.write_all(&sample_rate.to_le_bytes())?;
+    writer.write_all(&(sample_rate * block_align as u32).to_le_bytes())?;
+    writer.write_all(&block_align.to_le_bytes())?;
+    writer.write_all(&BITS_PER_SAMPLE.to_le_bytes())?;
+    writer.write_all(b"data")?;
+    writer.write_all(&0u32.to_le_bytes())?;
+
+    let scale = (1 << (BITS_PER_SAMPLE - 1)) as f32;
+    let mut data_len = 0u64;
+    for sample in samples {
+        let value = (sample * scale).round().clamp(-scale, scale - 1.0) as i32;
+        writer.write_all(&value.to_le_bytes()[..bytes_per_sample as usize])?;
+        data_len += bytes_per_sample as u64;
+    }
+
+    let data_len_u32 = u32::try_from(data_len).unwrap_or(u32::MAX);
+    writer.seek(SeekFrom::Start(4))?;
+    writer.write_all(
+        &(WAV_HEADER_LEN - 8)
+            .saturating_add(data_len_u32)
+            .to_le_bytes(),
+    )?;
+    writer.seek(SeekFrom::Start(WAV_HEADER_LEN as u64 - 4))?;
+    writer.write_all(&data_len_u32.to_le_bytes())?;
+    writer.flush()?;
+
+    Ok(data_len)
+}
+
+fn render_file_name(track: &Track, stretch: PlaybackStretchConfig, section: RenderSection) -> String {
+    let mut name = match &track.artist_name {
+        Some(artist) => format!("{artist} - {}", track.title),
+        None => track.title.clone(),
+    };
+
+    if section.start > Duration::ZERO || section.end.is_some() {
+        let end = section
+            .end
+            .map(|end| format!("{:.0}s", end.as_secs_f64()))
+            .unwrap_or_else(|| "end".to_string());
+        name.push_str(&format!(" [{:.0}s-{end}]", section.start.as_secs_f64()));
+    }
+    if stretch.time_stretch_ratio != 1.0 {
+        name.push_str(&format!(" {:.2}x", stretch.time_stretch_ratio));
+    }
+    let cents = stretch.pitch_semitones as i32 * 100 + stretch.pitch_cents as i32;
+    if cents != 0 {
+        name.push_str(&format!(" {cents:+}c"));
+    }
+
+    format!("{}.wav", sanitize_path_component(&name))
+}
+
+fn render_error(path: &Path, error: std::io::Error) -> Error {
+    Error::Render {
+        message: format!("{}: {error}", path.to_string_lossy()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_wav_header_and_samples() {
+        let target = std::env::temp_dir().join(format!("qobuz-render-{}.wav", std::process::id()));
+        let written = write_wav(&target, 2, 48_000, [0.5, -0.5, 0.0, 1.0].into_iter()).unwrap();
+        let bytes = fs::read(&target).unwrap();
+        fs::remove_file(&target).unwrap();
+
+        assert_eq!(written, 12);
+        assert_eq!(bytes.len(), 44 + 12);
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(u32::from_le_bytes(bytes[4..8].try_into().unwrap()), 36 + 12);
+        assert_eq!(u16::from_le_bytes(bytes[22..24].try_into().unwrap()), 2);
+        assert_eq!(u32::from_le_bytes(bytes[24..28].try_into().unwrap()), 48_000);
+        assert_eq!(u32::from_le_bytes(bytes[40..44].try_into().unwrap()), 12);
+        assert_eq!(&bytes[44..47], &[0x00, 0x00, 0x40]);
+        assert_eq!(&bytes[53..56], &[0xff, 0xff, 0x7f]);
+    }
+
+    #[test]
+    fn keeps_existing_renders() {
+        let target =
+            std::env::temp_dir().join(format!("qobuz-render-existing-{}.wav", std::process::id()));
+        fs::write(&target, b"earlier render").unwrap();
+        let error = write_wav(&target, 2, 48_000, [0.5, 0.5].into_iter()).unwrap_err();
+        let bytes = fs::read(&target).unwrap();
+        fs::remove_file(&target).unwrap();
+
+        assert_eq!(error.kind(), std::io::ErrorKind::AlreadyExists);
+        assert_eq!(bytes, b"earlier render");
+    }
+
+    #[test]
+    fn rejects_invalid_sections() {
+        assert!(RenderSection::from_secs(Some(f64::INFINITY), None).is_err());
+        assert!(RenderSection::from_secs(Some(1e300), None).is_err());
+        assert!(RenderSection::from_secs(Some(-1.0), None).is_err());
+        assert!(RenderSection::from_secs(None, Some(f64::NAN)).is_err());
+        assert!(RenderSection::from_secs(Some(30.0), Some(10.0)).is_err());
+
+        let section = RenderSection::from_secs(Some(30.0), Some(60.0)).unwrap();
+        assert_eq!(section.start, Duration::from_secs(30));
+        assert_eq!(section.end, Some(Duration::from_secs(60)));
+    }
+}
@@ -0,0 +1,185 @@
+use std::{fmt, str::FromStr, time::Duration};
+
+use crate::controls::Controls;
+
+pub const WEEKDAY_NAMES: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+/// Bit mask with every weekday set.
+pub const EVERY_DAY: u8 = 0b111_1111;
+/// Longest volume ramp a schedule can start with.
+pub const MAX_RAMP_UP_MINUTES: u64 = 24 * 60;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScheduleTarget {
+    Album(String),
+    Playlist(u32),
+}
+
+impl ScheduleTarget {
+    pub fn from_parts(kind: &str, id: &str) -> Option<Self> {
+        match kind {
+            "album" => Some(Self::Album(id.to_string())),
+            "playlist" => id.parse().ok().map(Self::Playlist),
+            _ => None,
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Album(_) => "album",
+            Self::Playlist(_) => "playlist",
+        }
+    }
+
+    pub fn id(&self) -> String {
+        match self {
+            Self::Album(id) => id.clone(),
+            Self::Playlist(id) => id.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ScheduleTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.kind(), self.id())
+    }
+}
+
+/// Time of day in local time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeOfDay {
+    pub hour: u8,
+    pub minute: u8,
+}
+
+impl fmt::Display for TimeOfDay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}", self.hour, self.minute)
+    }
+}
+
+impl FromStr for TimeOfDay {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let error = || format!("Invalid time '{value}'. Use HH:MM");
+        let (hour, minute) = value.trim().split_once(':').ok_or_else(error)?;
+        let hour = hour.parse::<u8>().map_err(|_| error())?;
+        let minute = minute.parse::<u8>().map_err(|_| error())?;
+        if hour > 23 || minute > 59 {
+            return Err(error());
+        }
+        Ok(Self { hour, minute })
+    }
+}
+
+/// Starts an album or playlist at a time of day on some weekdays.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    pub id: i64,
+    pub target: ScheduleTarget,
+    pub time: TimeOfDay,
+    /// Bit 0 is Monday.
+    pub weekdays: u8,
+    /// Raise the volume from silence over this long.
+    pub ramp_up: Option<Duration>,
+}
+
+impl Schedule {
+    /// `weekday` counts from Monday as 0.
+    pub fn is_due(&self, weekday: u8, time: TimeOfDay) -> bool {
+        self.weekdays & (1 << weekday) != 0 && self.time == time
+    }
+
+    pub fn start(&self, controls: &Controls) {
+        // Silence first, so the start of the track is not heard at full volume
+        if let Some(duration) = self.ramp_up {
+            controls.ramp_up_volume(duration);
+        }
+        match &self.target {
+            ScheduleTarget::Album(id) => controls.play_album(id, 0),
+            ScheduleTarget::Playlist(id) => controls.play_playlist(*id, 0, false),
+        }
+    }
+
+    pub fn weekday_names(&self) -> String {
+        if self.weekdays == EVERY_DAY {
+            return "every day".to_string();
+        }
+
+        WEEKDAY_NAMES
+            .iter()
+            .enumerate()
+            .filter(|(day, _)| self.weekdays & (1 << day) != 0)
+            .map(|(_, name)| *name)
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+/// Parses a comma separated list like `mon,tue,sat` into a weekday mask.
+pub fn parse_weekdays(value: &str) -> Result<u8, String> {
+    value
+        .split(',')
+        .map(|day| day.trim().to_lowercase())
+        .filter(|day| !day.is_empty())
+        .try_fold(0, |mask, day| {
+            WEEKDAY_NAMES
+                .iter()
+                .position(|name| day.starts_with(name))
+                .map(|position| mask | 1 << position)
+                .ok_or_else(|| {
+                    format!("Unknown weekday '{day}'. Use mon, tue, wed, thu, fri, sat or sun")
+                })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_times_and_weekdays() {
+        assert_eq!(
+            "07:05".parse::<TimeOfDay>(),
+            Ok(TimeOfDay { hour: 7, minute: 5 })
+        );
+        assert!("24:00".parse::<TimeOfDay>().is_err());
+        assert!("7".parse::<TimeOfDay>().is_err());
+
+        assert_eq!(parse_weekdays("mon, Friday,sun"), Ok(0b100_0001 | 0b1_0000));
+        assert!(parse_weekdays("mon,someday").is_err());
+
+        let schedule = Schedule {
+            id: 1,
+            target: ScheduleTarget::Playlist(42),
+            time: TimeOfDay {
+                hour: 6,
+                minute: 30,
+            },
+            weekdays: parse_weekdays("sat,sun").unwrap(),
+            ramp_up: None,
+        };
+        assert!(schedule.is_due(
+            6,
+            TimeOfDay {
+                hour: 6,
+                minute: 30
+            }
+        ));
+        assert!(!schedule.is_due(
+            0,
+            TimeOfDay {
+                hour: 6,
+                minute: 30
+            }
+        ));
+        assert!(!schedule.is_due(
+            5,
+            TimeOfDay {
+                hour: 6,
+                minute: 31
+            }
+        ));
+        assert_eq!(schedule.weekday_names(), "sat,sun");
+    }
+}
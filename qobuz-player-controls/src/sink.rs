@@ -1,6 +1,9 @@
+use std::collections::HashMap;
 use std::fs;
+use std::io::Read;
 use std::path::Path;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::time::Duration;
 
 use parking_lot::{Mutex, RwLock};
@@ -11,9 +14,16 @@ use tokio::sync::watch::{self, Receiver, Sender};
 use tokio::task::JoinHandle;
 use tokio::time::sleep;
 
+use crate::channel_mix::{ChannelMixSource, UpmixPreset};
+use crate::clap::{ClapChain, ClapChainSource};
+use crate::crossfeed::{CrossfeedSettings, CrossfeedSource};
+use crate::dither::DitherSource;
 use crate::error::Error;
+use crate::jack::list_jack_devices;
+use crate::meter::{Levels, MeterSource};
+use crate::output::{OutputBackend, OutputSampleFormat, OutputStream, open_bit_perfect_stream};
 use crate::stretch_source_signalsmith::SignalsmithStretchSource;
-use crate::{Result, VolumeReceiver};
+use crate::{LevelsReceiver, Result, VolumeReceiver};
 
 #[derive(Clone, Copy)]
 pub struct PlaybackStretchConfig {
@@ -33,37 +43,118 @@ impl Default for PlaybackStretchConfig {
 }
 
 pub struct Sink {
-    output_stream: Option<rodio::OutputStream>,
+    output_backend: OutputBackend,
+    /// Last backend a stream was opened on, so the wav backend appends to its recording.
+    opened_backend: Option<OutputBackend>,
+    output_stream: Option<OutputStream>,
     sink: Option<rodio::Sink>,
     sender: Option<Arc<rodio::queue::SourcesQueueInput>>,
     volume: VolumeReceiver,
+    /// Gain on top of the volume, lowered by the sleep timer.
+    fade: f32,
     playback_stretch: Arc<RwLock<PlaybackStretchConfig>>,
     live_stretch_enabled: bool,
     track_finished: Sender<()>,
     track_handle: Option<JoinHandle<()>>,
     duration_played: Arc<Mutex<Duration>>,
     position_offset_ms: Arc<Mutex<i64>>,
+    /// Where the first track started when queried with `start_at`, until the
+    /// next seek.
+    start_position: Arc<Mutex<Duration>>,
     selected_device_name: Arc<Mutex<Option<String>>>,
+    bit_perfect_enabled: bool,
+    bit_perfect: bool,
+    stream_bit_depth: Option<u32>,
+    output_sample_formats: HashMap<String, OutputSampleFormat>,
+    stream_dither_bits: Option<u32>,
+    /// Whether the queued track reaches the output unchanged apart from the
+    /// volume, so its samples already fit the output bit depth.
+    pass_through: bool,
+    dither_bits: Arc<AtomicU32>,
+    upmix_preset: UpmixPreset,
+    crossfeed: CrossfeedSettings,
+    clap_chain: ClapChain,
+    levels: watch::Sender<Levels>,
 }
 
 impl Sink {
-    pub fn new(volume: VolumeReceiver, playback_stretch: Arc<RwLock<PlaybackStretchConfig>>) -> Result<Self> {
+    pub fn new(
+        volume: VolumeReceiver,
+        playback_stretch: Arc<RwLock<PlaybackStretchConfig>>,
+        output_backend: OutputBackend,
+    ) -> Result<Self> {
         let (track_finished, _) = watch::channel(());
         Ok(Self {
+            output_backend,
+            opened_backend: None,
             sink: Default::default(),
             output_stream: Default::default(),
             sender: Default::default(),
             volume,
+            fade: 1.0,
             playback_stretch,
             live_stretch_enabled: false,
             track_finished,
             track_handle: Default::default(),
             duration_played: Default::default(),
             position_offset_ms: Arc::new(Mutex::new(0)),
+            start_position: Default::default(),
             selected_device_name: Arc::new(Mutex::new(None)),
+            bit_perfect_enabled: false,
+            bit_perfect: false,
+            stream_bit_depth: None,
+            output_sample_formats: Default::default(),
+            stream_dither_bits: None,
+            pass_through: false,
+            dither_bits: Default::default(),
+            upmix_preset: Default::default(),
+            crossfeed: Default::default(),
+            clap_chain: Default::default(),
+            levels: watch::Sender::new(Default::default()),
         })
     }
 
+    pub fn levels(&self) -> LevelsReceiver {
+        self.levels.subscribe()
+    }
+
+    pub fn clap_chain(&self) -> &ClapChain {
+        &self.clap_chain
+    }
+
+    pub fn set_crossfeed(&mut self, crossfeed: CrossfeedSettings) {
+        self.crossfeed = crossfeed;
+    }
+
+    pub fn set_upmix_preset(&mut self, preset: UpmixPreset) {
+        self.upmix_preset = preset;
+    }
+
+    pub fn set_output_sample_formats(&mut self, formats: HashMap<String, OutputSampleFormat>) {
+        self.output_sample_formats = formats;
+    }
+
+    pub fn set_bit_perfect(&mut self, enabled: bool) {
+        self.bit_perfect_enabled = enabled;
+    }
+
+    /// Whether samples reach the device unchanged, which needs the stream
+    /// opened at the track's format and no software gain.
+    pub fn is_bit_perfect(&self) -> bool {
+        self.bit_perfect && self.sink.is_some() && self.output_volume() == 1.0
+    }
+
+    fn output_volume(&self) -> f32 {
+        *self.volume.borrow() * self.fade
+    }
+
+    pub fn set_fade(&mut self, fade: f32) {
+        if self.fade != fade {
+            self.fade = fade;
+            self.sync_volume();
+        }
+    }
+
     pub fn set_device(&self, device_name: Option<String>) {
         tracing::info!("Setting audio device to: {:?}", device_name);
         *self.selected_device_name.lock() = device_name;
@@ -90,7 +181,7 @@ impl Sink {
             return Default::default();
         }
 
-        let raw = position - duration_played;
+        let raw = position - duration_played + *self.start_position.lock();
         let raw_ms = raw.as_millis() as i64;
         let offset_ms = *self.position_offset_ms.lock();
         Duration::from_millis(raw_ms.saturating_add(offset_ms).max(0) as u64)
@@ -98,6 +189,7 @@ impl Sink {
 
     fn reset_position_adjustment(&self) {
         *self.position_offset_ms.lock() = 0;
+        *self.start_position.lock() = Default::default();
     }
 
     pub fn adjust_position_offset_ms(&self, delta_ms: i64) {
@@ -139,6 +231,7 @@ impl Sink {
         self.sink = None;
         self.sender = None;
         self.output_stream = None;
+        self.levels.send_replace(Default::default());
         *self.duration_played.lock() = Default::default();
         self.reset_position_adjustment();
 
@@ -165,7 +258,7 @@ impl Sink {
     }
 
     pub fn supports_live_stretch(&self) -> bool {
-        self.live_stretch_enabled
+        self.live_stretch_enabled && !self.bit_perfect_enabled
     }
 
     pub fn query_track(
@@ -184,38 +277,51 @@ impl Sink {
             .build()?;
 
         let sample_rate = decoded.sample_rate();
-        #[allow(unused_variables)]
         let channels = decoded.channels();
-        self.live_stretch_enabled = channels == 2;
-        let (mut source, track_duration_override): (
-            Box<dyn rodio::Source<Item = f32> + Send>,
-            Option<Duration>,
-        ) = {
-            if channels == 2 {
-                let stretch_source =
-                    SignalsmithStretchSource::new(decoded, sample_rate, self.playback_stretch.clone());
-                (Box::new(stretch_source), None)
-            } else {
-                (box_source_f32(decoded), None)
-            }
-        };
+        let current_device = self.selected_device_name.lock().clone();
+        let output_backend = self.output_backend_for(current_device.as_deref());
+        let crossfeed = output_backend == OutputBackend::Cpal
+            && self.crossfeed_enabled_for(current_device.as_deref());
+        let bit_perfect_depth = self
+            .bit_perfect_depth(track_path, &output_backend)
+            .filter(|_| !crossfeed && self.clap_chain.is_empty());
+        let sample_format = self
+            .output_sample_formats
+            .get(current_device.as_deref().unwrap_or_default())
+            .copied();
+        self.live_stretch_enabled = channels == 2 && bit_perfect_depth.is_none();
         let same_sample_rate = self
             .output_stream
             .as_ref()
-            .map(|stream| stream.config().sample_rate() == sample_rate)
+            .map(|stream| {
+                (stream.has_fixed_rate() || stream.sample_rate() == sample_rate)
+                    && (bit_perfect_depth.is_none() || self.stream_bit_depth == bit_perfect_depth)
+            })
             .unwrap_or(true);
 
         if !same_sample_rate {
             return Ok(QueryTrackResult::RecreateStreamRequired);
         }
 
-        let current_device = self.selected_device_name.lock().clone();
+        // Built once the stream is known to stay, since it activates plugins
+        let (source, track_duration_override): (
+            Box<dyn rodio::Source<Item = f32> + Send>,
+            Option<Duration>,
+        ) = {
+            let playback_stretch = self
+                .live_stretch_enabled
+                .then(|| self.playback_stretch.clone());
+            (effect_source(decoded, playback_stretch, &self.clap_chain), None)
+        };
+
         let needs_stream = self.output_stream.is_none() 
             || self.sink.is_none() 
             || self.sender.is_none();
 
         if needs_stream {
-            let device_to_use = if current_device.is_none() {
+            let device_to_use = if output_backend != OutputBackend::Cpal {
+                None
+            } else if current_device.is_none() {
                 tracing::info!("Default device selected, resolving to system default");
                 match get_default_device_name() {
                     Ok(Some(default_name)) => {
@@ -235,41 +341,44 @@ impl Sink {
                 current_device
             };
             
-            tracing::info!("Creating audio stream with device: {:?}", device_to_use);
-            match open_stream_with_device(sample_rate, device_to_use.as_deref()) {
-                Ok(mut stream_handle) => {
-                    stream_handle.log_on_drop(false);
-
-                    let (sender, receiver) = queue(true);
-                    let sink = rodio::Sink::connect_new(stream_handle.mixer());
-                    sink.append(receiver);
-                    set_volume(&sink, &self.volume.borrow());
-
-                    self.sink = Some(sink);
-                    self.sender = Some(sender);
-                    self.output_stream = Some(stream_handle);
-                }
+            tracing::info!("Creating {:?} audio stream with device: {:?}", output_backend, device_to_use);
+            let opened = match bit_perfect_depth {
+                Some(bit_depth) => open_bit_perfect_stream(
+                    sample_rate,
+                    channels,
+                    bit_depth,
+                    device_to_use.as_deref(),
+                ),
+                None => output_backend.open(
+                    sample_rate,
+                    device_to_use.as_deref(),
+                    sample_format,
+                    self.opened_backend.as_ref() == Some(&output_backend),
+                ),
+            };
+            if opened.is_ok() && bit_perfect_depth.is_none() {
+                self.opened_backend = Some(output_backend.clone());
+            }
+            self.stream_bit_depth = bit_perfect_depth;
+            match opened {
+                Ok(stream) => self.connect_stream(stream, sample_format),
                 Err(e) => {
                     let error_msg = e.to_string();
                     if error_msg.contains("device") || error_msg.contains("no longer available") {
                         tracing::warn!("Selected device unavailable, trying default device");
                         *self.selected_device_name.lock() = None;
+                        self.stream_bit_depth = None;
                         let default_device = get_default_device_name()
                             .ok()
                             .flatten();
-                        match open_stream_with_device(sample_rate, default_device.as_deref()) {
-                            Ok(mut stream_handle) => {
-                                stream_handle.log_on_drop(false);
-
-                                let (sender, receiver) = queue(true);
-                                let sink = rodio::Sink::connect_new(stream_handle.mixer());
-                                sink.append(receiver);
-                                set_volume(&sink, &self.volume.borrow());
-
-                                self.sink = Some(sink);
-                                self.sender = Some(sender);
-                                self.output_stream = Some(stream_handle);
-                            }
+                        let sample_format = self.output_sample_formats.get("").copied();
+                        match OutputBackend::Cpal.open(
+                            sample_rate,
+                            default_device.as_deref(),
+                            sample_format,
+                            false,
+                        ) {
+                            Ok(stream) => self.connect_stream(stream, sample_format),
                             Err(fallback_err) => {
                                 tracing::error!("Failed to open default device as fallback: {}", fallback_err);
                                 return Err(e);
@@ -282,9 +391,45 @@ impl Sink {
             }
         }
 
+        self.bit_perfect = bit_perfect_depth.is_some() && self.stream_bit_depth == bit_perfect_depth;
+
+        let output_channels = self
+            .output_stream
+            .as_ref()
+            .map(|stream| stream.channels())
+            .unwrap_or_else(|| source.channels());
+        let crossfeed = crossfeed && output_channels == 2;
+        // Stretching, plugins, mixing channels, crossfeed and resampling all
+        // produce samples between the output steps
+        self.pass_through = !self.live_stretch_enabled
+            && (channels != 2 || self.clap_chain.is_empty())
+            && output_channels == channels
+            && !crossfeed
+            && self
+                .output_stream
+                .as_ref()
+                .is_some_and(|stream| stream.sample_rate() == sample_rate)
+            && flac_bit_depth(track_path)
+                .zip(self.stream_dither_bits)
+                .is_some_and(|(track_bits, output_bits)| track_bits <= output_bits);
+        self.sync_volume();
+
+        let source = ChannelMixSource::new(source, output_channels, self.upmix_preset);
+        let mut source: Box<dyn rodio::Source<Item = f32> + Send> = if crossfeed {
+            Box::new(CrossfeedSource::new(
+                source,
+                self.crossfeed.preset,
+                self.crossfeed.strength,
+            ))
+        } else {
+            Box::new(source)
+        };
+
         if let Some(pos) = start_at {
             if pos > Duration::ZERO {
                 source.try_seek(pos)?;
+                // The sink counts from where the source is appended
+                *self.start_position.lock() = pos;
             }
         }
 
@@ -294,12 +439,14 @@ impl Sink {
 
         let duration_played = self.duration_played.clone();
         let position_offset_ms = self.position_offset_ms.clone();
+        let start_position = self.start_position.clone();
         let signal = self.sender.as_ref().unwrap().append_with_signal(source);
 
         let track_handle = tokio::spawn(async move {
             loop {
                 if signal.try_recv().is_ok() {
-                    *duration_played.lock() += track_duration;
+                    let start_position = std::mem::take(&mut *start_position.lock());
+                    *duration_played.lock() += track_duration.saturating_sub(start_position);
                     *position_offset_ms.lock() = 0;
                     track_finished.send(()).expect("infallible");
                     break;
@@ -313,10 +460,120 @@ impl Sink {
         Ok(QueryTrackResult::Queued)
     }
 
+    fn bit_perfect_depth(&self, track_path: &Path, output_backend: &OutputBackend) -> Option<u32> {
+        let stretch = *self.playback_stretch.read();
+        let neutral = stretch.time_stretch_ratio == 1.0
+            && stretch.pitch_semitones == 0
+            && stretch.pitch_cents == 0
+            && *self.volume.borrow() == 1.0
+            && self.clap_chain.is_empty();
+
+        if !self.bit_perfect_enabled || !neutral || *output_backend != OutputBackend::Cpal {
+            return None;
+        }
+
+        flac_bit_depth(track_path)
+    }
+
+    fn crossfeed_enabled_for(&self, device_name: Option<&str>) -> bool {
+        let device_name = match device_name {
+            Some(device_name) => Some(device_name.to_string()),
+            None => get_default_device_name().ok().flatten(),
+        };
+
+        device_name.is_some_and(|device_name| self.crossfeed.enabled_for(&device_name))
+    }
+
+    fn output_backend_for(&self, device_name: Option<&str>) -> OutputBackend {
+        if self.output_backend != OutputBackend::Cpal {
+            return self.output_backend.clone();
+        }
+
+        // Headless backends are only chosen with `--output`, never by a
+        // stored device name
+        device_name
+            .and_then(OutputBackend::from_device_name)
+            .filter(|backend| !backend.is_headless())
+            .unwrap_or_default()
+    }
+
+    fn connect_stream(&mut self, stream: OutputStream, sample_format: Option<OutputSampleFormat>) {
+        let (sender, receiver) = queue(true);
+        let (mixer, mixer_source) = rodio::mixer::mixer(stream.channels(), stream.sample_rate());
+        let sink = rodio::Sink::connect_new(&mixer);
+        sink.append(receiver);
+        set_volume(&sink, self.output_volume());
+        stream
+            .mixer()
+            .add(DitherSource::new(
+                MeterSource::new(mixer_source, self.levels.clone()),
+                self.dither_bits.clone(),
+            ));
+
+        self.stream_dither_bits = stream.dither_bits(sample_format);
+        self.sink = Some(sink);
+        self.sender = Some(sender);
+        self.output_stream = Some(stream);
+        self.sync_dither();
+    }
+
     pub fn sync_volume(&self) {
         if let Some(sink) = &self.sink {
-            set_volume(sink, &self.volume.borrow());
+            set_volume(sink, self.output_volume());
         }
+        self.sync_dither();
+    }
+
+    fn sync_dither(&self) {
+        let bits = if self.is_bit_perfect() || (self.pass_through && self.output_volume() == 1.0) {
+            0
+        } else {
+            self.stream_dither_bits.unwrap_or(0)
+        };
+        self.dither_bits.store(bits, Ordering::Relaxed);
+    }
+}
+
+fn flac_bit_depth(track_path: &Path) -> Option<u32> {
+    let mut header = [0; 22];
+    fs::File::open(track_path)
+        .ok()?
+        .read_exact(&mut header)
+        .ok()?;
+
+    if !header.starts_with(b"fLaC") || header[4] & 0x7f != 0 {
+        return None;
+    }
+
+    let stream_info = &header[8..];
+    Some((((stream_info[12] & 0x01) as u32) << 4 | (stream_info[13] >> 4) as u32) + 1)
+}
+
+/// The decoded track through tempo, pitch and the plugin chain, before it
+/// is adapted to an output.
+pub(crate) fn effect_source<S>(
+    decoded: S,
+    playback_stretch: Option<Arc<RwLock<PlaybackStretchConfig>>>,
+    clap_chain: &ClapChain,
+) -> Box<dyn rodio::Source<Item = f32> + Send>
+where
+    S: rodio::Source<Item = f32> + Send + 'static,
+{
+    let sample_rate = decoded.sample_rate();
+    let stereo = decoded.channels() == 2;
+    let source = match playback_stretch {
+        Some(playback_stretch) => box_source_f32(SignalsmithStretchSource::new(
+            decoded,
+            sample_rate,
+            playback_stretch,
+        )),
+        None => box_source_f32(decoded),
+    };
+
+    if stereo && !clap_chain.is_empty() {
+        box_source_f32(ClapChainSource::new(source, clap_chain))
+    } else {
+        source
     }
 }
 
@@ -327,80 +584,11 @@ where
     Box::new(source)
 }
 
-fn set_volume(sink: &rodio::Sink, volume: &f32) {
+fn set_volume(sink: &rodio::Sink, volume: f32) {
     let volume = volume.clamp(0.0, 1.0).powi(3);
     sink.set_volume(volume);
 }
 
-#[allow(dead_code)]
-fn open_default_stream(sample_rate: u32) -> Result<rodio::OutputStream> {
-    open_stream_with_device(sample_rate, None)
-}
-
-fn open_stream_with_device(sample_rate: u32, device_name: Option<&str>) -> Result<rodio::OutputStream> {
-    tracing::info!("Opening audio stream with device: {:?}", device_name);
-    
-    let host = rodio::cpal::default_host();
-    
-    if let Some(device_name) = device_name {
-        tracing::info!("Looking for device: {}", device_name);
-        let devices = host.output_devices().map_err(|e| {
-            tracing::error!("Failed to enumerate output devices: {}", e);
-            Error::StreamError {
-                message: format!("Failed to enumerate output devices: {}", e),
-            }
-        })?;
-        
-        for device in devices {
-            let name = device.name().unwrap_or_else(|_| "Unknown".to_string());
-            tracing::debug!("Found device: {}", name);
-            
-            if name == device_name {
-                tracing::info!("Using selected device: {}", name);
-                return rodio::OutputStreamBuilder::from_device(device)
-                    .and_then(|x| x.with_sample_rate(sample_rate).open_stream_or_fallback())
-                    .map_err(|e| {
-                        tracing::error!("Failed to open selected device {}: {}", device_name, e);
-                        Error::StreamError {
-                            message: format!("Failed to open device {}: {}", device_name, e),
-                        }
-                    });
-            }
-        }
-        
-        tracing::warn!("Selected device '{}' not found, falling back to default", device_name);
-    }
-    
-    rodio::OutputStreamBuilder::from_default_device()
-        .and_then(|x| x.with_sample_rate(sample_rate).open_stream())
-        .or_else(|original_err| {
-            tracing::warn!("Failed to open default device, trying any available device");
-            let mut devices = match host.output_devices() {
-                Ok(devices) => devices,
-                Err(e) => {
-                    tracing::error!("Failed to enumerate output devices: {}", e);
-                    return Err(original_err);
-                }
-            };
-
-            devices
-                .find_map(|d| {
-                    let name = d.name().unwrap_or_else(|_| "Unknown".to_string());
-                    tracing::debug!("Trying device: {}", name);
-                    rodio::OutputStreamBuilder::from_device(d)
-                        .and_then(|x| x.with_sample_rate(sample_rate).open_stream_or_fallback())
-                        .ok()
-                })
-                .ok_or(original_err)
-        })
-        .map_err(|e: rodio::StreamError| {
-            tracing::error!("Failed to open any audio device: {}", e);
-            Error::StreamError {
-                message: format!("Failed to open audio device: {}", e),
-            }
-        })
-}
-
 pub fn list_audio_devices() -> Result<Vec<AudioDevice>> {
     tracing::info!("Listing available audio devices");
     let host = rodio::cpal::default_host();
@@ -417,6 +605,12 @@ pub fn list_audio_devices() -> Result<Vec<AudioDevice>> {
         tracing::debug!("Found audio device: {}", name);
         device_list.push(AudioDevice { name });
     }
+
+    device_list.extend(
+        list_jack_devices()
+            .into_iter()
+            .map(|name| AudioDevice { name }),
+    );
     
     tracing::info!("Found {} audio device(s)", device_list.len());
     Ok(device_list)
@@ -453,3 +647,89 @@ impl Drop for Sink {
         self.clear().unwrap();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 0.3 s of a constant mono 16-bit signal at a quarter of full scale.
+    const FLAC_8KHZ: &[u8] = include_bytes!("../tests/data/mono-8khz.flac");
+    const FLAC_16KHZ: &[u8] = include_bytes!("../tests/data/mono-16khz.flac");
+
+    async fn play_to_end(sink: &mut Sink, path: &Path) {
+        let mut track_finished = sink.track_finished();
+        track_finished.borrow_and_update();
+
+        assert!(matches!(
+            sink.query_track(path, None).unwrap(),
+            QueryTrackResult::Queued
+        ));
+        sink.play().unwrap();
+
+        tokio::time::timeout(Duration::from_secs(5), track_finished.changed())
+            .await
+            .expect("track finishes")
+            .unwrap();
+    }
+
+    fn test_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("qobuz-player-{name}-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn null_backend_plays_a_track_to_the_end() {
+        let dir = test_dir("sink-null");
+        let track = dir.join("track.flac");
+        fs::write(&track, FLAC_8KHZ).unwrap();
+
+        let (_volume, volume_receiver) = watch::channel(1.0);
+        let mut sink = Sink::new(volume_receiver, Default::default(), OutputBackend::Null).unwrap();
+        let started = std::time::Instant::now();
+        play_to_end(&mut sink, &track).await;
+        let elapsed = started.elapsed();
+        drop(sink);
+        fs::remove_dir_all(&dir).unwrap();
+
+        // The null backend consumes the track at playback speed
+        assert!(elapsed >= Duration::from_millis(250), "{elapsed:?}");
+    }
+
+    #[tokio::test]
+    async fn wav_backend_keeps_recording_across_streams() {
+        let dir = test_dir("sink-wav");
+        let first = dir.join("first.flac");
+        let second = dir.join("second.flac");
+        let recording = dir.join("out.wav");
+        fs::write(&first, FLAC_8KHZ).unwrap();
+        fs::write(&second, FLAC_16KHZ).unwrap();
+
+        let (_volume, volume_receiver) = watch::channel(1.0);
+        let mut sink = Sink::new(
+            volume_receiver,
+            Default::default(),
+            OutputBackend::Wav(recording.clone()),
+        )
+        .unwrap();
+
+        play_to_end(&mut sink, &first).await;
+        // Skipping drops the stream, which used to truncate the recording
+        sink.clear().unwrap();
+        play_to_end(&mut sink, &second).await;
+        drop(sink);
+
+        let data = fs::read(&recording).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(u32::from_le_bytes(data[24..28].try_into().unwrap()), 8_000);
+        let data_len = u32::from_le_bytes(data[40..44].try_into().unwrap()) as usize;
+        assert_eq!(data_len, data.len() - 44);
+
+        let audible_frames = data[44..]
+            .chunks_exact(8)
+            .filter(|frame| f32::from_le_bytes(frame[0..4].try_into().unwrap()).abs() > 0.01)
+            .count();
+        assert!(audible_frames >= 2 * 2_300, "{audible_frames}");
+    }
+}
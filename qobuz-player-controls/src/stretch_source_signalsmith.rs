@@ -189,7 +189,7 @@ where
     }
 }
 
-fn normalize_ratio(ratio: f32) -> f32 {
+pub(crate) fn normalize_ratio(ratio: f32) -> f32 {
     if ratio.is_finite() {
         ratio.clamp(0.5, 2.0)
     } else {
@@ -0,0 +1,280 @@
+use std::{fs, io, path::Path};
+
+use id3::{
+    Tag, TagLike, Version,
+    frame::{Picture, PictureType},
+};
+
+const FLAC_MARKER: &[u8] = b"fLaC";
+const FLAC_STREAMINFO: u8 = 0;
+const FLAC_PADDING: u8 = 1;
+const FLAC_VORBIS_COMMENT: u8 = 4;
+const FLAC_PICTURE: u8 = 6;
+const FLAC_MAX_BLOCK_LEN: usize = (1 << 24) - 1;
+const FRONT_COVER: u8 = 3;
+
+#[derive(Debug, Default, Clone)]
+pub struct TrackTags {
+    pub title: String,
+    pub artist: Option<String>,
+    pub album_artist: Option<String>,
+    pub album: Option<String>,
+    pub track_number: u32,
+    pub year: Option<u32>,
+    pub isrc: Option<String>,
+    pub cover: Option<Vec<u8>>,
+}
+
+/// Whether [`write_tags`] supports the file type.
+pub fn is_taggable(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == "flac" || extension == "mp3")
+}
+
+pub fn write_tags(path: &Path, tags: &TrackTags) -> io::Result<()> {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("flac") => {
+            let data = fs::read(path)?;
+            fs::write(path, tag_flac(&data, tags)?)
+        }
+        Some("mp3") => tag_mp3(path, tags),
+        _ => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Tagging is only supported for flac and mp3 files",
+        )),
+    }
+}
+
+fn tag_flac(data: &[u8], tags: &TrackTags) -> io::Result<Vec<u8>> {
+    if !data.starts_with(FLAC_MARKER) {
+        return Err(invalid_data("Not a flac file"));
+    }
+
+    let mut offset = FLAC_MARKER.len();
+    let mut blocks: Vec<(u8, &[u8])> = vec![];
+
+    loop {
+        let header = data
+            .get(offset..offset + 4)
+            .ok_or_else(|| invalid_data("Truncated flac metadata"))?;
+        let is_last = header[0] & 0x80 != 0;
+        let block_type = header[0] & 0x7f;
+        let length = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+        let body = data
+            .get(offset + 4..offset + 4 + length)
+            .ok_or_else(|| invalid_data("Truncated flac metadata"))?;
+
+        if !matches!(
+            block_type,
+            FLAC_PADDING | FLAC_VORBIS_COMMENT | FLAC_PICTURE
+        ) {
+            blocks.push((block_type, body));
+        }
+
+        offset += 4 + length;
+        if is_last {
+            break;
+        }
+    }
+
+    if blocks.first().map(|block| block.0) != Some(FLAC_STREAMINFO) {
+        return Err(invalid_data("Missing flac stream info"));
+    }
+
+    let vorbis_comment = vorbis_comment(tags);
+    blocks.push((FLAC_VORBIS_COMMENT, &vorbis_comment));
+
+    let picture = tags.cover.as_deref().map(flac_picture);
+    if let Some(picture) = &picture
+        && picture.len() <= FLAC_MAX_BLOCK_LEN
+    {
+        blocks.push((FLAC_PICTURE, picture));
+    }
+
+    let mut out = Vec::with_capacity(data.len() + vorbis_comment.len());
+    out.extend_from_slice(FLAC_MARKER);
+
+    let last_index = blocks.len() - 1;
+    for (index, (block_type, body)) in blocks.into_iter().enumerate() {
+        let last_flag = if index == last_index { 0x80 } else { 0 };
+        out.push(block_type | last_flag);
+        out.extend_from_slice(&(body.len() as u32).to_be_bytes()[1..]);
+        out.extend_from_slice(body);
+    }
+
+    out.extend_from_slice(&data[offset..]);
+    Ok(out)
+}
+
+fn vorbis_comment(tags: &TrackTags) -> Vec<u8> {
+    let vendor = b"qobuz-player";
+    let comments = text_fields(tags)
+        .into_iter()
+        .map(|(key, value)| format!("{key}={value}"))
+        .collect::<Vec<_>>();
+
+    let mut out = vec![];
+    out.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+    out.extend_from_slice(vendor);
+    out.extend_from_slice(&(comments.len() as u32).to_le_bytes());
+    for comment in comments {
+        out.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+        out.extend_from_slice(comment.as_bytes());
+    }
+    out
+}
+
+fn flac_picture(image: &[u8]) -> Vec<u8> {
+    let mime = image_mime_type(image);
+
+    let mut out = vec![];
+    out.extend_from_slice(&(FRONT_COVER as u32).to_be_bytes());
+    out.extend_from_slice(&(mime.len() as u32).to_be_bytes());
+    out.extend_from_slice(mime.as_bytes());
+    // Empty description, then width, height, color depth and palette size left unknown
+    out.extend_from_slice(&[0; 4 * 5]);
+    out.extend_from_slice(&(image.len() as u32).to_be_bytes());
+    out.extend_from_slice(image);
+    out
+}
+
+fn tag_mp3(path: &Path, tags: &TrackTags) -> io::Result<()> {
+    let mut tag = Tag::new();
+    tag.set_title(&tags.title);
+    if let Some(artist) = &tags.artist {
+        tag.set_artist(artist);
+    }
+    if let Some(album_artist) = &tags.album_artist {
+        tag.set_album_artist(album_artist);
+    }
+    if let Some(album) = &tags.album {
+        tag.set_album(album);
+    }
+    tag.set_track(tags.track_number);
+    if let Some(year) = tags.year {
+        tag.set_year(year as i32);
+    }
+    if let Some(isrc) = &tags.isrc {
+        tag.set_text("TSRC", isrc);
+    }
+    if let Some(cover) = &tags.cover {
+        tag.add_frame(Picture {
+            mime_type: image_mime_type(cover).to_string(),
+            picture_type: PictureType::CoverFront,
+            description: String::new(),
+            data: cover.clone(),
+        });
+    }
+
+    tag.write_to_path(path, Version::Id3v24)
+        .map_err(io::Error::other)
+}
+
+fn text_fields(tags: &TrackTags) -> Vec<(&'static str, String)> {
+    let mut fields = vec![("TITLE", tags.title.clone())];
+
+    if let Some(artist) = &tags.artist {
+        fields.push(("ARTIST", artist.clone()));
+    }
+    if let Some(album_artist) = &tags.album_artist {
+        fields.push(("ALBUMARTIST", album_artist.clone()));
+    }
+    if let Some(album) = &tags.album {
+        fields.push(("ALBUM", album.clone()));
+    }
+    fields.push(("TRACKNUMBER", tags.track_number.to_string()));
+    if let Some(year) = tags.year {
+        fields.push(("DATE", year.to_string()));
+    }
+    if let Some(isrc) = &tags.isrc {
+        fields.push(("ISRC", isrc.clone()));
+    }
+
+    fields
+}
+
+fn image_mime_type(image: &[u8]) -> &'static str {
+    if image.starts_with(b"\x89PNG") {
+        "image/png"
+    } else {
+        "image/jpeg"
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flac_tags_replace_existing_comments() {
+        let audio = [0xff, 0xf8, 0x01, 0x02];
+        let mut data = FLAC_MARKER.to_vec();
+        data.push(FLAC_STREAMINFO);
+        data.extend_from_slice(&[0, 0, 34]);
+        data.extend_from_slice(&[0; 34]);
+        data.push(FLAC_VORBIS_COMMENT | 0x80);
+        data.extend_from_slice(&[0, 0, 2]);
+        data.extend_from_slice(b"xx");
+        data.extend_from_slice(&audio);
+
+        let tags = TrackTags {
+            title: "Title".into(),
+            isrc: Some("USRC17607839".into()),
+            track_number: 3,
+            cover: Some(vec![0xff, 0xd8, 0xff]),
+            ..Default::default()
+        };
+
+        let tagged = tag_flac(&data, &tags).unwrap();
+
+        assert!(tagged.ends_with(&audio));
+        assert_eq!(tagged[4], FLAC_STREAMINFO);
+        assert_eq!(tagged[4 + 4 + 34], FLAC_VORBIS_COMMENT);
+
+        let text = String::from_utf8_lossy(&tagged);
+        assert!(text.contains("TITLE=Title"));
+        assert!(text.contains("TRACKNUMBER=3"));
+        assert!(text.contains("ISRC=USRC17607839"));
+        assert!(text.contains("image/jpeg"));
+        assert!(!text.contains("xx"));
+
+        let retagged = tag_flac(&tagged, &tags).unwrap();
+        assert_eq!(retagged, tagged);
+    }
+
+    #[test]
+    fn mp3_tags_replace_existing_tag() {
+        let dir = std::env::temp_dir().join(format!("qobuz-player-tags-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("track.mp3");
+        let audio = [0xff, 0xfb, 0x90, 0x00];
+        fs::write(&path, audio).unwrap();
+
+        let mut tags = TrackTags {
+            title: "First".into(),
+            track_number: 3,
+            isrc: Some("USRC17607839".into()),
+            ..Default::default()
+        };
+        write_tags(&path, &tags).unwrap();
+        tags.title = "Second".into();
+        write_tags(&path, &tags).unwrap();
+
+        let tag = Tag::read_from_path(&path).unwrap();
+        let data = fs::read(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(tag.title(), Some("Second"));
+        assert_eq!(tag.track(), Some(3));
+        assert_eq!(
+            tag.get("TSRC").and_then(|frame| frame.content().text()),
+            Some("USRC17607839")
+        );
+        assert!(data.ends_with(&audio));
+        assert!(!is_taggable(Path::new("track.mp4")));
+    }
+}
@@ -1,6 +1,7 @@
-use std::ops::Index;
+use std::{ops::Index, str::FromStr};
 
 use qobuz_player_models::{Track, TrackStatus};
+use rand::seq::SliceRandom;
 
 #[derive(Default, Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
 pub struct AlbumTracklist {
@@ -16,6 +17,13 @@ pub struct PlaylistTracklist {
     pub image: Option<String>,
 }
 
+#[derive(Default, Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct LocalPlaylistTracklist {
+    pub title: String,
+    pub id: i64,
+    pub image: Option<String>,
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
 pub struct TopTracklist {
     pub artist_name: String,
@@ -34,16 +42,59 @@ pub struct SingleTracklist {
 pub enum TracklistType {
     Album(AlbumTracklist),
     Playlist(PlaylistTracklist),
+    LocalPlaylist(LocalPlaylistTracklist),
     TopTracks(TopTracklist),
     Track(SingleTracklist),
     #[default]
     None,
 }
 
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum RepeatMode {
+    #[default]
+    Off,
+    /// Play the current track again when it finishes.
+    Track,
+    /// Start over from the first track after the last one.
+    Queue,
+}
+
+impl RepeatMode {
+    pub const ALL: [Self; 3] = [Self::Off, Self::Track, Self::Queue];
+}
+
+impl std::fmt::Display for RepeatMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Off => "off",
+            Self::Track => "track",
+            Self::Queue => "queue",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for RepeatMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|mode| mode.to_string() == value)
+            .ok_or_else(|| format!("Unknown repeat mode '{value}'. Use off, track or queue"))
+    }
+}
+
 #[derive(Default, Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
 pub struct Tracklist {
     pub queue: Vec<Track>,
     pub list_type: TracklistType,
+    /// Queue order from before shuffling, `None` while not shuffled.
+    #[serde(default)]
+    pub unshuffled: Option<Vec<Track>>,
+    /// Queue position to pause after, cleared once reached.
+    #[serde(default)]
+    pub stop_after: Option<usize>,
 }
 
 pub struct Entity {
@@ -86,6 +137,7 @@ impl Tracklist {
     }
 
     pub fn reset(&mut self) {
+        self.stop_after = None;
         for track in self.queue.iter_mut() {
             if track.status == TrackStatus::Played || track.status == TrackStatus::Playing {
                 track.status = TrackStatus::Unplayed;
@@ -101,14 +153,138 @@ impl Tracklist {
         }
     }
 
-    pub fn next_track(&self) -> Option<&Track> {
-        let current_position = self.current_position();
-        let next_position = current_position + 1;
-        if self.total() <= next_position {
+    /// Position to play after the current track, `None` when playback ends.
+    pub fn next_position(&self, repeat_mode: RepeatMode) -> Option<usize> {
+        if self.queue.is_empty() {
             return None;
         }
 
-        Some(self.queue.index(next_position))
+        let current_position = self.current_position();
+        match repeat_mode {
+            RepeatMode::Off => {
+                (current_position + 1 < self.total()).then_some(current_position + 1)
+            }
+            RepeatMode::Track => Some(current_position),
+            RepeatMode::Queue => Some((current_position + 1) % self.total()),
+        }
+    }
+
+    pub fn next_track(&self, repeat_mode: RepeatMode) -> Option<&Track> {
+        self.next_position(repeat_mode)
+            .map(|next_position| self.queue.index(next_position))
+    }
+
+    pub fn stops_after_current(&self) -> bool {
+        self.stop_after == Some(self.current_position())
+    }
+
+    /// Removes the track at `index`, keeping `stop_after` on the same track.
+    pub fn remove(&mut self, index: usize) {
+        self.queue.remove(index);
+        self.stop_after = match self.stop_after {
+            Some(position) if position == index => None,
+            Some(position) if position > index => Some(position - 1),
+            stop_after => stop_after,
+        };
+    }
+
+    /// Inserts a track at `index`, keeping `stop_after` on the same track.
+    pub fn insert(&mut self, index: usize, track: Track) {
+        self.queue.insert(index, track);
+        self.stop_after = self.stop_after.map(|position| {
+            if position >= index {
+                position + 1
+            } else {
+                position
+            }
+        });
+    }
+
+    /// Reorders the queue, `new_order` lists old positions in their new order.
+    pub fn reorder(&mut self, new_order: &[usize]) {
+        self.queue = new_order.iter().map(|&i| self.queue[i].clone()).collect();
+        self.stop_after = self
+            .stop_after
+            .and_then(|position| new_order.iter().position(|&i| i == position));
+    }
+
+    /// Adds tracks to the end of the queue, left to play.
+    pub fn append(&mut self, tracks: Vec<Track>) {
+        self.queue.extend(tracks.into_iter().map(|mut track| {
+            if track.status != TrackStatus::Unplayable {
+                track.status = TrackStatus::Unplayed;
+            }
+            track
+        }));
+    }
+
+    pub fn is_shuffled(&self) -> bool {
+        self.unshuffled.is_some()
+    }
+
+    /// Shuffles the queue in place. The current track moves to the front and
+    /// keeps playing, every other track is left to play.
+    pub fn shuffle(&mut self) {
+        if self.is_shuffled() {
+            return;
+        }
+        self.unshuffled = Some(self.queue.clone());
+        let stop_track_id = self.stop_track_id();
+
+        let current = self
+            .queue
+            .iter()
+            .position(|track| track.status == TrackStatus::Playing)
+            .map(|position| self.queue.remove(position));
+
+        self.queue.shuffle(&mut rand::rng());
+        for track in self.queue.iter_mut() {
+            if track.status == TrackStatus::Played {
+                track.status = TrackStatus::Unplayed;
+            }
+        }
+
+        if let Some(current) = current {
+            self.queue.insert(0, current);
+        }
+        self.stop_after = self.position_of(stop_track_id);
+    }
+
+    /// Restores the order from before shuffling. Tracks added since are kept
+    /// at the end and removed tracks stay removed.
+    pub fn unshuffle(&mut self) {
+        let Some(unshuffled) = self.unshuffled.take() else {
+            return;
+        };
+        let stop_track_id = self.stop_track_id();
+
+        let mut remaining = std::mem::take(&mut self.queue);
+        for track in unshuffled {
+            if let Some(position) = remaining.iter().position(|t| t.id == track.id) {
+                self.queue.push(remaining.remove(position));
+            }
+        }
+        self.queue.append(&mut remaining);
+
+        if let Some(current_position) = self
+            .queue
+            .iter()
+            .position(|track| track.status == TrackStatus::Playing)
+        {
+            self.skip_to_track(current_position as i32);
+        }
+        self.stop_after = self.position_of(stop_track_id);
+    }
+
+    fn stop_track_id(&self) -> Option<u32> {
+        self.stop_after
+            .and_then(|position| self.queue.get(position))
+            .map(|track| track.id)
+    }
+
+    fn position_of(&self, track_id: Option<u32>) -> Option<usize> {
+        let track_id = track_id?;
+        self.queue.iter().position(|track| track.id == track_id)
     }
 
     pub fn current_track(&self) -> Option<&Track> {
@@ -130,6 +306,11 @@ impl Tracklist {
                 link: Some(format!("/playlist/{}", tracklist.id)),
                 cover_link,
             },
+            TracklistType::LocalPlaylist(tracklist) => Entity {
+                title: Some(tracklist.title.clone()),
+                link: Some(format!("/local-playlist/{}", tracklist.id)),
+                cover_link,
+            },
             TracklistType::TopTracks(tracklist) => Entity {
                 title: Some(tracklist.artist_name.clone()),
                 link: Some(format!("/artist/{}", tracklist.id)),
@@ -155,6 +336,10 @@ impl Tracklist {
             return None;
         }
 
+        self.stop_after = self
+            .stop_after
+            .filter(|position| *position as i32 >= new_position);
+
         let mut new_track: Option<&Track> = None;
 
         for queue_item in self.queue.iter_mut().enumerate() {
@@ -180,3 +365,104 @@ impl Tracklist {
         new_track
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracklist(ids: &[u32], playing: usize) -> Tracklist {
+        let mut tracklist = Tracklist {
+            queue: ids
+                .iter()
+                .map(|id| Track {
+                    id: *id,
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        };
+        tracklist.skip_to_track(playing as i32);
+        tracklist
+    }
+
+    fn ids(tracklist: &Tracklist) -> Vec<u32> {
+        tracklist.queue.iter().map(|track| track.id).collect()
+    }
+
+    #[test]
+    fn next_position_follows_repeat_mode() {
+        let last = tracklist(&[1, 2, 3], 2);
+        assert_eq!(last.next_position(RepeatMode::Off), None);
+        assert_eq!(last.next_position(RepeatMode::Track), Some(2));
+        assert_eq!(last.next_position(RepeatMode::Queue), Some(0));
+
+        let first = tracklist(&[1, 2, 3], 0);
+        assert_eq!(first.next_position(RepeatMode::Off), Some(1));
+        assert_eq!(Tracklist::new().next_position(RepeatMode::Queue), None);
+    }
+
+    #[test]
+    fn unshuffle_restores_order() {
+        let mut tracklist = tracklist(&[1, 2, 3, 4, 5, 6], 3);
+
+        tracklist.shuffle();
+        assert!(tracklist.is_shuffled());
+        assert_eq!(tracklist.current_track().map(|track| track.id), Some(4));
+        assert_eq!(tracklist.current_position(), 0);
+
+        tracklist.queue.retain(|track| track.id != 2);
+        tracklist.queue.push(Track {
+            id: 7,
+            ..Default::default()
+        });
+
+        tracklist.unshuffle();
+        assert!(!tracklist.is_shuffled());
+        assert_eq!(ids(&tracklist), [1, 3, 4, 5, 6, 7]);
+        assert_eq!(tracklist.current_position(), 2);
+        assert_eq!(tracklist.queue[0].status, TrackStatus::Played);
+        assert_eq!(tracklist.queue[3].status, TrackStatus::Unplayed);
+    }
+
+    #[test]
+    fn stop_after_follows_its_track() {
+        let mut tracklist = tracklist(&[1, 2, 3, 4], 1);
+        tracklist.stop_after = Some(2);
+
+        tracklist.insert(
+            2,
+            Track {
+                id: 5,
+                ..Default::default()
+            },
+        );
+        assert_eq!(tracklist.stop_after, Some(3));
+
+        tracklist.remove(0);
+        assert_eq!(tracklist.stop_after, Some(2));
+        assert_eq!(ids(&tracklist), [2, 5, 3, 4]);
+
+        tracklist.reorder(&[0, 2, 1, 3]);
+        assert_eq!(tracklist.stop_after, Some(1));
+        assert!(!tracklist.stops_after_current());
+
+        tracklist.skip_to_track(1);
+        assert!(tracklist.stops_after_current());
+
+        tracklist.skip_to_track(2);
+        assert_eq!(tracklist.stop_after, None);
+    }
+
+    #[test]
+    fn stop_after_survives_shuffling() {
+        let mut tracklist = tracklist(&[1, 2, 3, 4, 5, 6], 1);
+        tracklist.stop_after = Some(4);
+
+        tracklist.shuffle();
+        let stop_after = tracklist.stop_after.expect("stop position is kept");
+        assert_eq!(tracklist.queue[stop_after].id, 5);
+
+        tracklist.unshuffle();
+        assert_eq!(tracklist.stop_after, Some(4));
+    }
+}
@@ -0,0 +1,194 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use qobuz_player_models::Track;
+use rodio::{Source, decoder::DecoderBuilder};
+
+use crate::{Result, downloader::find_cached_track, error::Error};
+
+pub const BUCKETS_PER_SECOND: u32 = 20;
+pub const WAVEFORM_DIR: &str = ".waveforms";
+
+const MAGIC: &[u8; 4] = b"QPWF";
+const VERSION: u8 = 1;
+const HEADER_LEN: usize = 11;
+
+/// Peak and RMS level per bucket, from 0.0 to 1.0, over all channels.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Waveform {
+    pub buckets_per_second: u32,
+    pub peaks: Vec<f32>,
+    pub rms: Vec<f32>,
+}
+
+impl Waveform {
+    pub fn compute(track_path: &Path) -> Result<Self> {
+        let file = fs::File::open(track_path).map_err(|e| waveform_error(track_path, e))?;
+        let decoded = DecoderBuilder::new().with_data(file).build()?;
+
+        let channels = decoded.channels().max(1) as usize;
+        let samples_per_bucket =
+            (decoded.sample_rate() / BUCKETS_PER_SECOND).max(1) as usize * channels;
+
+        let mut waveform = Self {
+            buckets_per_second: BUCKETS_PER_SECOND,
+            peaks: vec![],
+            rms: vec![],
+        };
+        let (mut peak, mut sum_squares, mut count) = (0f32, 0f64, 0usize);
+
+        for sample in decoded {
+            peak = peak.max(sample.abs());
+            sum_squares += (sample as f64).powi(2);
+            count += 1;
+
+            if count == samples_per_bucket {
+                waveform.push(peak, sum_squares, count);
+                (peak, sum_squares, count) = (0.0, 0.0, 0);
+            }
+        }
+        if count > 0 {
+            waveform.push(peak, sum_squares, count);
+        }
+
+        Ok(waveform)
+    }
+
+    fn push(&mut self, peak: f32, sum_squares: f64, count: usize) {
+        self.peaks.push(peak.min(1.0));
+        self.rms
+            .push(((sum_squares / count as f64).sqrt() as f32).min(1.0));
+    }
+
+    /// Compact form: a header followed by one peak and one RMS byte per
+    /// bucket.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.peaks.len() * 2);
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&(self.buckets_per_second as u16).to_le_bytes());
+        bytes.extend_from_slice(&(self.peaks.len() as u32).to_le_bytes());
+        for (peak, rms) in self.peaks.iter().zip(&self.rms) {
+            bytes.push((peak * 255.0).round() as u8);
+            bytes.push((rms * 255.0).round() as u8);
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < HEADER_LEN || &bytes[..4] != MAGIC || bytes[4] != VERSION {
+            return None;
+        }
+
+        let buckets_per_second = u16::from_le_bytes([bytes[5], bytes[6]]) as u32;
+        let count = u32::from_le_bytes(bytes[7..11].try_into().ok()?) as usize;
+        let data = &bytes[HEADER_LEN..];
+        if data.len() != count * 2 {
+            return None;
+        }
+
+        Some(Self {
+            buckets_per_second,
+            peaks: data.iter().step_by(2).map(|x| *x as f32 / 255.0).collect(),
+            rms: data.iter().skip(1).step_by(2).map(|x| *x as f32 / 255.0).collect(),
+        })
+    }
+
+    /// Reduces the overview to `width` columns of peaks, for small displays.
+    pub fn resample_peaks(&self, width: usize) -> Vec<f32> {
+        if width == 0 || self.peaks.is_empty() {
+            return vec![];
+        }
+
+        (0..width)
+            .map(|column| {
+                let start = column * self.peaks.len() / width;
+                let end = ((column + 1) * self.peaks.len() / width).max(start + 1);
+                self.peaks[start..end.min(self.peaks.len())]
+                    .iter()
+                    .copied()
+                    .fold(0.0, f32::max)
+            })
+            .collect()
+    }
+}
+
+pub fn waveform_path(audio_cache_dir: &Path, track_id: u32) -> PathBuf {
+    audio_cache_dir
+        .join(WAVEFORM_DIR)
+        .join(format!("{track_id}.bin"))
+}
+
+pub fn stored_waveform(audio_cache_dir: &Path, track_id: u32) -> Option<Waveform> {
+    fs::read(waveform_path(audio_cache_dir, track_id))
+        .ok()
+        .and_then(|bytes| Waveform::from_bytes(&bytes))
+}
+
+/// Loads the stored overview for a track, computing and storing it from the
+/// cached file when missing. Returns `None` while the track is not cached.
+pub async fn track_waveform(track: &Track, audio_cache_dir: &Path) -> Result<Option<Waveform>> {
+    if let Some(waveform) = stored_waveform(audio_cache_dir, track.id) {
+        return Ok(Some(waveform));
+    }
+
+    let Some(cached) = find_cached_track(track, audio_cache_dir) else {
+        return Ok(None);
+    };
+
+    let path = waveform_path(audio_cache_dir, track.id);
+    tokio::task::spawn_blocking(move || {
+        let waveform = Waveform::compute(&cached)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| waveform_error(parent, e))?;
+        }
+        fs::write(&path, waveform.to_bytes()).map_err(|e| waveform_error(&path, e))?;
+        Ok(Some(waveform))
+    })
+    .await
+    .expect("infallible")
+}
+
+fn waveform_error(path: &Path, error: std::io::Error) -> Error {
+    Error::StreamError {
+        message: format!("Unable to read waveform for {}: {error}", path.to_string_lossy()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_through_bytes() {
+        let waveform = Waveform {
+            buckets_per_second: BUCKETS_PER_SECOND,
+            peaks: vec![0.0, 1.0, 0.2],
+            rms: vec![0.0, 0.6, 0.4],
+        };
+
+        let decoded = Waveform::from_bytes(&waveform.to_bytes()).unwrap();
+        assert_eq!(decoded.buckets_per_second, BUCKETS_PER_SECOND);
+        for (a, b) in decoded.peaks.iter().zip(&waveform.peaks) {
+            assert!((a - b).abs() <= 0.5 / 255.0);
+        }
+        for (a, b) in decoded.rms.iter().zip(&waveform.rms) {
+            assert!((a - b).abs() <= 0.5 / 255.0);
+        }
+        assert!(Waveform::from_bytes(b"QPWF").is_none());
+    }
+
+    #[test]
+    fn resamples_to_column_peaks() {
+        let waveform = Waveform {
+            buckets_per_second: BUCKETS_PER_SECOND,
+            peaks: vec![0.1, 0.5, 0.2, 0.9],
+            rms: vec![0.0; 4],
+        };
+
+        assert_eq!(waveform.resample_peaks(2), vec![0.5, 0.9]);
+        assert_eq!(waveform.resample_peaks(8).len(), 8);
+    }
+}
@@ -24,6 +24,56 @@ pub struct Track {
     pub album_title: Option<String>,
     pub album_id: Option<String>,
     pub playlist_track_id: Option<u64>,
+    #[serde(default)]
+    pub isrc: Option<String>,
+    #[serde(default)]
+    pub stream_format: Option<StreamFormat>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct StreamFormat {
+    pub format_id: u32,
+    pub mime_type: String,
+    pub sampling_rate: f64,
+    pub bit_depth: u32,
+    #[serde(default)]
+    pub bit_perfect: bool,
+}
+
+impl StreamFormat {
+    pub fn codec(&self) -> String {
+        match self.mime_type.rsplit('/').next() {
+            Some("mpeg") => "MP3".to_string(),
+            Some(subtype) if !subtype.is_empty() => subtype.to_uppercase(),
+            _ => "Unknown".to_string(),
+        }
+    }
+
+    pub fn is_lossy(&self) -> bool {
+        self.format_id == 5
+    }
+}
+
+impl std::fmt::Display for StreamFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_lossy() {
+            return write!(f, "{} 320 kbps", self.codec());
+        }
+
+        write!(
+            f,
+            "{} {}-bit / {}kHz",
+            self.codec(),
+            self.bit_depth,
+            self.sampling_rate
+        )?;
+
+        if self.bit_perfect {
+            write!(f, " · bit-perfect")?;
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Default, Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
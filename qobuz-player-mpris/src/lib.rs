@@ -6,8 +6,8 @@ use mpris_server::{
     zbus::{self, fdo},
 };
 use qobuz_player_controls::{
-    ExitSender, PositionReceiver, Result, Status, StatusReceiver, TracklistReceiver,
-    VolumeReceiver, controls::Controls, error::Error,
+    ExitSender, PositionReceiver, RepeatModeReceiver, Result, Status, StatusReceiver,
+    TracklistReceiver, VolumeReceiver, controls::Controls, error::Error, tracklist::RepeatMode,
 };
 use qobuz_player_models::Track;
 
@@ -17,6 +17,7 @@ struct MprisPlayer {
     tracklist_receiver: TracklistReceiver,
     volume_receiver: VolumeReceiver,
     status_receiver: StatusReceiver,
+    repeat_mode_receiver: RepeatModeReceiver,
     exit_sender: ExitSender,
 }
 
@@ -117,11 +118,17 @@ impl PlayerInterface for MprisPlayer {
     }
 
     async fn loop_status(&self) -> fdo::Result<LoopStatus> {
-        Err(fdo::Error::NotSupported("Not supported".into()))
+        Ok(loop_status(*self.repeat_mode_receiver.borrow()))
     }
 
-    async fn set_loop_status(&self, _loop_status: LoopStatus) -> zbus::Result<()> {
-        Err(zbus::Error::Unsupported)
+    async fn set_loop_status(&self, loop_status: LoopStatus) -> zbus::Result<()> {
+        let mode = match loop_status {
+            LoopStatus::None => RepeatMode::Off,
+            LoopStatus::Track => RepeatMode::Track,
+            LoopStatus::Playlist => RepeatMode::Queue,
+        };
+        self.controls.set_repeat_mode(mode);
+        Ok(())
     }
 
     async fn rate(&self) -> fdo::Result<PlaybackRate> {
@@ -133,11 +140,12 @@ impl PlayerInterface for MprisPlayer {
     }
 
     async fn shuffle(&self) -> fdo::Result<bool> {
-        Ok(false)
+        Ok(self.tracklist_receiver.borrow().is_shuffled())
     }
 
-    async fn set_shuffle(&self, _shuffle: bool) -> zbus::Result<()> {
-        Err(zbus::Error::Unsupported)
+    async fn set_shuffle(&self, shuffle: bool) -> zbus::Result<()> {
+        self.controls.set_shuffle(shuffle);
+        Ok(())
     }
 
     async fn metadata(&self) -> fdo::Result<Metadata> {
@@ -205,6 +213,7 @@ pub async fn init(
     mut tracklist_receiver: TracklistReceiver,
     mut volume_receiver: VolumeReceiver,
     mut status_receiver: StatusReceiver,
+    mut repeat_mode_receiver: RepeatModeReceiver,
     controls: Controls,
     exit_sender: ExitSender,
 ) -> Result<()> {
@@ -218,6 +227,7 @@ pub async fn init(
             tracklist_receiver: tracklist_receiver.clone(),
             volume_receiver: volume_receiver.clone(),
             status_receiver: status_receiver.clone(),
+            repeat_mode_receiver: repeat_mode_receiver.clone(),
             exit_sender,
         },
     )
@@ -246,9 +256,10 @@ pub async fn init(
                             Property::Metadata(metadata),
                             Property::CanGoPrevious(can_previous),
                             Property::CanGoNext(can_next),
+                            Property::Shuffle(tracklist.is_shuffled()),
                         ])
                         .await else {
-                            return Err(Error::MprisPropertyError { property: "Metadata, CanGoPrevious, CanGoNext".into() });
+                            return Err(Error::MprisPropertyError { property: "Metadata, CanGoPrevious, CanGoNext, Shuffle".into() });
                         };
                 }
             },
@@ -260,6 +271,14 @@ pub async fn init(
                         return Err(Error::MprisPropertyError { property: "Volume".into() });
                     };
             },
+            Ok(_) = repeat_mode_receiver.changed() => {
+                let repeat_mode = *repeat_mode_receiver.borrow_and_update();
+                let Ok(_) = server
+                    .properties_changed([Property::LoopStatus(loop_status(repeat_mode))])
+                    .await else {
+                        return Err(Error::MprisPropertyError { property: "LoopStatus".into() });
+                    };
+            },
             Ok(_) = status_receiver.changed() => {
                 let status = *status_receiver.borrow_and_update();
                 let (can_play, can_pause) = match status {
@@ -292,6 +311,14 @@ pub async fn init(
     }
 }
 
+fn loop_status(repeat_mode: RepeatMode) -> LoopStatus {
+    match repeat_mode {
+        RepeatMode::Off => LoopStatus::None,
+        RepeatMode::Track => LoopStatus::Track,
+        RepeatMode::Queue => LoopStatus::Playlist,
+    }
+}
+
 fn track_to_metadata(track: &Track) -> Metadata {
     let mut metadata = Metadata::new();
     let duration = mpris_server::Time::from_secs(track.duration_seconds as i64);
@@ -310,5 +337,14 @@ fn track_to_metadata(track: &Track) -> Metadata {
     metadata.set_title(Some(track.title.clone()));
     metadata.set_track_number(Some(track.number as i32));
 
+    // stream format, under its own key so it does not pose as a comment
+    metadata.set(
+        "qobuz:streamFormat",
+        track
+            .stream_format
+            .as_ref()
+            .map(|stream_format| stream_format.to_string()),
+    );
+
     metadata
 }
@@ -10,21 +10,37 @@ use crate::{
 use core::fmt;
 use image::load_from_memory;
 use qobuz_player_controls::{
-    PositionReceiver, Result, Status, StatusReceiver, TracklistReceiver,
+    LevelsReceiver, PositionReceiver, Result, SleepTimerReceiver, Status, StatusReceiver,
+    TracklistReceiver,
+    chords::{ChordTimeline, track_chords},
     client::Client,
-    controls::Controls,
+    controls::{Controls, SleepTimer},
     database::Database,
     ExitSender,
     notification::{Notification, NotificationBroadcast},
+    pitch::PitchProbe,
     tracklist::Tracklist,
+    waveform::{Waveform, track_waveform},
 };
 use qobuz_player_models::Track;
 use crossterm::event::{Event, EventStream, KeyCode, KeyEventKind};
 use futures::StreamExt;
 use ratatui::{DefaultTerminal, widgets::*};
 use ratatui_image::{picker::Picker, protocol::StatefulProtocol};
-use std::{io, sync::Arc, time::Instant};
-use tokio::time::{self, Duration};
+use std::{io, path::PathBuf, sync::Arc, time::Instant};
+use tokio::{
+    sync::mpsc,
+    time::{self, Duration},
+};
+
+const SLEEP_TIMER_PRESETS: [Option<SleepTimer>; 6] = [
+    None,
+    Some(SleepTimer::After(Duration::from_secs(15 * 60))),
+    Some(SleepTimer::After(Duration::from_secs(30 * 60))),
+    Some(SleepTimer::After(Duration::from_secs(60 * 60))),
+    Some(SleepTimer::After(Duration::from_secs(90 * 60))),
+    Some(SleepTimer::EndOfTracklist),
+];
 
 #[derive(Default)]
 pub struct NotificationList {
@@ -54,6 +70,9 @@ pub struct App {
     pub client: Arc<Client>,
     pub controls: Controls,
     pub position: PositionReceiver,
+    pub levels: LevelsReceiver,
+    pub visualizer: bool,
+    pub pitch_probe: Option<PitchProbe>,
     pub tracklist: TracklistReceiver,
     pub status: StatusReceiver,
     pub current_screen: Tab,
@@ -73,6 +92,15 @@ pub struct App {
     pub full_screen: bool,
     pub disable_tui_album_cover: bool,
     pub playback_config: (f32, i16, i16),
+    pub audio_cache_dir: PathBuf,
+    pub waveform_sender: mpsc::UnboundedSender<(u32, Waveform)>,
+    pub waveform_receiver: mpsc::UnboundedReceiver<(u32, Waveform)>,
+    pub waveform_requested: Option<(u32, Instant)>,
+    pub chords_sender: mpsc::UnboundedSender<(u32, ChordTimeline)>,
+    pub chords_receiver: mpsc::UnboundedReceiver<(u32, ChordTimeline)>,
+    pub chords_requested: Option<(u32, Instant)>,
+    pub sleep_timer: SleepTimerReceiver,
+    pub sleep_timer_preset: usize,
 }
 
 #[derive(Default)]
@@ -89,6 +117,7 @@ pub enum Output {
     UpdateLibrary,
     Popup(Popup),
     PopPoputUpdateLibrary,
+    PopPopup,
     AddTrackToPlaylist(Track),
     AddTrackToPlaylistAndPopPopup((u32, u32)),
 }
@@ -181,14 +210,50 @@ impl App {
 
                 Ok(_) = self.position.changed() => {
                     self.now_playing.duration_ms = self.position.borrow_and_update().as_millis() as u32;
+                    self.request_waveform();
+                    self.request_chords();
+                    self.should_draw = true;
+                },
+
+                Ok(_) = self.levels.changed(), if self.visualizer => {
+                    self.levels.borrow_and_update();
+                    self.should_draw = true;
+                },
+
+                Some((track_id, waveform)) = self.waveform_receiver.recv() => {
+                    if self.now_playing.playing_track.as_ref().is_some_and(|track| track.id == track_id) {
+                        self.now_playing.waveform = Some(waveform);
+                        self.should_draw = true;
+                    }
+                },
+
+                Ok(_) = self.sleep_timer.changed() => {
+                    self.now_playing.sleep_timer = *self.sleep_timer.borrow_and_update();
                     self.should_draw = true;
                 },
 
+                Some((track_id, chords)) = self.chords_receiver.recv() => {
+                    if self.now_playing.playing_track.as_ref().is_some_and(|track| track.id == track_id) {
+                        self.now_playing.chords = Some(chords);
+                        self.should_draw = true;
+                    }
+                },
+
                 Ok(_) = self.tracklist.changed() => {
                     let tracklist = self.tracklist.borrow_and_update().clone();
-                    self.queue.set_items(tracklist.queue().to_vec());
+                    self.queue.set_items(tracklist.queue().to_vec(), tracklist.stop_after);
                     let status = self.now_playing.status;
+                    let previous = self.now_playing.playing_track.as_ref().map(|track| track.id);
+                    let waveform = self.now_playing.waveform.take();
+                    let chords = self.now_playing.chords.take();
                     self.now_playing = get_current_state(tracklist, status).await;
+                    self.now_playing.sleep_timer = *self.sleep_timer.borrow();
+                    if self.now_playing.playing_track.as_ref().map(|track| track.id) == previous {
+                        self.now_playing.waveform = waveform;
+                        self.now_playing.chords = chords;
+                    } else {
+                        self.pitch_probe = None;
+                    }
                     self.should_draw = true;
                 },
 
@@ -255,6 +320,75 @@ impl App {
         Ok(())
     }
 
+    /// Loads the overview of the playing track in the background once it is
+    /// cached, retrying while the download is still in progress.
+    fn request_waveform(&mut self) {
+        if self.now_playing.waveform.is_some() {
+            return;
+        }
+        let Some(track) = self.now_playing.playing_track.clone() else {
+            return;
+        };
+        if let Some((track_id, requested)) = self.waveform_requested
+            && track_id == track.id
+            && requested.elapsed() < Duration::from_secs(5)
+        {
+            return;
+        }
+
+        self.waveform_requested = Some((track.id, Instant::now()));
+        let audio_cache_dir = self.audio_cache_dir.clone();
+        let sender = self.waveform_sender.clone();
+        tokio::spawn(async move {
+            match track_waveform(&track, &audio_cache_dir).await {
+                Ok(Some(waveform)) => _ = sender.send((track.id, waveform)),
+                Ok(None) => {}
+                Err(e) => tracing::warn!("Unable to load waveform: {e}"),
+            }
+        });
+    }
+
+    /// Steps through the sleep timer presets, starting over once the timer
+    /// has run out or was cancelled elsewhere.
+    fn cycle_sleep_timer(&mut self) {
+        if self.sleep_timer.borrow().is_none() {
+            self.sleep_timer_preset = 0;
+        }
+
+        self.sleep_timer_preset = (self.sleep_timer_preset + 1) % SLEEP_TIMER_PRESETS.len();
+        let timer = SLEEP_TIMER_PRESETS[self.sleep_timer_preset];
+        self.controls.set_sleep_timer(timer);
+    }
+
+    /// Loads or estimates the chord timeline of the playing track in the
+    /// background, retrying while the download is still in progress.
+    fn request_chords(&mut self) {
+        if self.now_playing.chords.is_some() {
+            return;
+        }
+        let Some(track) = self.now_playing.playing_track.clone() else {
+            return;
+        };
+        if let Some((track_id, requested)) = self.chords_requested
+            && track_id == track.id
+            && requested.elapsed() < Duration::from_secs(5)
+        {
+            return;
+        }
+
+        self.chords_requested = Some((track.id, Instant::now()));
+        let database = self.database.clone();
+        let audio_cache_dir = self.audio_cache_dir.clone();
+        let sender = self.chords_sender.clone();
+        tokio::spawn(async move {
+            match track_chords(&database, &track, &audio_cache_dir).await {
+                Ok(Some(chords)) => _ = sender.send((track.id, chords)),
+                Ok(None) => {}
+                Err(e) => tracing::warn!("Unable to estimate chords: {e}"),
+            }
+        });
+    }
+
     async fn update_library(&mut self) {
         let library = self.client.library().await;
         let Ok(library) = library else {
@@ -269,6 +403,9 @@ impl App {
             .playlists
             .set_all_items(library.playlists.into_iter().map(Into::into).collect());
         self.library.tracks.set_all_items(library.tracks);
+        if let Ok(local_playlists) = self.database.get_local_playlists().await {
+            self.library.local_playlists.set_all_items(local_playlists);
+        }
         self.library.filter.reset();
     }
 
@@ -343,6 +480,25 @@ impl App {
                     self.full_screen = !self.full_screen;
                     self.should_draw = true;
                 }
+                KeyCode::Char('v') => {
+                    self.visualizer = !self.visualizer;
+                    self.should_draw = true;
+                }
+                KeyCode::Char('s') => {
+                    self.cycle_sleep_timer();
+                    self.should_draw = true;
+                }
+                KeyCode::Char('P') => {
+                    if self.pitch_probe.take().is_none() {
+                        match self.controls.probe_pitch().await {
+                            Ok(probe) => self.pitch_probe = Some(probe),
+                            Err(err) => self
+                                .notifications
+                                .push(Notification::Error(err.to_string())),
+                        }
+                    }
+                    self.should_draw = true;
+                }
                 _ => {}
             },
             Output::Popup(popup) => {
@@ -356,6 +512,15 @@ impl App {
                 self.app_state = AppState::Popup(popups);
                 self.should_draw = true;
             }
+            Output::PopPopup => {
+                if let AppState::Popup(popups) = &mut self.app_state {
+                    popups.pop();
+                    if popups.is_empty() {
+                        self.app_state = AppState::Normal;
+                    }
+                    self.should_draw = true;
+                }
+            }
             Output::PopPoputUpdateLibrary => {
                 if let AppState::Popup(popups) = &mut self.app_state {
                     popups.pop();
@@ -375,7 +540,9 @@ impl App {
                         .collect::<Vec<_>>()
                 });
 
-                if let Ok(playlists) = playlists_res {
+                let local_playlists = self.database.get_local_playlists().await;
+
+                if let (Ok(playlists), Ok(local_playlists)) = (playlists_res, local_playlists) {
                     let mut popups = match std::mem::take(&mut self.app_state) {
                         AppState::Popup(v) => v,
                         other => {
@@ -384,7 +551,11 @@ impl App {
                         }
                     };
 
-                    popups.push(Popup::Track(TrackPopupState::new(track, playlists)));
+                    popups.push(Popup::Track(Box::new(TrackPopupState::new(
+                        track,
+                        playlists,
+                        local_playlists,
+                    ))));
 
                     self.app_state = AppState::Popup(popups);
                     self.should_draw = true;
@@ -444,6 +615,7 @@ impl App {
                                             event,
                                             &self.client,
                                             &self.controls,
+                                            &self.database,
                                             &mut self.notifications,
                                         )
                                         .await
@@ -470,6 +642,7 @@ impl App {
                                 event,
                                 &self.client,
                                 &self.controls,
+                                &self.database,
                                 &mut self.notifications,
                             )
                             .await
@@ -484,7 +657,11 @@ impl App {
                             )
                             .await
                     }
-                    Tab::Queue => Ok(self.queue.handle_events(event, &self.controls).await),
+                    Tab::Queue => {
+                        self.queue
+                            .handle_events(event, &self.controls, &self.database)
+                            .await
+                    }
                     Tab::Discover => {
                         self.discover
                             .handle_events(event, &self.client, &mut self.notifications)
@@ -571,5 +748,8 @@ pub async fn get_current_state(tracklist: Tracklist, status: Status) -> NowPlayi
         status,
         tracklist_position: tracklist.current_position(),
         duration_ms: 0,
+        waveform: None,
+        chords: None,
+        sleep_timer: None,
     }
 }
@@ -1,10 +1,10 @@
-use std::sync::Arc;
+use std::{path::PathBuf, sync::Arc};
 
 use app::{App, get_current_state};
 use library::LibraryState;
 use qobuz_player_controls::{
-    database::Database, ExitSender, PositionReceiver, Result, StatusReceiver, TracklistReceiver,
-    client::Client, controls::Controls, error::Error, notification::NotificationBroadcast,
+    database::Database, ExitSender, LevelsReceiver, PositionReceiver, Result, SleepTimerReceiver, StatusReceiver,
+    TracklistReceiver, client::Client, controls::Controls, error::Error, notification::NotificationBroadcast,
 };
 use queue::QueueState;
 use ratatui::{prelude::*, widgets::*};
@@ -14,12 +14,14 @@ mod app;
 mod discover;
 mod library;
 mod now_playing;
+mod pitch_probe;
 mod popup;
 mod queue;
 mod search;
 mod settings;
 mod sub_tab;
 mod ui;
+mod visualizer;
 mod widgets;
 
 #[allow(clippy::too_many_arguments)]
@@ -28,11 +30,14 @@ pub async fn init(
     broadcast: Arc<NotificationBroadcast>,
     controls: Controls,
     position_receiver: PositionReceiver,
+    levels_receiver: LevelsReceiver,
     tracklist_receiver: TracklistReceiver,
     status_receiver: StatusReceiver,
+    sleep_timer_receiver: SleepTimerReceiver,
     exit_sender: ExitSender,
     database: Arc<Database>,
     disable_tui_album_cover: bool,
+    audio_cache_dir: PathBuf,
 ) -> Result<()> {
     let mut terminal = ratatui::init();
 
@@ -41,6 +46,7 @@ pub async fn init(
     let tracklist_value = tracklist_receiver.borrow().clone();
     let status_value = *status_receiver.borrow();
     let queue = tracklist_value.queue().clone();
+    let stop_after = tracklist_value.stop_after;
     let now_playing = get_current_state(tracklist_value, status_value).await;
     let exit_sender_clone = exit_sender.clone();
 
@@ -49,6 +55,8 @@ pub async fn init(
         .await
         .map(|c| (c.time_stretch_ratio, c.pitch_semitones, c.pitch_cents))
         .unwrap_or((1.0, 0, 0));
+    let (waveform_sender, waveform_receiver) = tokio::sync::mpsc::unbounded_channel();
+    let (chords_sender, chords_receiver) = tokio::sync::mpsc::unbounded_channel();
     let mut app = App {
         broadcast,
         notifications: Default::default(),
@@ -56,6 +64,9 @@ pub async fn init(
         now_playing,
         full_screen: false,
         position: position_receiver,
+        levels: levels_receiver,
+        visualizer: false,
+        pitch_probe: None,
         tracklist: tracklist_receiver,
         status: status_receiver,
         current_screen: Default::default(),
@@ -63,15 +74,24 @@ pub async fn init(
         should_draw: true,
         app_state: Default::default(),
         disable_tui_album_cover,
-        library: LibraryState::new(&client).await?,
+        library: LibraryState::new(&client, &database).await?,
         search: Default::default(),
-        queue: QueueState::new(queue),
+        queue: QueueState::new(queue, stop_after),
         discover: discover::DiscoverState::new(&client).await?,
         settings: settings::SettingsState::new(&database).await?,
         database,
         exit_sender,
         client,
         playback_config,
+        audio_cache_dir,
+        waveform_sender,
+        waveform_receiver,
+        waveform_requested: None,
+        chords_sender,
+        chords_receiver,
+        chords_requested: None,
+        sleep_timer: sleep_timer_receiver,
+        sleep_timer_preset: 0,
     };
 
     _ = app.run(&mut terminal).await;
@@ -1,4 +1,4 @@
-use qobuz_player_controls::{Result, client::Client, controls::Controls};
+use qobuz_player_controls::{Result, client::Client, controls::Controls, database::Database};
 use ratatui::{
     crossterm::event::{Event, KeyCode, KeyEventKind},
     prelude::*,
@@ -12,6 +12,7 @@ use crate::{
     widgets::{
         album_list::AlbumList,
         artist_list::ArtistList,
+        local_playlist_list::LocalPlaylistList,
         playlist_list::PlaylistList,
         track_list::{TrackList, TrackListEvent},
     },
@@ -24,12 +25,14 @@ pub struct LibraryState {
     pub artists: ArtistList,
     pub playlists: PlaylistList,
     pub tracks: TrackList,
+    pub local_playlists: LocalPlaylistList,
     pub sub_tab: SubTab,
 }
 
 impl LibraryState {
-    pub async fn new(client: &Client) -> Result<Self> {
+    pub async fn new(client: &Client, database: &Database) -> Result<Self> {
         let library = client.library().await?;
+        let local_playlists = database.get_local_playlists().await?;
 
         Ok(Self {
             editing: Default::default(),
@@ -38,6 +41,7 @@ impl LibraryState {
             artists: ArtistList::new(library.artists),
             playlists: PlaylistList::new(library.playlists.into_iter().map(Into::into).collect()),
             tracks: TrackList::new(library.tracks),
+            local_playlists: LocalPlaylistList::new(local_playlists),
             sub_tab: Default::default(),
         })
     }
@@ -65,7 +69,7 @@ impl LibraryState {
             .constraints([Constraint::Length(2), Constraint::Min(1)])
             .split(tab_content_area);
 
-        let tabs = tab_bar(SubTab::labels(), self.sub_tab.selected().into());
+        let tabs = tab_bar(SubTab::library_labels(), self.sub_tab.selected().into());
         frame.render_widget(tabs, chunks[0]);
 
         match self.sub_tab {
@@ -73,6 +77,7 @@ impl LibraryState {
             SubTab::Artists => self.artists.render(chunks[1], frame.buffer_mut()),
             SubTab::Playlists => self.playlists.render(chunks[1], frame.buffer_mut()),
             SubTab::Tracks => self.tracks.render(chunks[1], frame.buffer_mut(), true),
+            SubTab::LocalPlaylists => self.local_playlists.render(chunks[1], frame.buffer_mut()),
         };
     }
 
@@ -81,6 +86,7 @@ impl LibraryState {
         event: Event,
         client: &Client,
         controls: &Controls,
+        database: &Database,
         notifications: &mut NotificationList,
     ) -> Result<Output> {
         match event {
@@ -130,6 +136,12 @@ impl LibraryState {
                                     )
                                     .await;
                             }
+                            SubTab::LocalPlaylists => {
+                                return self
+                                    .local_playlists
+                                    .handle_events(key_event.code, database)
+                                    .await;
+                            }
                         },
                     },
                     true => match key_event.code {
@@ -174,6 +186,15 @@ impl LibraryState {
                                     .collect(),
                             );
 
+                            self.local_playlists.set_filter(
+                                self.local_playlists
+                                    .all_items()
+                                    .iter()
+                                    .filter(|playlist| match_in(&playlist.title))
+                                    .cloned()
+                                    .collect(),
+                            );
+
                             self.tracks.set_filter(
                                 self.tracks
                                     .all_items()
@@ -211,10 +232,10 @@ impl LibraryState {
     }
 
     fn cycle_subtab_backwards(&mut self) {
-        self.sub_tab = self.sub_tab.previous();
+        self.sub_tab = self.sub_tab.previous_library();
     }
 
     fn cycle_subtab(&mut self) {
-        self.sub_tab = self.sub_tab.next();
+        self.sub_tab = self.sub_tab.next_library();
     }
 }
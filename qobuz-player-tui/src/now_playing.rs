@@ -1,5 +1,11 @@
+use std::time::Duration;
+
 use crate::ui::block;
-use qobuz_player_controls::Status;
+use qobuz_player_controls::{
+    Status,
+    chords::{ChordTimeline, transpose_semitones},
+    waveform::Waveform,
+};
 use qobuz_player_models::Track;
 use ratatui::{prelude::*, widgets::*};
 use ratatui_image::{StatefulImage, protocol::StatefulProtocol};
@@ -13,8 +19,12 @@ pub struct NowPlayingState {
     pub tracklist_position: usize,
     pub status: Status,
     pub duration_ms: u32,
+    pub waveform: Option<Waveform>,
+    pub chords: Option<ChordTimeline>,
+    pub sleep_timer: Option<Duration>,
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn render(
     frame: &mut Frame,
     area: Rect,
@@ -22,14 +32,21 @@ pub fn render(
     full_screen: bool,
     disable_tui_album_cover: bool,
     time_stretch_ratio: f32,
-    _pitch_semitones: i16,
+    pitch_semitones: i16,
+    pitch_cents: i16,
 ) {
     let track = match &state.playing_track {
         Some(t) => t,
         None => return,
     };
 
-    let title = get_status(state.status).to_string();
+    let mut title = get_status(state.status).to_string();
+    if let Some(remaining) = state.sleep_timer {
+        title.push_str(&format!(
+            " · Sleep {}",
+            format_seconds(remaining.as_secs() as u32)
+        ));
+    }
     let block = block(Some(&title));
 
     let length = state
@@ -58,9 +75,14 @@ pub fn render(
         frame.render_stateful_widget(stateful_image, chunks[0], &mut image.0);
     }
 
+    let waveform_height = if state.waveform.is_some() { 3 } else { 0 };
     let info_chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Min(1), Constraint::Length(1)])
+        .constraints([
+            Constraint::Min(1),
+            Constraint::Length(waveform_height),
+            Constraint::Length(1),
+        ])
         .split(*chunks.last().unwrap());
 
     let mut lines = vec![];
@@ -81,6 +103,23 @@ pub fn render(
         state.tracklist_length
     )));
 
+    if let Some(stream_format) = &track.stream_format {
+        lines.push(Line::from(stream_format.to_string()).style(Style::new().dim()));
+    }
+
+    if let Some(chords) = &state.chords {
+        let position =
+            Duration::from_millis(state.duration_ms as u64).mul_f32(time_stretch_ratio.max(0.0));
+        let transpose = transpose_semitones(pitch_semitones, pitch_cents);
+        if let (Some(current), next) = chords.around(position) {
+            let mut spans = vec![Span::from(current.label(transpose)).bold()];
+            if let Some(next) = next {
+                spans.push(Span::from(format!(" → {}", next.label(transpose))).dim());
+            }
+            lines.push(Line::from(spans));
+        }
+    }
+
     let displayed_duration_ms =
         (track.duration_seconds as f32 * 1000.0 / time_stretch_ratio).round() as u32;
     let duration = if state.duration_ms < displayed_duration_ms {
@@ -105,10 +144,39 @@ pub fn render(
         .gauge_style(Style::default().fg(Color::Blue))
         .label(label);
 
-    frame.render_widget(gauge, info_chunks[1]);
+    if let Some(waveform) = &state.waveform {
+        render_waveform(frame, info_chunks[1], waveform, ratio);
+    }
+    frame.render_widget(gauge, info_chunks[2]);
     frame.render_widget(Text::from(lines), info_chunks[0]);
 }
 
+fn render_waveform(frame: &mut Frame, area: Rect, waveform: &Waveform, ratio: f64) {
+    let peaks = waveform
+        .resample_peaks(area.width as usize)
+        .into_iter()
+        .map(|peak| (peak * 100.0) as u64)
+        .collect::<Vec<_>>();
+    let played = ((area.width as f64 * ratio) as usize).min(peaks.len());
+
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Length(played as u16), Constraint::Min(0)])
+        .split(area);
+
+    let played_sparkline = Sparkline::default()
+        .data(&peaks[..played])
+        .max(100)
+        .style(Style::default().fg(Color::Blue));
+    let remaining_sparkline = Sparkline::default()
+        .data(&peaks[played..])
+        .max(100)
+        .style(Style::default().dim());
+
+    frame.render_widget(played_sparkline, chunks[0]);
+    frame.render_widget(remaining_sparkline, chunks[1]);
+}
+
 fn get_status(state: Status) -> String {
     match state {
         Status::Playing => "Playing ⏵".to_string(),
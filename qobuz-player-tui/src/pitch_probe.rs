@@ -0,0 +1,41 @@
+use qobuz_player_controls::pitch::PitchProbe;
+use ratatui::{prelude::*, widgets::*};
+
+use crate::ui::block;
+
+const STRENGTH_WIDTH: usize = 8;
+
+pub fn render(frame: &mut Frame, area: Rect, probe: &PitchProbe) {
+    let seconds = probe.position.as_secs();
+    let title = format!("Pitches {:02}:{:02}", seconds / 60, seconds % 60);
+    let block = block(Some(&title));
+
+    if probe.pitches.is_empty() {
+        let paragraph = Paragraph::new("Nothing sounding")
+            .style(Style::new().dim())
+            .block(block);
+        frame.render_widget(paragraph, area);
+        return;
+    }
+
+    let rows = probe.pitches.iter().map(|pitch| {
+        let strength = (pitch.strength * STRENGTH_WIDTH as f32).round().max(1.0) as usize;
+        Row::new([
+            Cell::from(pitch.note.clone()).style(Style::new().bold()),
+            Cell::from(format!("{:+}c", pitch.cents)),
+            Cell::from("█".repeat(strength.min(STRENGTH_WIDTH))).style(Color::Blue),
+        ])
+    });
+
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(4),
+            Constraint::Length(5),
+            Constraint::Length(STRENGTH_WIDTH as u16),
+        ],
+    )
+    .block(block);
+
+    frame.render_widget(table, area);
+}
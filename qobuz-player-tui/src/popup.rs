@@ -1,4 +1,10 @@
-use qobuz_player_controls::{Result, client::Client, controls::Controls};
+use qobuz_player_controls::{
+    Result,
+    client::Client,
+    controls::Controls,
+    database::{Database, LocalPlaylist, LocalPlaylistEntry, SavedQueue},
+    notification::Notification,
+};
 use qobuz_player_models::{Album, Artist, Playlist, PlaylistSimple, Track};
 use ratatui::{
     crossterm::event::{Event, KeyCode, KeyEventKind},
@@ -10,7 +16,10 @@ use tui_input::{Input, backend::crossterm::EventHandler};
 
 use crate::{
     app::{NotificationList, Output},
-    ui::{block, center, centered_rect_fixed, render_input, tab_bar},
+    ui::{
+        COLUMN_SPACING, ROW_HIGHLIGHT_STYLE, basic_list_table, block, center, centered_rect_fixed,
+        render_input, tab_bar,
+    },
     widgets::{
         album_list::AlbumList,
         playlist_list::PlaylistList,
@@ -118,23 +127,39 @@ impl DeletePlaylistPopupstate {
 
 pub struct TrackPopupState {
     playlists: PlaylistList,
+    local_playlists: Vec<LocalPlaylist>,
+    local_state: TableState,
+    show_local: bool,
     track: Track,
 }
 
 impl TrackPopupState {
-    pub fn new(track: Track, owned_playlists: Vec<PlaylistSimple>) -> Self {
+    pub fn new(
+        track: Track,
+        owned_playlists: Vec<PlaylistSimple>,
+        local_playlists: Vec<LocalPlaylist>,
+    ) -> Self {
         Self {
             playlists: PlaylistList::new(owned_playlists),
+            local_playlists,
+            local_state: Default::default(),
+            show_local: false,
             track,
         }
     }
 
     fn select_next(&mut self) {
-        self.playlists.select_next();
+        match self.show_local {
+            true => self.local_state.select_next(),
+            false => self.playlists.select_next(),
+        }
     }
 
     fn select_previous(&mut self) {
-        self.playlists.select_previous();
+        match self.show_local {
+            true => self.local_state.select_previous(),
+            false => self.playlists.select_previous(),
+        }
     }
 }
 
@@ -150,13 +175,145 @@ impl NewPlaylistPopupState {
     }
 }
 
+pub struct NewLocalPlaylistPopupState {
+    name: Input,
+}
+
+impl NewLocalPlaylistPopupState {
+    pub fn new() -> Self {
+        Self {
+            name: Default::default(),
+        }
+    }
+}
+
+pub struct DeleteLocalPlaylistPopupState {
+    title: String,
+    id: i64,
+    confirm: bool,
+}
+
+impl DeleteLocalPlaylistPopupState {
+    pub fn new(playlist: &LocalPlaylist) -> Self {
+        Self {
+            title: playlist.title.clone(),
+            id: playlist.id,
+            confirm: false,
+        }
+    }
+}
+
+enum LocalPlaylistInput {
+    Title(Input),
+    Note(Input),
+}
+
+pub struct LocalPlaylistPopupState {
+    id: i64,
+    title: String,
+    entries: Vec<LocalPlaylistEntry>,
+    state: TableState,
+    shuffle: bool,
+    input: Option<LocalPlaylistInput>,
+}
+
+impl LocalPlaylistPopupState {
+    pub fn new(playlist: LocalPlaylist) -> Self {
+        let mut state = TableState::default();
+        if !playlist.entries.is_empty() {
+            state.select_first();
+        }
+        Self {
+            id: playlist.id,
+            title: playlist.title,
+            entries: playlist.entries,
+            state,
+            shuffle: false,
+            input: None,
+        }
+    }
+
+    async fn reload(&mut self, database: &Database) -> Result<()> {
+        if let Some(playlist) = database.get_local_playlist(self.id).await? {
+            self.title = playlist.title;
+            self.entries = playlist.entries;
+        }
+        Ok(())
+    }
+
+    async fn move_selected(&mut self, database: &Database, down: bool) -> Result<()> {
+        let Some(from) = self
+            .state
+            .selected()
+            .filter(|from| *from < self.entries.len())
+        else {
+            return Ok(());
+        };
+        let to = match down {
+            true if from + 1 < self.entries.len() => from + 1,
+            false if from > 0 => from - 1,
+            _ => return Ok(()),
+        };
+
+        database
+            .move_local_playlist_entry(self.id, from, to)
+            .await?;
+        self.reload(database).await?;
+        self.state.select(Some(to));
+        Ok(())
+    }
+}
+
+pub struct SaveQueuePopupState {
+    name: Input,
+}
+
+impl SaveQueuePopupState {
+    pub fn new() -> Self {
+        Self {
+            name: Default::default(),
+        }
+    }
+}
+
+pub struct SavedQueuesPopupState {
+    queues: Vec<SavedQueue>,
+    append: bool,
+    state: TableState,
+}
+
+impl SavedQueuesPopupState {
+    pub fn new(queues: Vec<SavedQueue>) -> Self {
+        let mut state = TableState::default();
+        if !queues.is_empty() {
+            state.select_first();
+        }
+        Self {
+            queues,
+            append: false,
+            state,
+        }
+    }
+
+    fn selected(&self) -> Option<&SavedQueue> {
+        self.state
+            .selected()
+            .and_then(|index| self.queues.get(index))
+    }
+}
+
 pub enum Popup {
     Artist(ArtistPopupState),
     Album(AlbumPopupState),
     Playlist(PlaylistPopupState),
-    Track(TrackPopupState),
+    Track(Box<TrackPopupState>),
     NewPlaylist(NewPlaylistPopupState),
     DeletePlaylist(DeletePlaylistPopupstate),
+    SaveQueue(SaveQueuePopupState),
+    SavedQueues(SavedQueuesPopupState),
+    LocalPlaylist(LocalPlaylistPopupState),
+    NewLocalPlaylist(NewLocalPlaylistPopupState),
+    DeleteLocalPlaylist(DeleteLocalPlaylistPopupState),
 }
 
 impl Popup {
@@ -274,12 +431,34 @@ impl Popup {
 
                 let block_title = format!("Add {} to playlist", track_state.track.title);
                 let block = block(Some(&block_title));
+                let inner = block.inner(area);
 
                 frame.render_widget(Clear, area);
                 frame.render_widget(&block, area);
-                track_state
-                    .playlists
-                    .render(block.inner(area), frame.buffer_mut());
+
+                let chunks = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Length(2), Constraint::Min(1)])
+                    .split(inner);
+
+                let tabs = tab_bar(
+                    ["Qobuz", "Local"].into(),
+                    if track_state.show_local { 1 } else { 0 },
+                );
+                frame.render_widget(tabs, chunks[0]);
+
+                if track_state.show_local {
+                    let table = basic_list_table(
+                        track_state
+                            .local_playlists
+                            .iter()
+                            .map(|playlist| Row::new(Line::from(playlist.title.clone())))
+                            .collect(),
+                    );
+                    frame.render_stateful_widget(table, chunks[1], &mut track_state.local_state);
+                } else {
+                    track_state.playlists.render(chunks[1], frame.buffer_mut());
+                }
             }
             Popup::NewPlaylist(state) => {
                 let area = center(
@@ -292,21 +471,131 @@ impl Popup {
                 render_input(&state.name, false, area, frame, "Create playlist");
             }
             Popup::DeletePlaylist(state) => {
-                let block_title = format!("Delete {}?", state.title);
+                render_delete_confirmation(frame, &state.title, state.confirm);
+            }
+            Popup::DeleteLocalPlaylist(state) => {
+                render_delete_confirmation(frame, &state.title, state.confirm);
+            }
+            Popup::NewLocalPlaylist(state) => {
                 let area = center(
                     frame.area(),
-                    Constraint::Length(block_title.chars().count() as u16 + 6),
+                    Constraint::Percentage(75),
                     Constraint::Length(3),
                 );
 
-                let tabs = tab_bar(
-                    ["Delete", "Cancel"].into(),
-                    if state.confirm { 0 } else { 1 },
+                frame.render_widget(Clear, area);
+                render_input(&state.name, false, area, frame, "Create local playlist");
+            }
+            Popup::LocalPlaylist(state) => {
+                let visible_rows = (state.entries.len() as u16).clamp(1, 15);
+                let input_height = if state.input.is_some() { 3 } else { 0 };
+                let popup_height =
+                    (visible_rows + 5 + input_height).min(frame.area().height.saturating_sub(2));
+                let popup_width = (frame.area().width * 75 / 100).max(30);
+                let area = centered_rect_fixed(popup_width, popup_height, frame.area());
+
+                let block = block(Some(&state.title));
+                let inner = block.inner(area);
+
+                frame.render_widget(Clear, area);
+                frame.render_widget(block, area);
+
+                let chunks = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([
+                        Constraint::Min(1),
+                        Constraint::Length(input_height),
+                        Constraint::Length(1),
+                        Constraint::Length(1),
+                    ])
+                    .split(inner);
+
+                let rows: Vec<Row> = state
+                    .entries
+                    .iter()
+                    .map(|entry| {
+                        Row::new(vec![
+                            Line::from(entry.track.title.clone()),
+                            Line::from(entry.track.artist_name.clone().unwrap_or_default()),
+                            Line::from(entry.note.clone().unwrap_or_default()).italic(),
+                        ])
+                    })
+                    .collect();
+                let table = Table::new(
+                    rows,
+                    [
+                        Constraint::Ratio(2, 5),
+                        Constraint::Ratio(1, 5),
+                        Constraint::Ratio(2, 5),
+                    ],
                 )
-                .block(block(Some(&block_title)));
+                .row_highlight_style(ROW_HIGHLIGHT_STYLE)
+                .column_spacing(COLUMN_SPACING);
+                frame.render_stateful_widget(table, chunks[0], &mut state.state);
+
+                match &state.input {
+                    Some(LocalPlaylistInput::Title(input)) => {
+                        render_input(input, true, chunks[1], frame, "Rename")
+                    }
+                    Some(LocalPlaylistInput::Note(input)) => {
+                        render_input(input, true, chunks[1], frame, "Note")
+                    }
+                    None => {}
+                }
+
+                let buttons = tab_bar(
+                    ["Play", "Shuffle"].into(),
+                    if state.shuffle { 1 } else { 0 },
+                );
+                frame.render_widget(buttons, chunks[3]);
+            }
+            Popup::SaveQueue(state) => {
+                let area = center(
+                    frame.area(),
+                    Constraint::Percentage(75),
+                    Constraint::Length(3),
+                );
 
                 frame.render_widget(Clear, area);
-                frame.render_widget(tabs, area);
+                render_input(&state.name, false, area, frame, "Save queue as");
+            }
+            Popup::SavedQueues(state) => {
+                let visible_rows = (state.queues.len() as u16).clamp(1, 15);
+                let popup_height = (visible_rows + 4).min(frame.area().height.saturating_sub(2));
+                let popup_width = (frame.area().width * 50 / 100).max(30);
+                let area = centered_rect_fixed(popup_width, popup_height, frame.area());
+
+                let block = block(Some("Saved queues"));
+                let inner = block.inner(area);
+
+                frame.render_widget(Clear, area);
+                frame.render_widget(block, area);
+
+                let chunks = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Min(1), Constraint::Length(1)])
+                    .split(inner);
+
+                let table = basic_list_table(
+                    state
+                        .queues
+                        .iter()
+                        .map(|queue| {
+                            Row::new(Line::from(format!(
+                                "{} ({} tracks)",
+                                queue.name,
+                                queue.tracklist.total()
+                            )))
+                        })
+                        .collect(),
+                );
+                let buttons = tab_bar(
+                    ["Replace", "Append"].into(),
+                    if state.append { 1 } else { 0 },
+                );
+
+                frame.render_stateful_widget(table, chunks[0], &mut state.state);
+                frame.render_widget(buttons, chunks[1]);
             }
         };
     }
@@ -316,6 +605,7 @@ impl Popup {
         event: Event,
         client: &Client,
         controls: &Controls,
+        database: &Database,
         notifications: &mut NotificationList,
     ) -> Result<Output> {
         match event {
@@ -380,6 +670,27 @@ impl Popup {
                     }
                 },
                 Popup::Track(track_popup_state) => match key_event.code {
+                    KeyCode::Left | KeyCode::Char('h') | KeyCode::Right | KeyCode::Char('l') => {
+                        track_popup_state.show_local = !track_popup_state.show_local;
+                        Ok(Output::Consumed)
+                    }
+                    KeyCode::Enter if track_popup_state.show_local => {
+                        let playlist = track_popup_state
+                            .local_state
+                            .selected()
+                            .and_then(|index| track_popup_state.local_playlists.get(index));
+
+                        let Some(playlist) = playlist else {
+                            return Ok(Output::Consumed);
+                        };
+
+                        database
+                            .add_local_playlist_track(playlist.id, &track_popup_state.track)
+                            .await?;
+                        notifications
+                            .push(Notification::Info(format!("Added to {}", playlist.title)));
+                        Ok(Output::PopPoputUpdateLibrary)
+                    }
                     KeyCode::Up | KeyCode::Char('k') => {
                         track_popup_state.select_previous();
                         Ok(Output::Consumed)
@@ -433,8 +744,182 @@ impl Popup {
                     }
                     _ => Ok(Output::Consumed),
                 },
+                Popup::NewLocalPlaylist(state) => match key_event.code {
+                    KeyCode::Enter => {
+                        let title = state.name.value().trim();
+                        if title.is_empty() {
+                            return Ok(Output::Consumed);
+                        }
+                        database.create_local_playlist(title).await?;
+                        Ok(Output::PopPoputUpdateLibrary)
+                    }
+                    _ => {
+                        state.name.handle_event(&event);
+                        Ok(Output::Consumed)
+                    }
+                },
+                Popup::DeleteLocalPlaylist(state) => match key_event.code {
+                    KeyCode::Enter => {
+                        if state.confirm {
+                            database.remove_local_playlist(state.id).await?;
+                        }
+                        Ok(Output::PopPoputUpdateLibrary)
+                    }
+                    KeyCode::Left | KeyCode::Right => {
+                        state.confirm = !state.confirm;
+                        Ok(Output::Consumed)
+                    }
+                    _ => Ok(Output::Consumed),
+                },
+                Popup::LocalPlaylist(state) => {
+                    if let Some(input) = &mut state.input {
+                        if key_event.code != KeyCode::Enter {
+                            match input {
+                                LocalPlaylistInput::Title(input)
+                                | LocalPlaylistInput::Note(input) => input.handle_event(&event),
+                            };
+                            return Ok(Output::Consumed);
+                        }
+
+                        match input {
+                            LocalPlaylistInput::Title(input) => {
+                                let title = input.value().trim();
+                                if !title.is_empty() {
+                                    database.rename_local_playlist(state.id, title).await?;
+                                }
+                            }
+                            LocalPlaylistInput::Note(input) => {
+                                if let Some(position) = state.state.selected() {
+                                    database
+                                        .set_local_playlist_note(
+                                            state.id,
+                                            position,
+                                            Some(input.value()),
+                                        )
+                                        .await?;
+                                }
+                            }
+                        }
+                        state.input = None;
+                        state.reload(database).await?;
+                        return Ok(Output::UpdateLibrary);
+                    }
+
+                    match key_event.code {
+                        KeyCode::Up | KeyCode::Char('k') => {
+                            state.state.select_previous();
+                            Ok(Output::Consumed)
+                        }
+                        KeyCode::Down | KeyCode::Char('j') => {
+                            state.state.select_next();
+                            Ok(Output::Consumed)
+                        }
+                        KeyCode::Left
+                        | KeyCode::Char('h')
+                        | KeyCode::Right
+                        | KeyCode::Char('l') => {
+                            state.shuffle = !state.shuffle;
+                            Ok(Output::Consumed)
+                        }
+                        KeyCode::Char('u') => {
+                            state.move_selected(database, false).await?;
+                            Ok(Output::UpdateLibrary)
+                        }
+                        KeyCode::Char('d') => {
+                            state.move_selected(database, true).await?;
+                            Ok(Output::UpdateLibrary)
+                        }
+                        KeyCode::Char('D') => {
+                            if let Some(position) = state.state.selected() {
+                                database
+                                    .remove_local_playlist_entry(state.id, position)
+                                    .await?;
+                                state.reload(database).await?;
+                            }
+                            Ok(Output::UpdateLibrary)
+                        }
+                        KeyCode::Char('n') => {
+                            let note = state
+                                .state
+                                .selected()
+                                .and_then(|index| state.entries.get(index))
+                                .map(|entry| entry.note.clone().unwrap_or_default());
+                            if let Some(note) = note {
+                                state.input = Some(LocalPlaylistInput::Note(Input::new(note)));
+                            }
+                            Ok(Output::Consumed)
+                        }
+                        KeyCode::Char('R') => {
+                            state.input =
+                                Some(LocalPlaylistInput::Title(Input::new(state.title.clone())));
+                            Ok(Output::Consumed)
+                        }
+                        KeyCode::Enter => {
+                            let index = state.state.selected().unwrap_or_default();
+                            controls.play_local_playlist(state.id, index, state.shuffle);
+                            Ok(Output::Consumed)
+                        }
+                        _ => Ok(Output::Consumed),
+                    }
+                }
+                Popup::SaveQueue(state) => match key_event.code {
+                    KeyCode::Enter => {
+                        controls.save_queue(state.name.value()).await;
+                        Ok(Output::PopPopup)
+                    }
+                    _ => {
+                        state.name.handle_event(&event);
+                        Ok(Output::Consumed)
+                    }
+                },
+                Popup::SavedQueues(state) => match key_event.code {
+                    KeyCode::Up | KeyCode::Char('k') => {
+                        state.state.select_previous();
+                        Ok(Output::Consumed)
+                    }
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        state.state.select_next();
+                        Ok(Output::Consumed)
+                    }
+                    KeyCode::Left | KeyCode::Char('h') | KeyCode::Right | KeyCode::Char('l') => {
+                        state.append = !state.append;
+                        Ok(Output::Consumed)
+                    }
+                    KeyCode::Enter => {
+                        if let Some(queue) = state.selected() {
+                            controls.load_saved_queue(&queue.name, state.append);
+                            return Ok(Output::PopPopup);
+                        }
+                        Ok(Output::Consumed)
+                    }
+                    KeyCode::Char('D') => {
+                        if let Some(index) = state.state.selected()
+                            && index < state.queues.len()
+                        {
+                            let queue = state.queues.remove(index);
+                            database.remove_saved_queue(&queue.name).await?;
+                        }
+                        Ok(Output::Consumed)
+                    }
+                    _ => Ok(Output::Consumed),
+                },
             },
             _ => Ok(Output::Consumed),
         }
     }
 }
+
+fn render_delete_confirmation(frame: &mut Frame, title: &str, confirm: bool) {
+    let block_title = format!("Delete {title}?");
+    let area = center(
+        frame.area(),
+        Constraint::Length(block_title.chars().count() as u16 + 6),
+        Constraint::Length(3),
+    );
+
+    let tabs = tab_bar(["Delete", "Cancel"].into(), if confirm { 0 } else { 1 })
+        .block(block(Some(&block_title)));
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(tabs, area);
+}
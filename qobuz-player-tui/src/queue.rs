@@ -1,4 +1,4 @@
-use qobuz_player_controls::controls::Controls;
+use qobuz_player_controls::{Result, controls::Controls, database::Database};
 use qobuz_player_models::{Track, TrackStatus};
 use ratatui::{
     crossterm::event::{Event, KeyCode, KeyEventKind},
@@ -9,18 +9,21 @@ use ratatui::{
 
 use crate::{
     app::Output,
+    popup::{Popup, SaveQueuePopupState, SavedQueuesPopupState},
     ui::{basic_list_table, block, mark_explicit_and_hifi},
 };
 
 pub struct QueueState {
     items: Vec<Track>,
+    stop_after: Option<usize>,
     state: TableState,
 }
 
 impl QueueState {
-    pub fn new(tracks: Vec<Track>) -> Self {
+    pub fn new(tracks: Vec<Track>, stop_after: Option<usize>) -> Self {
         Self {
             items: tracks,
+            stop_after,
             state: Default::default(),
         }
     }
@@ -38,7 +41,7 @@ impl QueueState {
                             Style::default().add_modifier(Modifier::CROSSED_OUT)
                         }
                     };
-                    Row::new(Line::from(vec![
+                    let mut spans = vec![
                         format!(
                             "{} {}",
                             index + 1,
@@ -49,7 +52,11 @@ impl QueueState {
                             )
                         )
                         .set_style(style),
-                    ]))
+                    ];
+                    if self.stop_after == Some(index) {
+                        spans.push(" · stops here".set_style(Style::default().fg(Color::Blue)));
+                    }
+                    Row::new(Line::from(spans))
                 })
                 .collect(),
         )
@@ -62,12 +69,18 @@ impl QueueState {
         &self.items
     }
 
-    pub fn set_items(&mut self, items: Vec<Track>) {
-        self.items = items
+    pub fn set_items(&mut self, items: Vec<Track>, stop_after: Option<usize>) {
+        self.items = items;
+        self.stop_after = stop_after;
     }
 
-    pub async fn handle_events(&mut self, event: Event, controls: &Controls) -> Output {
-        match event {
+    pub async fn handle_events(
+        &mut self,
+        event: Event,
+        controls: &Controls,
+        database: &Database,
+    ) -> Result<Output> {
+        Ok(match event {
             Event::Key(key_event) if key_event.kind == KeyEventKind::Press => {
                 match key_event.code {
                     KeyCode::Down | KeyCode::Char('j') => {
@@ -83,7 +96,7 @@ impl QueueState {
 
                         if let Some(index) = index {
                             if index == self.items().len() - 1 {
-                                return Output::Consumed;
+                                return Ok(Output::Consumed);
                             }
 
                             let mut order: Vec<_> =
@@ -99,7 +112,7 @@ impl QueueState {
 
                         if let Some(index) = index {
                             if index == 0 {
-                                return Output::Consumed;
+                                return Ok(Output::Consumed);
                             }
                             let mut order: Vec<_> =
                                 self.items().iter().enumerate().map(|x| x.0).collect();
@@ -117,6 +130,20 @@ impl QueueState {
                         }
                         Output::Consumed
                     }
+                    KeyCode::Char('S') => {
+                        Output::Popup(Popup::SaveQueue(SaveQueuePopupState::new()))
+                    }
+                    KeyCode::Char('L') => {
+                        let queues = database.get_saved_queues().await?;
+                        Output::Popup(Popup::SavedQueues(SavedQueuesPopupState::new(queues)))
+                    }
+                    KeyCode::Char('x') => {
+                        if let Some(index) = self.state.selected() {
+                            let position = (self.stop_after != Some(index)).then_some(index);
+                            controls.set_stop_after(position);
+                        }
+                        Output::Consumed
+                    }
                     KeyCode::Enter => {
                         let index = self.state.selected();
 
@@ -130,6 +157,6 @@ impl QueueState {
                 }
             }
             _ => Output::NotConsumed,
-        }
+        })
     }
 }
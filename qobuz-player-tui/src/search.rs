@@ -60,6 +60,7 @@ impl SearchState {
             SubTab::Artists => self.artists.render(chunks[1], frame.buffer_mut()),
             SubTab::Playlists => self.playlists.render(chunks[1], frame.buffer_mut()),
             SubTab::Tracks => self.tracks.render(chunks[1], frame.buffer_mut(), true),
+            SubTab::LocalPlaylists => {}
         };
     }
 
@@ -113,6 +114,7 @@ impl SearchState {
                                     )
                                     .await
                             }
+                            SubTab::LocalPlaylists => Ok(Output::NotConsumed),
                         },
                     },
                     true => match key_event.code {
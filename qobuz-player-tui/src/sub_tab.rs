@@ -7,30 +7,46 @@ pub enum SubTab {
     Artists = 1,
     Playlists = 2,
     Tracks = 3,
+    /// Only shown in the library.
+    LocalPlaylists = 4,
 }
 
 impl SubTab {
     pub const COUNT: u8 = 4;
+    pub const LIBRARY_COUNT: u8 = 5;
 
     pub fn selected(self) -> u8 {
         self as u8
     }
 
     pub fn from_u8(v: u8) -> Self {
-        match v % Self::COUNT {
+        match v % Self::LIBRARY_COUNT {
             0 => Self::Albums,
             1 => Self::Artists,
             2 => Self::Playlists,
-            _ => Self::Tracks,
+            3 => Self::Tracks,
+            _ => Self::LocalPlaylists,
         }
     }
 
     pub fn next(self) -> Self {
-        Self::from_u8(self.selected().wrapping_add(1))
+        self.cycle(Self::COUNT, 1)
     }
 
     pub fn previous(self) -> Self {
-        Self::from_u8(self.selected().wrapping_add(Self::COUNT - 1))
+        self.cycle(Self::COUNT, Self::COUNT - 1)
+    }
+
+    pub fn next_library(self) -> Self {
+        self.cycle(Self::LIBRARY_COUNT, 1)
+    }
+
+    pub fn previous_library(self) -> Self {
+        self.cycle(Self::LIBRARY_COUNT, Self::LIBRARY_COUNT - 1)
+    }
+
+    fn cycle(self, count: u8, step: u8) -> Self {
+        Self::from_u8((self.selected() % count + step) % count)
     }
 
     pub const fn as_str(self) -> &'static str {
@@ -39,15 +55,31 @@ impl SubTab {
             Self::Artists => "Artists",
             Self::Playlists => "Playlists",
             Self::Tracks => "Tracks",
+            Self::LocalPlaylists => "Local",
         }
     }
 
     pub const VALUES: [Self; Self::COUNT as usize] =
         [Self::Albums, Self::Artists, Self::Playlists, Self::Tracks];
 
+    pub const LIBRARY_VALUES: [Self; Self::LIBRARY_COUNT as usize] = [
+        Self::Albums,
+        Self::Artists,
+        Self::Playlists,
+        Self::Tracks,
+        Self::LocalPlaylists,
+    ];
+
     pub fn labels() -> Vec<&'static str> {
         Self::VALUES.iter().map(|tab| tab.as_str()).collect()
     }
+
+    pub fn library_labels() -> Vec<&'static str> {
+        Self::LIBRARY_VALUES
+            .iter()
+            .map(|tab| tab.as_str())
+            .collect()
+    }
 }
 
 impl fmt::Display for SubTab {
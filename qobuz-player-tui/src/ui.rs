@@ -5,6 +5,7 @@ use tui_input::Input;
 use crate::{
     app::{App, AppState, Tab},
     now_playing::{self},
+    pitch_probe, visualizer,
 };
 
 impl App {
@@ -32,6 +33,7 @@ impl App {
                 self.disable_tui_album_cover,
                 self.playback_config.0,
                 self.playback_config.1,
+                self.playback_config.2,
             );
             return;
         }
@@ -65,14 +67,37 @@ impl App {
         frame.render_widget(tabs, chunks[0]);
 
         if self.now_playing.playing_track.is_some() {
+            let mut constraints = vec![Constraint::Min(1)];
+            if self.visualizer {
+                constraints.push(Constraint::Percentage(40));
+            }
+            if self.pitch_probe.is_some() {
+                constraints.push(Constraint::Length(21));
+            }
+            let areas = Layout::horizontal(constraints).split(chunks[2]);
+            let mut panels = areas.iter().skip(1);
+
+            if self.visualizer
+                && let Some(area) = panels.next()
+            {
+                visualizer::render(frame, *area, &self.levels.borrow());
+            }
+            if let Some(probe) = &self.pitch_probe
+                && let Some(area) = panels.next()
+            {
+                pitch_probe::render(frame, *area, probe);
+            }
+            let now_playing_area = areas[0];
+
             now_playing::render(
                 frame,
-                chunks[2],
+                now_playing_area,
                 &mut self.now_playing,
                 self.full_screen,
                 self.disable_tui_album_cover,
                 self.playback_config.0,
                 self.playback_config.1,
+                self.playback_config.2,
             );
         }
 
@@ -189,6 +214,9 @@ pub fn centered_rect_fixed(width: u16, height: u16, area: Rect) -> Rect {
 fn render_help(frame: &mut Frame) {
     let rows = [
         ["Toggle focus mode", "F"],
+        ["Toggle level visualizer", "v"],
+        ["Show pitches at position", "P"],
+        ["Cycle sleep timer", "s"],
         ["Next song", "n"],
         ["Previous song", "p"],
         ["Jump forward", "f"],
@@ -203,6 +231,9 @@ fn render_help(frame: &mut Frame) {
         ["Delete from queue", "D"],
         ["Move up in queue", "u"],
         ["Move down in queue", "d"],
+        ["Stop after track in queue", "x"],
+        ["Save queue", "S (queue page)"],
+        ["Load or delete saved queue", "L (queue page)"],
         ["Remove from library", "D"],
         ["Add to library", "A"],
         ["Create playlist", "C (playlist page)"],
@@ -210,6 +241,8 @@ fn render_help(frame: &mut Frame) {
         ["Add track to playlist", "a"],
         ["Move playlist track up", "u"],
         ["Move playlist track down", "d"],
+        ["Note on local playlist track", "n (local playlist)"],
+        ["Rename local playlist", "R (local playlist)"],
         ["Exit", "q"],
     ];
 
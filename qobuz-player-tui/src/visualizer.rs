@@ -0,0 +1,57 @@
+use qobuz_player_controls::meter::Levels;
+use ratatui::{prelude::*, widgets::*};
+
+use crate::ui::block;
+
+const METER_FLOOR_DB: f32 = -60.0;
+
+pub fn render(frame: &mut Frame, area: Rect, levels: &Levels) {
+    let block = block(Some("Levels"));
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Length(levels.peak.len() as u16 * 2),
+            Constraint::Length(1),
+            Constraint::Min(1),
+        ])
+        .split(inner);
+
+    let meters = levels
+        .peak
+        .iter()
+        .map(|peak| {
+            let value = meter_value(*peak);
+            let color = match value {
+                95.. => Color::Red,
+                80.. => Color::Yellow,
+                _ => Color::Green,
+            };
+            Bar::new(value).text_value("").style(color)
+        })
+        .collect::<Vec<_>>();
+    let meters = BarChart::vertical(meters).bar_width(1).bar_gap(1).max(100);
+
+    let bands = levels
+        .spectrum
+        .iter()
+        .map(|band| Bar::new((band * 100.0) as u64).text_value(""))
+        .collect::<Vec<_>>();
+    let bar_width = (chunks[2].width / bands.len().max(1) as u16).max(1);
+    let spectrum = BarChart::vertical(bands)
+        .bar_width(bar_width)
+        .bar_gap(0)
+        .bar_style(Style::default().fg(Color::Blue))
+        .max(100);
+
+    frame.render_widget(meters, chunks[0]);
+    frame.render_widget(spectrum, chunks[2]);
+}
+
+/// Scales a linear peak to 0-100 over the bottom 60 dB.
+fn meter_value(peak: f32) -> u64 {
+    let db = 20.0 * peak.max(1e-6).log10();
+    (((db - METER_FLOOR_DB) / -METER_FLOOR_DB).clamp(0.0, 1.0) * 100.0) as u64
+}
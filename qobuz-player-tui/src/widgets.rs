@@ -1,4 +1,5 @@
 pub mod album_list;
 pub mod artist_list;
+pub mod local_playlist_list;
 pub mod playlist_list;
 pub mod track_list;
@@ -0,0 +1,128 @@
+use qobuz_player_controls::{
+    Result,
+    database::{Database, LocalPlaylist},
+};
+use ratatui::{
+    buffer::Buffer,
+    crossterm::event::KeyCode,
+    layout::{Constraint, Rect},
+    style::{Modifier, Stylize},
+    text::Line,
+    widgets::{Row, StatefulWidget, Table},
+};
+
+use crate::{
+    app::{FilteredListState, Output},
+    popup::{
+        DeleteLocalPlaylistPopupState, LocalPlaylistPopupState, NewLocalPlaylistPopupState, Popup,
+    },
+    ui::{COLUMN_SPACING, ROW_HIGHLIGHT_STYLE, format_duration},
+};
+
+pub struct LocalPlaylistList {
+    items: FilteredListState<LocalPlaylist>,
+}
+
+impl LocalPlaylistList {
+    pub fn new(playlists: Vec<LocalPlaylist>) -> Self {
+        Self {
+            items: FilteredListState::new(playlists),
+        }
+    }
+
+    pub fn render(&mut self, area: Rect, buf: &mut Buffer) {
+        let table = local_playlist_list(self.items.filter());
+        table.render(area, buf, &mut self.items.state);
+    }
+
+    pub fn set_filter(&mut self, items: Vec<LocalPlaylist>) {
+        self.items.set_filter(items);
+    }
+
+    pub fn all_items(&self) -> &Vec<LocalPlaylist> {
+        self.items.all_items()
+    }
+
+    pub fn set_all_items(&mut self, items: Vec<LocalPlaylist>) {
+        self.items.set_all_items(items);
+    }
+
+    fn selected(&self) -> Option<&LocalPlaylist> {
+        self.items
+            .state
+            .selected()
+            .and_then(|index| self.items.filter().get(index))
+    }
+
+    pub async fn handle_events(&mut self, event: KeyCode, database: &Database) -> Result<Output> {
+        match event {
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.items.state.select_next();
+                Ok(Output::Consumed)
+            }
+
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.items.state.select_previous();
+                Ok(Output::Consumed)
+            }
+
+            KeyCode::Char('C') => Ok(Output::Popup(Popup::NewLocalPlaylist(
+                NewLocalPlaylistPopupState::new(),
+            ))),
+
+            KeyCode::Char('D') => match self.selected() {
+                Some(selected) => Ok(Output::Popup(Popup::DeleteLocalPlaylist(
+                    DeleteLocalPlaylistPopupState::new(selected),
+                ))),
+                None => Ok(Output::Consumed),
+            },
+
+            KeyCode::Enter => {
+                let Some(selected) = self.selected() else {
+                    return Ok(Output::Consumed);
+                };
+
+                match database.get_local_playlist(selected.id).await? {
+                    Some(playlist) => Ok(Output::Popup(Popup::LocalPlaylist(
+                        LocalPlaylistPopupState::new(playlist),
+                    ))),
+                    None => Ok(Output::UpdateLibrary),
+                }
+            }
+
+            _ => Ok(Output::NotConsumed),
+        }
+    }
+}
+
+fn local_playlist_list<'a>(rows: &[LocalPlaylist]) -> Table<'a> {
+    let body_rows: Vec<Row<'a>> = rows
+        .iter()
+        .map(|playlist| {
+            Row::new(vec![
+                Line::from(playlist.title.clone()),
+                Line::from(playlist.entries.len().to_string()),
+                Line::from(format_duration(playlist.duration_seconds())),
+            ])
+        })
+        .collect();
+
+    let is_empty = body_rows.is_empty();
+
+    let constraints = [
+        Constraint::Ratio(2, 3),
+        Constraint::Length(8),
+        Constraint::Length(10),
+    ];
+
+    let mut table = Table::new(body_rows, constraints)
+        .row_highlight_style(ROW_HIGHLIGHT_STYLE)
+        .column_spacing(COLUMN_SPACING);
+
+    if !is_empty {
+        table =
+            table.header(Row::new(["Title", "Tracks", "Duration"]).add_modifier(Modifier::BOLD));
+    }
+
+    table
+}
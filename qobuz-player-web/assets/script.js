@@ -101,9 +101,44 @@ function initSse() {
     slider.value = event.data;
     updatePositionText(event.data);
     updateProgressBarVisual();
+    updateChords(event.data);
+  });
+
+  evtSource.addEventListener("sleep-timer", (event) => {
+    const remaining = document.getElementById("sleep-timer-remaining");
+    if (remaining !== null) {
+      remaining.innerText = event.data;
+    }
+  });
+
+  evtSource.addEventListener("chords", (event) => {
+    const elements = document.querySelectorAll("[data-sse=chords]");
+
+    for (const element of elements) {
+      if (document.body.contains(element)) {
+        htmx.trigger(element, "chords");
+      }
+    }
   });
 }
 
+function updateChords(milliseconds) {
+  const element = document.getElementById("chords");
+  const current = document.getElementById("chord-current");
+  const next = document.getElementById("chord-next");
+  if (element === null || current === null || next === null) {
+    return;
+  }
+
+  const chords = JSON.parse(element.dataset.chords);
+  const ratio = parseFloat(element.dataset.ratio) || 1;
+  const seconds = (parseInt(milliseconds, 10) / 1000) * ratio;
+  const index = chords.findIndex((chord) => seconds >= chord.start && seconds < chord.end);
+
+  current.innerText = index >= 0 ? chords[index].label : "";
+  next.innerText = index >= 0 && index + 1 < chords.length ? chords[index + 1].label : "";
+}
+
 function updatePositionText(milliseconds) {
   const positionElement = document.getElementById("position");
   if (positionElement === null) {
@@ -620,6 +655,12 @@ function formatSettingsSliderPreview(input) {
   if (name === "pitch_cents") {
     return value + " cents";
   }
+  if (name === "crossfeed_strength") {
+    return value + "%";
+  }
+  if (name === "clap_param_value") {
+    return (parseFloat(value) || 0).toFixed(2);
+  }
   return value;
 }
 
@@ -1,17 +1,17 @@
 use axum::response::{Html, IntoResponse, Response};
 use qobuz_player_controls::{
-    ExitSender, PositionReceiver, Result, Status, StatusReceiver, TracklistReceiver, VolumeReceiver,
+    ExitSender, LevelsReceiver, PositionReceiver, Result, SleepTimerReceiver, Status, StatusReceiver, TracklistReceiver, VolumeReceiver,
     client::Client,
     controls::Controls,
     database::Database,
     notification::{Notification, NotificationBroadcast},
     AudioQuality,
 };
-use qobuz_player_models::Library;
+use qobuz_player_models::{Library, StreamFormat};
 use qobuz_player_rfid::RfidState;
 use serde_json::json;
 use skabelon::Templates;
-use std::sync::Arc;
+use std::{path::PathBuf, sync::Arc};
 use std::time::{Duration, Instant};
 use tokio::{sync::{broadcast::Sender, RwLock, watch}, try_join};
 
@@ -58,12 +58,15 @@ pub struct AppState {
     pub client: Arc<Client>,
     pub controls: Controls,
     pub position_receiver: PositionReceiver,
+    pub levels_receiver: LevelsReceiver,
     pub tracklist_receiver: TracklistReceiver,
     pub status_receiver: StatusReceiver,
+    pub sleep_timer_receiver: SleepTimerReceiver,
     pub volume_receiver: VolumeReceiver,
     pub templates: watch::Receiver<Templates>,
     pub database: Arc<Database>,
     pub exit_sender: ExitSender,
+    pub audio_cache_dir: PathBuf,
     pub library_cache: Arc<RwLock<LibraryCache>>,
 }
 
@@ -97,7 +100,10 @@ impl AppState {
                 _ => max_quality.clone(),
             },
         };
-        let audio_quality_display = audio_quality_display(effective_quality);
+        let audio_quality_display = match current_track.and_then(|t| t.stream_format.as_ref()) {
+            Some(stream_format) => stream_format_display(stream_format),
+            None => audio_quality_display(effective_quality),
+        };
 
         let playing_info = PlayingInfo {
             title,
@@ -230,6 +236,34 @@ fn audio_quality_display(quality: AudioQuality) -> AudioQualityDisplay {
     }
 }
 
+fn stream_format_display(stream_format: &StreamFormat) -> AudioQualityDisplay {
+    let codec = stream_format.codec();
+
+    if stream_format.is_lossy() {
+        return AudioQualityDisplay {
+            icon: "/assets/svg/mp3.svg".into(),
+            line1: format!("{codec} 320 kbps"),
+            line2: String::new(),
+        };
+    }
+
+    let icon = if stream_format.bit_depth > 16 || stream_format.sampling_rate > 48.0 {
+        "/assets/logo-hires.png"
+    } else {
+        "/assets/svg/cd.svg"
+    };
+
+    AudioQualityDisplay {
+        icon: icon.into(),
+        line1: format!("{codec} {}-Bit", stream_format.bit_depth),
+        line2: if stream_format.bit_perfect {
+            format!("{}kHz · Bit-perfect", stream_format.sampling_rate)
+        } else {
+            format!("{}kHz", stream_format.sampling_rate)
+        },
+    }
+}
+
 fn merge_serialized<T: serde::Serialize, Y: serde::Serialize>(
     info: &T,
     extra: &Y,
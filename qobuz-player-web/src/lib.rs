@@ -2,7 +2,7 @@ use assets::static_handler;
 use axum::{
     Router,
     extract::State,
-    http::{HeaderMap, StatusCode},
+    http::{HeaderMap, StatusCode, header},
     response::{Html, IntoResponse, Response, Sse, sse::Event},
     routing::{get, post},
     Form,
@@ -10,30 +10,31 @@ use axum::{
 use futures::stream::Stream;
 use qobuz_player_client::client::AudioQuality;
 use qobuz_player_controls::{
-    ExitSender, PositionReceiver, Result, Status, StatusReceiver, TracklistReceiver, VolumeReceiver,
+    ExitSender, LevelsReceiver, PositionReceiver, Result, SleepTimerReceiver, Status, StatusReceiver, TracklistReceiver, VolumeReceiver,
     client::Client,
     controls::Controls,
     database::Database,
     error::Error,
     notification::{Notification, NotificationBroadcast},
+    playlist_file::{PlaylistFormat, export_tracks},
 };
-use qobuz_player_models::{Album, AlbumSimple, Playlist};
+use qobuz_player_models::{Album, AlbumSimple, Playlist, Track};
 use qobuz_player_rfid::RfidState;
 use serde_json::json;
 use skabelon::Templates;
-use std::{convert::Infallible, env, sync::Arc};
+use std::{convert::Infallible, env, path::PathBuf, sync::Arc};
 use tokio::sync::{
     broadcast::{self, Receiver, Sender},
     watch, RwLock,
 };
 use tokio_stream::StreamExt as _;
-use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::wrappers::{BroadcastStream, WatchStream};
 
 use crate::{
     app_state::AppState,
     routes::{
-        album, api, artist, auth, controls, discover, genre, library, now_playing, playlist, queue,
-        search, settings,
+        album, api, artist, auth, controls, discover, genre, library, local_playlist, now_playing,
+        playlist, queue, search, settings,
     },
     views::templates,
 };
@@ -216,9 +217,11 @@ fn create_auth_success_response() -> Response {
 pub async fn init(
     controls: Controls,
     position_receiver: PositionReceiver,
+    levels_receiver: LevelsReceiver,
     tracklist_receiver: TracklistReceiver,
     volume_receiver: VolumeReceiver,
     status_receiver: StatusReceiver,
+    sleep_timer_receiver: SleepTimerReceiver,
     port: u16,
     web_secret: Option<String>,
     rfid_state: Option<RfidState>,
@@ -226,6 +229,7 @@ pub async fn init(
     client: Arc<Client>,
     database: Arc<Database>,
     exit_sender: ExitSender,
+    audio_cache_dir: PathBuf,
 ) -> Result<()> {
     let interface = format!("0.0.0.0:{port}");
     let listener = tokio::net::TcpListener::bind(&interface)
@@ -235,15 +239,18 @@ pub async fn init(
     let router = create_router(
         controls,
         position_receiver,
+        levels_receiver,
         tracklist_receiver,
         volume_receiver,
         status_receiver,
+        sleep_timer_receiver,
         web_secret,
         rfid_state,
         broadcast,
         client,
         database,
         exit_sender,
+        audio_cache_dir,
     )
     .await;
 
@@ -255,15 +262,18 @@ pub async fn init(
 async fn create_router(
     controls: Controls,
     position_receiver: PositionReceiver,
+    levels_receiver: LevelsReceiver,
     tracklist_receiver: TracklistReceiver,
     volume_receiver: VolumeReceiver,
     status_receiver: StatusReceiver,
+    sleep_timer_receiver: SleepTimerReceiver,
     web_secret: Option<String>,
     rfid_state: Option<RfidState>,
     broadcast: Arc<NotificationBroadcast>,
     client: Arc<Client>,
     database: Arc<Database>,
     exit_sender: ExitSender,
+    audio_cache_dir: PathBuf,
 ) -> Router {
     let (tx, _rx) = broadcast::channel::<ServerSentEvent>(100);
     let broadcast_subscribe = broadcast.subscribe();
@@ -316,12 +326,15 @@ async fn create_router(
         client,
         tx: tx.clone(),
         position_receiver: position_receiver.clone(),
+        levels_receiver,
         tracklist_receiver: tracklist_receiver.clone(),
         volume_receiver: volume_receiver.clone(),
         status_receiver: status_receiver.clone(),
+        sleep_timer_receiver: sleep_timer_receiver.clone(),
         templates: templates_rx.clone(),
         database,
         exit_sender,
+        audio_cache_dir,
         library_cache: Arc::new(RwLock::new(app_state::LibraryCache::new())),
     });
 
@@ -332,11 +345,13 @@ async fn create_router(
         tracklist_receiver,
         volume_receiver,
         status_receiver,
+        sleep_timer_receiver,
         templates_rx,
     ));
 
     axum::Router::new()
         .route("/sse", get(sse_handler))
+        .route("/sse/levels", get(levels_sse_handler))
         .merge(now_playing::routes())
         .merge(queue::routes())
         .merge(api::routes())
@@ -345,6 +360,7 @@ async fn create_router(
         .merge(album::routes())
         .merge(artist::routes())
         .merge(playlist::routes())
+        .merge(local_playlist::routes())
         .merge(genre::routes())
         .merge(library::routes())
         .merge(controls::routes())
@@ -358,6 +374,7 @@ async fn create_router(
         .with_state(shared_state.clone())
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn background_task(
     tx: Sender<ServerSentEvent>,
     mut receiver: Receiver<Notification>,
@@ -365,6 +382,7 @@ async fn background_task(
     mut tracklist: TracklistReceiver,
     mut volume: VolumeReceiver,
     mut status: StatusReceiver,
+    mut sleep_timer: SleepTimerReceiver,
     templates: watch::Receiver<Templates>,
 ) {
     loop {
@@ -409,6 +427,14 @@ async fn background_task(
                 };
                 _ = tx.send(event);
             }
+            Ok(_) = sleep_timer.changed() => {
+                let remaining = *sleep_timer.borrow_and_update();
+                let event = ServerSentEvent {
+                    event_name: "sleep-timer".into(),
+                    event_data: remaining.map(controls::format_remaining).unwrap_or_default(),
+                };
+                _ = tx.send(event);
+            }
             notification_result = receiver.recv() => {
                 if let Ok(notification) = notification_result {
                     let (message_string, severity) = match &notification {
@@ -457,6 +483,26 @@ async fn sse_handler(
     (headers, Sse::new(stream))
 }
 
+/// Output levels are only streamed to clients that ask for them, so the
+/// metering rate never crowds out events on the shared stream.
+async fn levels_sse_handler(
+    State(state): State<Arc<AppState>>,
+) -> (
+    axum::http::HeaderMap,
+    Sse<impl Stream<Item = Result<Event, Infallible>>>,
+) {
+    let stream = WatchStream::from_changes(state.levels_receiver.clone()).map(|levels| {
+        Ok(Event::default()
+            .event("levels")
+            .data(serde_json::to_string(&levels).expect("infallible")))
+    });
+
+    let mut headers = axum::http::HeaderMap::new();
+    headers.insert("X-Accel-Buffering", "no".parse().expect("infallible"));
+
+    (headers, Sse::new(stream))
+}
+
 #[derive(Clone)]
 pub struct AlbumData {
     pub album: Album,
@@ -524,6 +570,38 @@ fn ok_or_broadcast<T>(
     }
 }
 
+/// Playlist file download, 404 for unknown formats.
+fn playlist_file_response(format: &str, title: &str, tracks: &[Track]) -> Response {
+    let Ok(format) = format.parse::<PlaylistFormat>() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let file_name: String = title
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, ' ' | '-' | '_') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let disposition = format!(
+        "attachment; filename=\"{}.{}\"",
+        file_name.trim(),
+        format.extension()
+    );
+
+    (
+        [
+            (header::CONTENT_TYPE, format.mime_type().to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        export_tracks(format, title, tracks),
+    )
+        .into_response()
+}
+
 pub fn hx_redirect(url: &str) -> Response {
     let mut headers = HeaderMap::new();
     headers.insert("HX-Redirect", url.parse().unwrap());
@@ -6,6 +6,7 @@ pub mod controls;
 pub mod discover;
 pub mod genre;
 pub mod library;
+pub mod local_playlist;
 pub mod now_playing;
 pub mod playlist;
 pub mod queue;
@@ -1,14 +1,20 @@
-use std::sync::Arc;
+use std::{path::PathBuf, sync::Arc};
 
 use axum::{
     Router,
     extract::{Path, State},
     response::IntoResponse,
-    routing::{get, put},
+    routing::{get, post, put},
+};
+use qobuz_player_controls::{
+    export::{DEFAULT_EXPORT_TEMPLATE, export_album},
+    notification::Notification,
 };
 use serde_json::json;
 
-use crate::{AppState, ResponseResult, ok_or_send_error_toast};
+use crate::{
+    AppState, ResponseResult, ok_or_error_page, ok_or_send_error_toast, playlist_file_response,
+};
 
 pub fn routes() -> Router<std::sync::Arc<crate::AppState>> {
     Router::new()
@@ -20,6 +26,8 @@ pub fn routes() -> Router<std::sync::Arc<crate::AppState>> {
         .route("/album/{id}/play", put(play))
         .route("/album/{id}/play/{track_position}", put(play_track))
         .route("/album/{id}/link", put(link))
+        .route("/album/{id}/export", post(export))
+        .route("/album/{id}/export/{format}", get(export_file))
 }
 
 async fn play_track(
@@ -59,6 +67,53 @@ async fn play(State(state): State<Arc<AppState>>, Path(id): Path<String>) -> imp
     state.controls.play_album(&id, 0);
 }
 
+async fn export(State(state): State<Arc<AppState>>, Path(id): Path<String>) -> ResponseResult {
+    let config = ok_or_send_error_toast(&state, state.database.get_configuration().await)?;
+
+    let Some(export_directory) = config.export_directory else {
+        return Ok(state.send_toast(Notification::Warning(
+            "Set an export directory in settings first".into(),
+        )));
+    };
+    let template = config
+        .export_template
+        .unwrap_or_else(|| DEFAULT_EXPORT_TEMPLATE.to_string());
+
+    let client = state.client.clone();
+    let broadcast = state.broadcast.clone();
+    let audio_cache_dir = state.audio_cache_dir.clone();
+    tokio::spawn(async move {
+        let export_directory = PathBuf::from(export_directory);
+        match export_album(&client, &audio_cache_dir, &id, &export_directory, &template).await {
+            Ok(report) if report.not_cached.is_empty() && report.untagged.is_empty() => {
+                broadcast.send(Notification::Success(format!(
+                    "Exported {} tracks",
+                    report.exported.len()
+                )));
+            }
+            Ok(report) => {
+                broadcast.send(Notification::Warning(format!(
+                    "Exported {} tracks. {} not cached, {} could not be tagged",
+                    report.exported.len(),
+                    report.not_cached.len(),
+                    report.untagged.len()
+                )));
+            }
+            Err(e) => broadcast.send_error(e.to_string()),
+        }
+    });
+
+    Ok(state.send_toast(Notification::Info("Exporting album".into())))
+}
+
+async fn export_file(
+    State(state): State<Arc<AppState>>,
+    Path((id, format)): Path<(String, String)>,
+) -> ResponseResult {
+    let album = ok_or_error_page(&state, state.client.album(&id).await)?;
+    Ok(playlist_file_response(&format, &album.title, &album.tracks))
+}
+
 async fn link(State(state): State<Arc<AppState>>, Path(id): Path<String>) -> impl IntoResponse {
     let Some(rfid_state) = state.rfid_state.clone() else {
         return;
@@ -84,6 +139,7 @@ async fn content(State(state): State<Arc<AppState>>, Path(id): Path<String>) ->
     let duration = album_data.album.duration_seconds / 60;
 
     let click_string = format!("/album/{}/play/", album_data.album.id);
+    let export_url = format!("/album/{}/export", album_data.album.id);
 
     Ok(state.render(
         "album.html",
@@ -93,7 +149,8 @@ async fn content(State(state): State<Arc<AppState>>, Path(id): Path<String>) ->
             "suggested_albums": album_data.suggested_albums,
             "is_favorite": is_favorite,
             "rfid": state.rfid_state.is_some(),
-            "click": click_string
+            "click": click_string,
+            "export_url": export_url
         }),
     ))
 }
@@ -5,13 +5,17 @@ use std::{
 };
 
 use axum::{
-    Router,
+    Json, Router,
     extract::{Path, State},
+    http::{StatusCode, header},
     response::IntoResponse,
-    routing::{post, put},
+    routing::{get, post, put},
 };
 use axum_extra::extract::Form;
-use qobuz_player_controls::notification::Notification;
+use qobuz_player_controls::{
+    notification::Notification,
+    waveform::{Waveform, stored_waveform, track_waveform},
+};
 use serde::Deserialize;
 
 use crate::{AppState, ResponseResult, hx_redirect, ok_or_send_error_toast};
@@ -35,6 +39,45 @@ pub fn routes() -> Router<Arc<AppState>> {
         .route("/api/track/play/{track_id}", put(play_track))
         .route("/api/track/action", put(track_action))
         .route("/api/queue/reorder", put(reorder_queue))
+        .route("/api/queue/stop-after/{index}", put(toggle_stop_after))
+        .route("/api/waveform/{track_id}", get(waveform_json))
+        .route("/api/waveform/{track_id}/binary", get(waveform_binary))
+}
+
+async fn waveform_json(
+    State(state): State<Arc<AppState>>,
+    Path(track_id): Path<u32>,
+) -> ResponseResult {
+    let waveform = waveform(&state, track_id).await?;
+    Ok(Json(waveform).into_response())
+}
+
+async fn waveform_binary(
+    State(state): State<Arc<AppState>>,
+    Path(track_id): Path<u32>,
+) -> ResponseResult {
+    let waveform = waveform(&state, track_id).await?;
+    Ok((
+        [(header::CONTENT_TYPE, "application/octet-stream")],
+        waveform.to_bytes(),
+    )
+        .into_response())
+}
+
+#[allow(clippy::result_large_err)]
+async fn waveform(state: &AppState, track_id: u32) -> Result<Waveform, axum::response::Response> {
+    if let Some(waveform) = stored_waveform(&state.audio_cache_dir, track_id) {
+        return Ok(waveform);
+    }
+
+    let internal_error = |e: qobuz_player_controls::error::Error| {
+        (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+    };
+    let track = state.client.track(track_id).await.map_err(internal_error)?;
+    track_waveform(&track, &state.audio_cache_dir)
+        .await
+        .map_err(internal_error)?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Track is not cached").into_response())
 }
 
 #[derive(Debug, Deserialize)]
@@ -100,6 +143,15 @@ async fn reorder_queue(
     state.controls.reorder_queue(req.new_order);
 }
 
+async fn toggle_stop_after(
+    State(state): State<Arc<AppState>>,
+    Path(index): Path<usize>,
+) -> impl IntoResponse {
+    let stop_after = state.tracklist_receiver.borrow().stop_after;
+    let position = (stop_after != Some(index)).then_some(index);
+    state.controls.set_stop_after(position);
+}
+
 async fn remove_index_from_queue(
     State(state): State<Arc<AppState>>,
     Path(index): Path<usize>,
@@ -1,13 +1,91 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
-use axum::{Router, extract::State, response::IntoResponse, routing::get};
+use axum::{
+    Form, Router,
+    extract::State,
+    response::IntoResponse,
+    routing::{get, post},
+};
+use qobuz_player_controls::{
+    controls::{MAX_SLEEP_TIMER, SleepTimer},
+    notification::Notification,
+};
+use serde::Deserialize;
+use serde_json::json;
 
 use crate::AppState;
 
+#[derive(Deserialize)]
+struct SleepTimerForm {
+    timer: String,
+}
+
 pub fn routes() -> Router<std::sync::Arc<crate::AppState>> {
-    Router::new().route("/controls", get(controls))
+    Router::new()
+        .route("/controls", get(controls))
+        .route("/controls/sleep-timer", get(sleep_timer))
+        .route("/controls/sleep-timer", post(set_sleep_timer))
 }
 
 async fn controls(State(state): State<Arc<AppState>>) -> impl IntoResponse {
     state.render("controls.html", &())
 }
+
+async fn sleep_timer(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let remaining = *state.sleep_timer_receiver.borrow();
+    state.render(
+        "sleep-timer.html",
+        &json!({"remaining": remaining.map(format_remaining)}),
+    )
+}
+
+async fn set_sleep_timer(
+    State(state): State<Arc<AppState>>,
+    Form(form): Form<SleepTimerForm>,
+) -> impl IntoResponse {
+    let timer = match form.timer.as_str() {
+        "end" => Some(SleepTimer::EndOfTracklist),
+        "off" => None,
+        minutes => match parse_minutes(minutes) {
+            Some(duration) => Some(SleepTimer::After(duration)),
+            None => {
+                state.broadcast.send(Notification::Warning(format!(
+                    "Invalid sleep timer: {minutes}"
+                )));
+                return sleep_timer(State(state)).await;
+            }
+        },
+    };
+    state.controls.set_sleep_timer(timer);
+
+    sleep_timer(State(state)).await
+}
+
+/// A whole number of minutes, up to the longest sleep timer.
+fn parse_minutes(minutes: &str) -> Option<Duration> {
+    minutes
+        .parse::<u64>()
+        .ok()
+        .and_then(|minutes| minutes.checked_mul(60))
+        .map(Duration::from_secs)
+        .filter(|duration| *duration <= MAX_SLEEP_TIMER)
+}
+
+pub fn format_remaining(remaining: Duration) -> String {
+    let seconds = remaining.as_secs();
+    format!("{:02}:{:02}", seconds / 60, seconds % 60)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_sleep_timer_minutes() {
+        assert_eq!(parse_minutes("15"), Some(Duration::from_secs(15 * 60)));
+        assert_eq!(parse_minutes("1440"), Some(MAX_SLEEP_TIMER));
+        assert_eq!(parse_minutes("1441"), None);
+        assert_eq!(parse_minutes(&u64::MAX.to_string()), None);
+        assert_eq!(parse_minutes("soon"), None);
+    }
+}
@@ -24,9 +24,20 @@ pub fn routes() -> Router<std::sync::Arc<crate::AppState>> {
 
 async fn index(State(state): State<Arc<AppState>>, Path(tab): Path<Tab>) -> ResponseResult {
     let library = ok_or_error_page(&state, state.get_library().await)?;
+    let local_playlists = ok_or_error_page(&state, state.database.get_local_playlists().await)?
+        .into_iter()
+        .map(|playlist| {
+            json!({
+                "id": playlist.id,
+                "title": playlist.title,
+                "image": playlist.image(),
+                "tracks_count": playlist.entries.len(),
+            })
+        })
+        .collect::<Vec<_>>();
 
     Ok(state.render(
         "library.html",
-        &json!({"library": library, "tab": tab}),
+        &json!({"library": library, "local_playlists": local_playlists, "tab": tab}),
     ))
 }
@@ -0,0 +1,256 @@
+use std::sync::Arc;
+
+use axum::{
+    Router,
+    extract::{Path, State},
+    response::IntoResponse,
+    routing::{get, post, put},
+};
+use axum_extra::extract::Form;
+use qobuz_player_controls::{database::LocalPlaylist, notification::Notification};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{
+    AppState, ResponseResult, hx_redirect, ok_or_send_error_toast, routes::playlist::moved_index,
+};
+
+pub fn routes() -> Router<std::sync::Arc<crate::AppState>> {
+    Router::new()
+        .route("/local-playlist/create", post(create))
+        .route("/local-playlist/{id}", get(index).delete(delete))
+        .route("/local-playlist/{id}/content", get(content))
+        .route("/local-playlist/{id}/tracks", get(tracks_partial))
+        .route("/local-playlist/{id}/tracks/edit", get(edit_tracks_partial))
+        .route("/local-playlist/{id}/rename", post(rename))
+        .route("/local-playlist/{id}/play", put(play))
+        .route("/local-playlist/{id}/play/shuffle", put(shuffle))
+        .route(
+            "/local-playlist/{id}/play/{track_position}",
+            put(play_track),
+        )
+        .route("/local-playlist/add-track", post(add_track))
+        .route("/local-playlist/remove-track", post(remove_track))
+        .route("/local-playlist/reorder", post(reorder_tracks))
+        .route("/local-playlist/set-note", post(set_note))
+}
+
+#[derive(Deserialize)]
+struct TitleForm {
+    title: String,
+}
+
+#[derive(Deserialize)]
+struct AddTrackParameters {
+    track_id: u32,
+    playlist_id: i64,
+}
+
+#[derive(Deserialize)]
+struct EntryParameters {
+    playlist_id: i64,
+    position: usize,
+}
+
+#[derive(Deserialize)]
+struct NoteParameters {
+    playlist_id: i64,
+    position: usize,
+    note: String,
+}
+
+#[derive(Deserialize)]
+struct ReorderParameters {
+    playlist_id: i64,
+    new_order: Vec<usize>,
+}
+
+async fn get_playlist(
+    state: &AppState,
+    id: i64,
+) -> Result<LocalPlaylist, axum::response::Response> {
+    let playlist = ok_or_send_error_toast(state, state.database.get_local_playlist(id).await)?;
+    playlist
+        .ok_or_else(|| state.send_toast(Notification::Warning("Local playlist not found".into())))
+}
+
+fn playlist_json(playlist: &LocalPlaylist) -> serde_json::Value {
+    json!({
+        "id": playlist.id,
+        "title": playlist.title,
+        "image": playlist.image(),
+        "entries": playlist.entries,
+    })
+}
+
+async fn create(State(state): State<Arc<AppState>>, Form(req): Form<TitleForm>) -> ResponseResult {
+    let title = req.title.trim();
+    if title.is_empty() {
+        return Ok(state.send_toast(Notification::Warning("Name the playlist".into())));
+    }
+
+    let id = ok_or_send_error_toast(&state, state.database.create_local_playlist(title).await)?;
+    Ok(hx_redirect(&format!("/local-playlist/{id}")))
+}
+
+async fn rename(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    Form(req): Form<TitleForm>,
+) -> ResponseResult {
+    let title = req.title.trim();
+    if title.is_empty() {
+        return Ok(state.send_toast(Notification::Warning("Name the playlist".into())));
+    }
+
+    ok_or_send_error_toast(
+        &state,
+        state.database.rename_local_playlist(id, title).await,
+    )?;
+    Ok(state.send_toast(Notification::Success(format!("Renamed to {title}"))))
+}
+
+async fn delete(State(state): State<Arc<AppState>>, Path(id): Path<i64>) -> ResponseResult {
+    ok_or_send_error_toast(&state, state.database.remove_local_playlist(id).await)?;
+    Ok(hx_redirect("/library/playlists"))
+}
+
+async fn play(State(state): State<Arc<AppState>>, Path(id): Path<i64>) -> impl IntoResponse {
+    state.controls.play_local_playlist(id, 0, false);
+}
+
+async fn shuffle(State(state): State<Arc<AppState>>, Path(id): Path<i64>) -> impl IntoResponse {
+    state.controls.play_local_playlist(id, 0, true);
+}
+
+async fn play_track(
+    State(state): State<Arc<AppState>>,
+    Path((id, track_position)): Path<(i64, usize)>,
+) -> impl IntoResponse {
+    state
+        .controls
+        .play_local_playlist(id, track_position, false);
+}
+
+async fn index(State(state): State<Arc<AppState>>, Path(id): Path<i64>) -> impl IntoResponse {
+    let url = format!("/local-playlist/{id}/content");
+    state.render("lazy-load-component.html", &json!({"url": url}))
+}
+
+async fn content(State(state): State<Arc<AppState>>, Path(id): Path<i64>) -> ResponseResult {
+    let playlist = get_playlist(&state, id).await?;
+    let duration = playlist.duration_seconds() / 60;
+
+    Ok(state.render(
+        "local-playlist.html",
+        &json!({
+            "playlist": playlist_json(&playlist),
+            "duration": duration,
+        }),
+    ))
+}
+
+async fn tracks_partial(State(state): State<Arc<AppState>>, Path(id): Path<i64>) -> ResponseResult {
+    let playlist = get_playlist(&state, id).await?;
+
+    Ok(state.render(
+        "local-playlist-tracks.html",
+        &json!({"playlist": playlist_json(&playlist)}),
+    ))
+}
+
+async fn edit_tracks_partial(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> ResponseResult {
+    let playlist = get_playlist(&state, id).await?;
+
+    Ok(state.render(
+        "local-playlist-edit-tracks.html",
+        &json!({"playlist": playlist_json(&playlist)}),
+    ))
+}
+
+async fn add_track(
+    State(state): State<Arc<AppState>>,
+    Form(req): Form<AddTrackParameters>,
+) -> ResponseResult {
+    let playlist = get_playlist(&state, req.playlist_id).await?;
+    let track = ok_or_send_error_toast(&state, state.client.track(req.track_id).await)?;
+    ok_or_send_error_toast(
+        &state,
+        state
+            .database
+            .add_local_playlist_track(req.playlist_id, &track)
+            .await,
+    )?;
+
+    Ok(state.send_toast(Notification::Success(format!(
+        "Added to {}",
+        playlist.title
+    ))))
+}
+
+async fn remove_track(
+    State(state): State<Arc<AppState>>,
+    Form(req): Form<EntryParameters>,
+) -> ResponseResult {
+    ok_or_send_error_toast(
+        &state,
+        state
+            .database
+            .remove_local_playlist_entry(req.playlist_id, req.position)
+            .await,
+    )?;
+    let playlist = get_playlist(&state, req.playlist_id).await?;
+
+    Ok(state.render(
+        "local-playlist-edit-tracks.html",
+        &json!({"playlist": playlist_json(&playlist)}),
+    ))
+}
+
+async fn reorder_tracks(
+    State(state): State<Arc<AppState>>,
+    Form(req): Form<ReorderParameters>,
+) -> ResponseResult {
+    let Some(moved) = moved_index(&req.new_order) else {
+        return Ok(([("HX-Reswap", "none")], "").into_response());
+    };
+    let Some(to) = req
+        .new_order
+        .iter()
+        .position(|index| *index == moved.moved_index)
+    else {
+        return Ok(([("HX-Reswap", "none")], "").into_response());
+    };
+
+    ok_or_send_error_toast(
+        &state,
+        state
+            .database
+            .move_local_playlist_entry(req.playlist_id, moved.moved_index, to)
+            .await,
+    )?;
+    let playlist = get_playlist(&state, req.playlist_id).await?;
+
+    Ok(state.render(
+        "local-playlist-edit-tracks.html",
+        &json!({"playlist": playlist_json(&playlist)}),
+    ))
+}
+
+async fn set_note(
+    State(state): State<Arc<AppState>>,
+    Form(req): Form<NoteParameters>,
+) -> ResponseResult {
+    ok_or_send_error_toast(
+        &state,
+        state
+            .database
+            .set_local_playlist_note(req.playlist_id, req.position, Some(&req.note))
+            .await,
+    )?;
+
+    Ok(state.send_toast(Notification::Success("Note saved".into())))
+}
@@ -1,20 +1,35 @@
-use std::sync::Arc;
+use std::{path::PathBuf, sync::Arc, time::Duration};
 
 use axum::{
-    Router,
+    Form, Router,
     extract::State,
     response::{IntoResponse, Response},
-    routing::get,
+    routing::{get, post},
 };
+use qobuz_player_controls::{
+    chords::{track_chords, transpose_semitones},
+    notification::Notification,
+    render::{RenderSection, render_track},
+};
+use serde::Deserialize;
 use serde_json::json;
 
-use crate::AppState;
+use crate::{AppState, ResponseResult, ok_or_send_error_toast};
+
+#[derive(Deserialize)]
+struct RenderForm {
+    start: Option<String>,
+    end: Option<String>,
+}
 
 pub fn routes() -> Router<std::sync::Arc<crate::AppState>> {
     Router::new()
         .route("/", get(index))
         .route("/status", get(status_partial))
         .route("/now-playing", get(now_playing_partial))
+        .route("/now-playing/render", post(render))
+        .route("/now-playing/pitch-probe", post(pitch_probe))
+        .route("/now-playing/chords", get(chords))
 }
 
 async fn index(State(state): State<Arc<AppState>>) -> impl IntoResponse {
@@ -43,6 +58,147 @@ async fn now_playing_partial(State(state): State<Arc<AppState>>) -> impl IntoRes
     now_playing(&state, true, time_stretch_ratio)
 }
 
+async fn render(State(state): State<Arc<AppState>>, Form(form): Form<RenderForm>) -> ResponseResult {
+    let current_track = state.tracklist_receiver.borrow().current_track().cloned();
+    let Some(track) = current_track else {
+        return Ok(state.send_toast(Notification::Warning("Nothing is playing".into())));
+    };
+
+    let config = ok_or_send_error_toast(&state, state.database.get_configuration().await)?;
+    let Some(export_directory) = config.export_directory else {
+        return Ok(state.send_toast(Notification::Warning(
+            "Set an export directory in settings first".into(),
+        )));
+    };
+
+    let seconds = |value: Option<String>| match value.as_deref().map(str::trim) {
+        None | Some("") => Ok(None),
+        Some(value) => value
+            .parse::<f64>()
+            .map(Some)
+            .map_err(|_| value.to_string()),
+    };
+    let section = match (seconds(form.start), seconds(form.end)) {
+        (Ok(start), Ok(end)) => RenderSection::from_secs(start, end),
+        (Err(value), _) | (_, Err(value)) => {
+            return Ok(state.send_toast(Notification::Warning(format!(
+                "'{value}' is not a number of seconds"
+            ))));
+        }
+    };
+    let section = match section {
+        Ok(section) => section,
+        Err(e) => return Ok(state.send_toast(Notification::Warning(e.to_string()))),
+    };
+
+    let client = state.client.clone();
+    let database = state.database.clone();
+    let broadcast = state.broadcast.clone();
+    let audio_cache_dir = state.audio_cache_dir.clone();
+    let title = track.title.clone();
+    tokio::spawn(async move {
+        let export_directory = PathBuf::from(export_directory);
+        match render_track(
+            &client,
+            &database,
+            &audio_cache_dir,
+            track.id,
+            section,
+            &export_directory,
+        )
+        .await
+        {
+            Ok(report) => broadcast.send(Notification::Success(format!(
+                "Rendered '{}' ({:.0}s)",
+                title,
+                report.duration.as_secs_f64()
+            ))),
+            Err(e) => broadcast.send_error(e.to_string()),
+        }
+    });
+
+    Ok(state.send_toast(Notification::Info(format!("Rendering '{}'", track.title))))
+}
+
+async fn pitch_probe(State(state): State<Arc<AppState>>) -> ResponseResult {
+    let probe = ok_or_send_error_toast(&state, state.controls.probe_pitch().await)?;
+
+    let pitches = probe
+        .pitches
+        .iter()
+        .map(|pitch| {
+            json!({
+                "note": pitch.note,
+                "cents": format!("{:+}", pitch.cents),
+                "strength": (pitch.strength * 100.0).round(),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    Ok(state.render(
+        "pitch-probe.html",
+        &json!({
+            "position": mseconds_to_mm_ss(probe.position.as_millis()),
+            "has_pitches": !pitches.is_empty(),
+            "pitches": pitches,
+        }),
+    ))
+}
+
+async fn chords(State(state): State<Arc<AppState>>) -> ResponseResult {
+    let current_track = state.tracklist_receiver.borrow().current_track().cloned();
+    let Some(track) = current_track else {
+        return Ok(state.render("chords.html", &json!({})));
+    };
+
+    let config = ok_or_send_error_toast(&state, state.database.get_configuration().await)?;
+    let timeline = ok_or_send_error_toast(
+        &state,
+        state.database.get_chord_timeline(track.id).await,
+    )?;
+
+    let Some(timeline) = timeline else {
+        let background_state = state.clone();
+        tokio::spawn(async move {
+            let state = background_state;
+            match track_chords(&state.database, &track, &state.audio_cache_dir).await {
+                Ok(Some(_)) => state.send_sse("chords".into(), "Chords analysed".into()),
+                Ok(None) => {}
+                Err(e) => tracing::warn!("Unable to analyse chords: {e}"),
+            }
+        });
+        return Ok(state.render("chords.html", &json!({"pending": true})));
+    };
+
+    let transpose = transpose_semitones(config.pitch_semitones, config.pitch_cents);
+    let position = Duration::from_secs_f64(
+        state.position_receiver.borrow().as_secs_f64() * config.time_stretch_ratio as f64,
+    );
+    let (current, next) = timeline.around(position);
+    let chords = timeline
+        .segments
+        .iter()
+        .map(|segment| {
+            json!({
+                "start": segment.start.as_secs_f64(),
+                "end": segment.end.as_secs_f64(),
+                "label": segment.label(transpose),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    Ok(state.render(
+        "chords.html",
+        &json!({
+            "has_chords": true,
+            "ratio": config.time_stretch_ratio,
+            "chords": serde_json::to_string(&chords).expect("infallible"),
+            "current": current.map(|segment| segment.label(transpose)),
+            "next": next.map(|segment| segment.label(transpose)),
+        }),
+    ))
+}
+
 fn now_playing(state: &AppState, partial: bool, time_stretch_ratio: f32) -> Response {
     let tracklist = state.tracklist_receiver.borrow().clone();
     let current_track = tracklist.current_track().cloned();
@@ -11,7 +11,10 @@ use qobuz_player_controls::{error::Error, notification::Notification};
 use serde::Deserialize;
 use serde_json::json;
 
-use crate::{AppState, ResponseResult, hx_redirect, ok_or_send_error_toast};
+use crate::{
+    AppState, ResponseResult, hx_redirect, ok_or_error_page, ok_or_send_error_toast,
+    playlist_file_response,
+};
 
 pub fn routes() -> Router<std::sync::Arc<crate::AppState>> {
     Router::new()
@@ -26,6 +29,7 @@ pub fn routes() -> Router<std::sync::Arc<crate::AppState>> {
         .route("/playlist/{id}/play/shuffle", put(shuffle))
         .route("/playlist/{id}/play/{track_position}", put(play_track))
         .route("/playlist/{id}/link", put(link))
+        .route("/playlist/{id}/export/{format}", get(export_file))
         .route("/playlist/add-track/{id}", get(add_track_to_playlist_page))
         .route(
             "/playlist/remove-track",
@@ -128,9 +132,15 @@ async fn add_track_to_playlist_page(
         .filter(|x| x.is_owned)
         .collect();
 
+    let local_playlists = state.database.get_local_playlists().await;
+    let local_playlists = ok_or_send_error_toast(&state, local_playlists)?
+        .into_iter()
+        .map(|playlist| json!({"id": playlist.id, "title": playlist.title}))
+        .collect::<Vec<_>>();
+
     Ok(state.render(
         "add-track-to-playlist.html",
-        &json!({"track": track, "playlists": playlists}),
+        &json!({"track": track, "playlists": playlists, "local_playlists": local_playlists}),
     ))
 }
 
@@ -226,6 +236,7 @@ async fn content(State(state): State<Arc<AppState>>, Path(id): Path<u32>) -> Res
     let is_favorite = library.playlists.iter().any(|playlist| playlist.id == id);
     let duration = playlist.duration_seconds / 60;
     let click_string = format!("/playlist/{}/play/", playlist.id);
+    let export_url = format!("/playlist/{}/export", playlist.id);
 
     Ok(state.render(
         "playlist.html",
@@ -234,11 +245,24 @@ async fn content(State(state): State<Arc<AppState>>, Path(id): Path<u32>) -> Res
             "duration": duration,
             "is_favorite": is_favorite,
             "rfid": state.rfid_state.is_some(),
-            "click": click_string
+            "click": click_string,
+            "export_url": export_url
         }),
     ))
 }
 
+async fn export_file(
+    State(state): State<Arc<AppState>>,
+    Path((id, format)): Path<(u32, String)>,
+) -> ResponseResult {
+    let playlist = ok_or_error_page(&state, state.client.playlist(id).await)?;
+    Ok(playlist_file_response(
+        &format,
+        &playlist.title,
+        &playlist.tracks,
+    ))
+}
+
 async fn tracks_partial(State(state): State<Arc<AppState>>, Path(id): Path<u32>) -> ResponseResult {
     let playlist = ok_or_send_error_toast(&state, state.client.playlist(id).await)?;
     let click_string = format!("/playlist/{}/play/", playlist.id);
@@ -1,19 +1,36 @@
 use std::sync::Arc;
 
 use axum::{
-    Router,
-    extract::State,
+    Form, Router,
+    extract::{Path, State},
     response::{IntoResponse, Response},
-    routing::get,
+    routing::{get, post},
 };
+use serde::Deserialize;
 use serde_json::json;
 
-use crate::app_state::AppState;
+use crate::{ResponseResult, app_state::AppState, ok_or_error_page, playlist_file_response};
 
 pub fn routes() -> Router<std::sync::Arc<crate::AppState>> {
     Router::new()
         .route("/queue", get(index))
         .route("/queue/partial", get(queue_partial))
+        .route("/queue/saved", get(saved_queues))
+        .route("/queue/save", post(save_queue))
+        .route("/queue/load", post(load_saved_queue))
+        .route("/queue/delete-saved", post(delete_saved_queue))
+        .route("/queue/export/{format}", get(export_file))
+}
+
+#[derive(Deserialize)]
+struct SaveQueueForm {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct LoadSavedQueueForm {
+    name: String,
+    append: Option<String>,
 }
 
 async fn index(State(state): State<Arc<AppState>>) -> impl IntoResponse {
@@ -28,13 +45,66 @@ fn queue(state: &AppState, partial: bool) -> Response {
     let tracklist = state.tracklist_receiver.borrow();
     let tracks = tracklist.queue().to_vec();
     let currently_playing_position = tracklist.current_position();
+    let stop_after = tracklist.stop_after;
 
     state.render(
         "queue.html",
         &json! ({
             "partial": partial,
             "tracks": tracks,
-            "currently_playing_position": currently_playing_position
+            "currently_playing_position": currently_playing_position,
+            "stop_after": stop_after
         }),
     )
 }
+
+async fn export_file(State(state): State<Arc<AppState>>, Path(format): Path<String>) -> Response {
+    let tracks = state.tracklist_receiver.borrow().queue().to_vec();
+    playlist_file_response(&format, "Queue", &tracks)
+}
+
+async fn render_saved_queues(state: &AppState) -> ResponseResult {
+    let queues = ok_or_error_page(state, state.database.get_saved_queues().await)?
+        .into_iter()
+        .map(|queue| {
+            json!({
+                "name": queue.name,
+                "total": queue.tracklist.total(),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    Ok(state.render("saved-queues.html", &json!({ "queues": queues })))
+}
+
+async fn saved_queues(State(state): State<Arc<AppState>>) -> ResponseResult {
+    render_saved_queues(&state).await
+}
+
+async fn save_queue(
+    State(state): State<Arc<AppState>>,
+    Form(form): Form<SaveQueueForm>,
+) -> ResponseResult {
+    state.controls.save_queue(&form.name).await;
+    render_saved_queues(&state).await
+}
+
+async fn load_saved_queue(
+    State(state): State<Arc<AppState>>,
+    Form(form): Form<LoadSavedQueueForm>,
+) -> impl IntoResponse {
+    state
+        .controls
+        .load_saved_queue(&form.name, form.append.is_some());
+}
+
+async fn delete_saved_queue(
+    State(state): State<Arc<AppState>>,
+    Form(form): Form<SaveQueueForm>,
+) -> ResponseResult {
+    if let Err(e) = state.database.remove_saved_queue(&form.name).await {
+        tracing::error!("Failed to delete saved queue: {}", e);
+        return ok_or_error_page(&state, Err(e));
+    }
+    render_saved_queues(&state).await
+}
@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{path::Path, sync::Arc, time::Duration};
 
 use axum::{
     extract::State,
@@ -7,10 +7,21 @@ use axum::{
     Router,
     Form,
 };
+use axum_extra::extract::Form as MultiValueForm;
 use serde::Deserialize;
 use serde_json::json;
 
-use qobuz_player_controls::{list_audio_devices, notification::Notification};
+use qobuz_player_controls::{
+    channel_mix::UpmixPreset,
+    clap::{ClapPlugin, DEFAULT_CHAIN, scan_plugins},
+    crossfeed::CrossfeedPreset,
+    export::DEFAULT_EXPORT_TEMPLATE,
+    get_default_device_name, list_audio_devices, notification::Notification,
+    output::{OutputBackend, OutputSampleFormat},
+    schedule::{
+        EVERY_DAY, MAX_RAMP_UP_MINUTES, ScheduleTarget, TimeOfDay, WEEKDAY_NAMES, parse_weekdays,
+    },
+};
 
 use crate::{AppState, ResponseResult, hx_redirect, ok_or_error_page};
 
@@ -39,6 +50,75 @@ struct SetPitchCentsForm {
     pitch_cents: Option<String>,
 }
 
+#[derive(Deserialize)]
+struct SetSampleFormatForm {
+    sample_format: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SetUpmixPresetForm {
+    upmix_preset: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SetCrossfeedForm {
+    crossfeed_mode: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SetCrossfeedPresetForm {
+    crossfeed_preset: Option<String>,
+    crossfeed_strength: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SetBitPerfectForm {
+    bit_perfect: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SetResumePlaybackForm {
+    resume_playback: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct AddPluginForm {
+    plugin: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RemovePluginForm {
+    position: u32,
+}
+
+#[derive(Deserialize)]
+struct SetPluginParamForm {
+    position: u32,
+    param_id: u32,
+    clap_param_value: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct AddScheduleForm {
+    target_kind: String,
+    target_id: String,
+    time: String,
+    #[serde(default)]
+    days: Vec<String>,
+    ramp_up_minutes: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RemoveScheduleForm {
+    id: i64,
+}
+
+#[derive(Deserialize)]
+struct SetExportForm {
+    export_directory: Option<String>,
+    export_template: Option<String>,
+}
+
 pub fn routes() -> Router<Arc<AppState>> {
     Router::new()
         .route("/settings", get(index))
@@ -50,6 +130,20 @@ pub fn routes() -> Router<Arc<AppState>> {
         .route("/settings/set-time-stretch", post(set_time_stretch))
         .route("/settings/set-pitch", post(set_pitch))
         .route("/settings/set-pitch-cents", post(set_pitch_cents))
+        .route("/settings/set-sample-format", post(set_sample_format))
+        .route("/settings/set-upmix-preset", post(set_upmix_preset))
+        .route("/settings/set-crossfeed", post(set_crossfeed))
+        .route("/settings/set-crossfeed-preset", post(set_crossfeed_preset))
+        .route("/settings/set-bit-perfect", post(set_bit_perfect))
+        .route("/settings/set-resume-playback", post(set_resume_playback))
+        .route("/settings/set-export", post(set_export))
+        .route("/settings/plugins", get(plugins))
+        .route("/settings/add-plugin", post(add_plugin))
+        .route("/settings/remove-plugin", post(remove_plugin))
+        .route("/settings/set-plugin-param", post(set_plugin_param))
+        .route("/settings/schedules", get(schedules))
+        .route("/settings/add-schedule", post(add_schedule))
+        .route("/settings/remove-schedule", post(remove_schedule))
         .route("/disconnected", get(disconnected))
 }
 
@@ -72,6 +166,45 @@ fn settings_context(
     let time_stretch_ratio_display = format!("{:.1}", time_stretch_ratio);
     let pitch_semitones = config.as_ref().map(|c| c.pitch_semitones).unwrap_or(0);
     let pitch_cents = config.as_ref().map(|c| c.pitch_cents).unwrap_or(0);
+    let selected_sample_format = config
+        .as_ref()
+        .and_then(|c| c.output_sample_formats.get(selected_device_name).copied())
+        .map(|format| format.to_string())
+        .unwrap_or_default();
+    let sample_formats = OutputSampleFormat::ALL
+        .iter()
+        .map(|format| format.to_string())
+        .collect::<Vec<_>>();
+    let selected_upmix_preset = config
+        .as_ref()
+        .map(|c| c.upmix_preset)
+        .unwrap_or_default()
+        .to_string();
+    let upmix_presets = UpmixPreset::ALL
+        .iter()
+        .map(|preset| preset.to_string())
+        .collect::<Vec<_>>();
+    let crossfeed = config.as_ref().map(|c| c.crossfeed.clone()).unwrap_or_default();
+    let crossfeed_mode = crossfeed_device(selected_device.clone())
+        .map(|device| match crossfeed.devices.get(&device) {
+            Some(true) => "on",
+            Some(false) => "off",
+            None if crossfeed.enabled_for(&device) => "auto-on",
+            None => "auto-off",
+        })
+        .unwrap_or("auto-off");
+    let crossfeed_presets = CrossfeedPreset::ALL
+        .iter()
+        .map(|preset| preset.to_string())
+        .collect::<Vec<_>>();
+    let crossfeed_strength = (crossfeed.strength * 100.0).round() as u32;
+    let bit_perfect = config.as_ref().is_some_and(|c| c.bit_perfect);
+    let resume_playback = config.as_ref().is_some_and(|c| c.resume_playback);
+    let export_directory = config.as_ref().and_then(|c| c.export_directory.clone());
+    let export_template = config
+        .as_ref()
+        .and_then(|c| c.export_template.clone())
+        .unwrap_or_else(|| DEFAULT_EXPORT_TEMPLATE.to_string());
     json!({
         "devices": devices,
         "selected_device": selected_device,
@@ -83,9 +216,25 @@ fn settings_context(
         "time_stretch_ratio_display": time_stretch_ratio_display,
         "pitch_semitones": pitch_semitones,
         "pitch_cents": pitch_cents,
+        "sample_formats": sample_formats,
+        "selected_sample_format": selected_sample_format,
+        "upmix_presets": upmix_presets,
+        "selected_upmix_preset": selected_upmix_preset,
+        "crossfeed_mode": crossfeed_mode,
+        "crossfeed_presets": crossfeed_presets,
+        "selected_crossfeed_preset": crossfeed.preset.to_string(),
+        "crossfeed_strength": crossfeed_strength,
+        "bit_perfect": bit_perfect,
+        "resume_playback": resume_playback,
+        "export_directory": export_directory,
+        "export_template": export_template,
     })
 }
 
+fn crossfeed_device(selected_device: Option<String>) -> Option<String> {
+    selected_device.or_else(|| get_default_device_name().ok().flatten())
+}
+
 async fn index(State(state): State<Arc<AppState>>) -> ResponseResult {
     let devices = list_audio_devices().unwrap_or_default();
     let config = state.database.get_configuration().await.ok();
@@ -118,15 +267,24 @@ async fn set_device(
     tracing::info!("Setting audio device to: {:?}", device_name);
     
     if let Some(ref name) = device_name {
-        let devices = list_audio_devices().unwrap_or_default();
-        if !devices.iter().any(|d| &d.name == name) {
-            tracing::warn!("Device '{}' not found", name);
+        let error = if OutputBackend::is_headless_device(name) {
+            // Null and wav outputs write wherever they are told, so they are
+            // only available from the command line
+            Some(format!("'{}' can only be chosen with --output", name))
+        } else {
+            let devices = list_audio_devices().unwrap_or_default();
+            (!devices.iter().any(|d| &d.name == name))
+                .then(|| format!("Device '{}' not found", name))
+        };
+
+        if let Some(error) = error {
+            tracing::warn!("{}", error);
             let devices = list_audio_devices().unwrap_or_default();
             let config = state.database.get_configuration().await.ok();
             let genres = state.client.genres().await.unwrap_or_default();
             let mut ctx = settings_context(devices, config, genres);
             if let Some(obj) = ctx.as_object_mut() {
-                obj.insert("error".to_string(), json!(format!("Device '{}' not found", name)));
+                obj.insert("error".to_string(), json!(error));
             }
             return Ok(state.render("settings-content.html", &ctx));
         }
@@ -245,6 +403,174 @@ async fn set_pitch_cents(
     Ok(state.render("settings-content.html", &context))
 }
 
+async fn set_sample_format(
+    State(state): State<Arc<AppState>>,
+    Form(form): Form<SetSampleFormatForm>,
+) -> ResponseResult {
+    let sample_format = form
+        .sample_format
+        .and_then(|s| s.parse::<OutputSampleFormat>().ok());
+    let device_name = state
+        .database
+        .get_configuration()
+        .await
+        .ok()
+        .and_then(|c| c.audio_device_name);
+    if let Err(e) = state
+        .database
+        .set_output_sample_format(device_name.as_deref(), sample_format)
+        .await
+    {
+        tracing::error!("Failed to set output sample format: {}", e);
+        return ok_or_error_page(&state, Err(e));
+    }
+    state
+        .controls
+        .set_output_sample_format(device_name, sample_format);
+    let devices = list_audio_devices().unwrap_or_default();
+    let config = state.database.get_configuration().await.ok();
+    let genres = state.client.genres().await.unwrap_or_default();
+    let context = settings_context(devices, config, genres);
+    Ok(state.render("settings-content.html", &context))
+}
+
+async fn set_upmix_preset(
+    State(state): State<Arc<AppState>>,
+    Form(form): Form<SetUpmixPresetForm>,
+) -> ResponseResult {
+    let preset = form
+        .upmix_preset
+        .and_then(|s| s.parse::<UpmixPreset>().ok())
+        .unwrap_or_default();
+    if let Err(e) = state.database.set_upmix_preset(preset).await {
+        tracing::error!("Failed to set upmix preset: {}", e);
+        return ok_or_error_page(&state, Err(e));
+    }
+    state.controls.set_upmix_preset(preset);
+    let devices = list_audio_devices().unwrap_or_default();
+    let config = state.database.get_configuration().await.ok();
+    let genres = state.client.genres().await.unwrap_or_default();
+    let context = settings_context(devices, config, genres);
+    Ok(state.render("settings-content.html", &context))
+}
+
+async fn set_crossfeed(
+    State(state): State<Arc<AppState>>,
+    Form(form): Form<SetCrossfeedForm>,
+) -> ResponseResult {
+    let enabled = match form.crossfeed_mode.as_deref() {
+        Some("on") => Some(true),
+        Some("off") => Some(false),
+        _ => None,
+    };
+    let selected_device = state
+        .database
+        .get_configuration()
+        .await
+        .ok()
+        .and_then(|c| c.audio_device_name);
+    if let Some(device_name) = crossfeed_device(selected_device) {
+        if let Err(e) = state
+            .database
+            .set_crossfeed_device(&device_name, enabled)
+            .await
+        {
+            tracing::error!("Failed to set crossfeed: {}", e);
+            return ok_or_error_page(&state, Err(e));
+        }
+        state.controls.set_crossfeed_device(device_name, enabled);
+    }
+    let devices = list_audio_devices().unwrap_or_default();
+    let config = state.database.get_configuration().await.ok();
+    let genres = state.client.genres().await.unwrap_or_default();
+    let context = settings_context(devices, config, genres);
+    Ok(state.render("settings-content.html", &context))
+}
+
+async fn set_crossfeed_preset(
+    State(state): State<Arc<AppState>>,
+    Form(form): Form<SetCrossfeedPresetForm>,
+) -> ResponseResult {
+    let preset = form
+        .crossfeed_preset
+        .and_then(|s| s.parse::<CrossfeedPreset>().ok())
+        .unwrap_or_default();
+    let strength = form
+        .crossfeed_strength
+        .and_then(|s| s.parse::<f32>().ok())
+        .map(|s| (s / 100.0).clamp(0.0, 1.0))
+        .unwrap_or(1.0);
+    if let Err(e) = state.database.set_crossfeed_preset(preset, strength).await {
+        tracing::error!("Failed to set crossfeed preset: {}", e);
+        return ok_or_error_page(&state, Err(e));
+    }
+    state.controls.set_crossfeed_preset(preset, strength);
+    let devices = list_audio_devices().unwrap_or_default();
+    let config = state.database.get_configuration().await.ok();
+    let genres = state.client.genres().await.unwrap_or_default();
+    let context = settings_context(devices, config, genres);
+    Ok(state.render("settings-content.html", &context))
+}
+
+async fn set_resume_playback(
+    State(state): State<Arc<AppState>>,
+    Form(form): Form<SetResumePlaybackForm>,
+) -> ResponseResult {
+    let enabled = form.resume_playback.is_some();
+    if let Err(e) = state.database.set_resume_playback(enabled).await {
+        tracing::error!("Failed to set resume playback: {}", e);
+        return ok_or_error_page(&state, Err(e));
+    }
+    let devices = list_audio_devices().unwrap_or_default();
+    let config = state.database.get_configuration().await.ok();
+    let genres = state.client.genres().await.unwrap_or_default();
+    let context = settings_context(devices, config, genres);
+    Ok(state.render("settings-content.html", &context))
+}
+
+async fn set_bit_perfect(
+    State(state): State<Arc<AppState>>,
+    Form(form): Form<SetBitPerfectForm>,
+) -> ResponseResult {
+    // The player stores the setting once it has applied it
+    let active = state
+        .controls
+        .set_bit_perfect(form.bit_perfect.is_some())
+        .await;
+    let devices = list_audio_devices().unwrap_or_default();
+    let config = state.database.get_configuration().await.ok();
+    let genres = state.client.genres().await.unwrap_or_default();
+    let mut ctx = settings_context(devices, config, genres);
+    if let Some(obj) = ctx.as_object_mut() {
+        obj.insert("bit_perfect_active".to_string(), json!(active));
+    }
+    Ok(state.render("settings-content.html", &ctx))
+}
+
+async fn set_export(
+    State(state): State<Arc<AppState>>,
+    Form(form): Form<SetExportForm>,
+) -> ResponseResult {
+    let export_directory = form.export_directory.filter(|s| !s.trim().is_empty());
+    let export_template = form.export_template.filter(|s| !s.trim().is_empty());
+    if let Err(e) = state
+        .database
+        .set_export_settings(export_directory, export_template)
+        .await
+    {
+        tracing::error!("Failed to set export settings: {}", e);
+        return ok_or_error_page(&state, Err(e));
+    }
+    state
+        .broadcast
+        .send(Notification::Info("Export settings saved.".to_string()));
+    let devices = list_audio_devices().unwrap_or_default();
+    let config = state.database.get_configuration().await.ok();
+    let genres = state.client.genres().await.unwrap_or_default();
+    let context = settings_context(devices, config, genres);
+    Ok(state.render("settings-content.html", &context))
+}
+
 async fn sign_out(State(state): State<Arc<AppState>>) -> Response {
     match state.database.refresh_database().await {
         Ok(_) => {
@@ -262,3 +588,228 @@ async fn sign_out(State(state): State<Arc<AppState>>) -> Response {
 async fn disconnected(State(state): State<Arc<AppState>>) -> ResponseResult {
     Ok(state.render("disconnected.html", &json!({})))
 }
+
+async fn render_plugins(state: &AppState) -> ResponseResult {
+    let config = state.database.get_configuration().await.ok();
+    let clap_plugin_path = config.and_then(|c| c.clap_plugin_path);
+    let available_plugins = clap_plugin_path
+        .as_deref()
+        .map(|path| scan_plugins(Path::new(path)))
+        .unwrap_or_default()
+        .into_iter()
+        .map(|plugin| {
+            json!({
+                "id": plugin.id,
+                "name": plugin.name,
+                "vendor": plugin.vendor,
+            })
+        })
+        .collect::<Vec<_>>();
+    let plugins = ok_or_error_page(state, state.database.get_clap_plugins(DEFAULT_CHAIN).await)?
+        .into_iter()
+        .map(|plugin| {
+            let params = plugin
+                .params
+                .iter()
+                .map(|param| {
+                    json!({
+                        "id": param.id,
+                        "name": param.name,
+                        "min": param.min,
+                        "max": param.max,
+                        "step": (param.max - param.min) / 100.0,
+                        "value": param.value,
+                        "value_display": format!("{:.2}", param.value),
+                    })
+                })
+                .collect::<Vec<_>>();
+            json!({
+                "position": plugin.position,
+                "name": plugin
+                    .path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                "plugin_id": plugin.plugin_id,
+                "params": params,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    Ok(state.render(
+        "settings-plugins.html",
+        &json!({
+            "clap_plugin_path": clap_plugin_path,
+            "has_available_plugins": !available_plugins.is_empty(),
+            "available_plugins": available_plugins,
+            "plugins": plugins,
+        }),
+    ))
+}
+
+async fn plugins(State(state): State<Arc<AppState>>) -> ResponseResult {
+    render_plugins(&state).await
+}
+
+async fn add_plugin(
+    State(state): State<Arc<AppState>>,
+    Form(form): Form<AddPluginForm>,
+) -> ResponseResult {
+    // Only plugins found in the directory configured from the command line
+    // can be loaded
+    let config = state.database.get_configuration().await.ok();
+    let Some(descriptor) = config
+        .and_then(|c| c.clap_plugin_path)
+        .zip(form.plugin)
+        .and_then(|(directory, plugin_id)| {
+            scan_plugins(Path::new(&directory))
+                .into_iter()
+                .find(|descriptor| descriptor.id == plugin_id)
+        })
+    else {
+        return render_plugins(&state).await;
+    };
+
+    let (path, plugin_id) = (descriptor.path.as_path(), descriptor.id.as_str());
+    let plugin = ok_or_error_page(&state, ClapPlugin::load(path, plugin_id))?;
+    let (plugin_state, params) = (plugin.save_state(), plugin.params());
+    drop(plugin);
+
+    if let Err(e) = state
+        .database
+        .add_clap_plugin(DEFAULT_CHAIN, path, plugin_id, plugin_state.as_deref(), &params)
+        .await
+    {
+        tracing::error!("Failed to add plugin: {}", e);
+        return ok_or_error_page(&state, Err(e));
+    }
+    state.controls.reload_clap_chain();
+    render_plugins(&state).await
+}
+
+async fn remove_plugin(
+    State(state): State<Arc<AppState>>,
+    Form(form): Form<RemovePluginForm>,
+) -> ResponseResult {
+    if let Err(e) = state
+        .database
+        .remove_clap_plugin(DEFAULT_CHAIN, form.position)
+        .await
+    {
+        tracing::error!("Failed to remove plugin: {}", e);
+        return ok_or_error_page(&state, Err(e));
+    }
+    state.controls.reload_clap_chain();
+    render_plugins(&state).await
+}
+
+async fn set_plugin_param(
+    State(state): State<Arc<AppState>>,
+    Form(form): Form<SetPluginParamForm>,
+) -> ResponseResult {
+    let Some(value) = form
+        .clap_param_value
+        .and_then(|s| s.parse::<f64>().ok())
+        .filter(|value| value.is_finite())
+    else {
+        return render_plugins(&state).await;
+    };
+    // The player saves the value once the plugin has it
+    if let Err(e) = state
+        .controls
+        .set_clap_parameter(form.position, form.param_id, value)
+        .await
+    {
+        tracing::error!("Failed to set plugin parameter: {}", e);
+        return ok_or_error_page(&state, Err(e));
+    }
+    render_plugins(&state).await
+}
+
+async fn render_schedules(state: &AppState) -> ResponseResult {
+    let schedules = ok_or_error_page(state, state.database.get_schedules().await)?
+        .into_iter()
+        .map(|schedule| {
+            json!({
+                "id": schedule.id,
+                "target": schedule.target.to_string(),
+                "time": schedule.time.to_string(),
+                "weekdays": schedule.weekday_names(),
+                "ramp_up": schedule
+                    .ramp_up
+                    .map(|ramp_up| format!("ramp up {} min", ramp_up.as_secs() / 60))
+                    .unwrap_or_default(),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    Ok(state.render(
+        "settings-schedules.html",
+        &json!({
+            "schedules": schedules,
+            "weekdays": WEEKDAY_NAMES,
+        }),
+    ))
+}
+
+async fn schedules(State(state): State<Arc<AppState>>) -> ResponseResult {
+    render_schedules(&state).await
+}
+
+async fn add_schedule(
+    State(state): State<Arc<AppState>>,
+    MultiValueForm(form): MultiValueForm<AddScheduleForm>,
+) -> ResponseResult {
+    let target = ScheduleTarget::from_parts(&form.target_kind, form.target_id.trim());
+    let time = form.time.parse::<TimeOfDay>();
+    let weekdays = match form.days.is_empty() {
+        true => Ok(EVERY_DAY),
+        false => parse_weekdays(&form.days.join(",")),
+    };
+
+    let (Some(target), Ok(time), Ok(weekdays)) = (target, time, weekdays) else {
+        state.broadcast.send(Notification::Warning(
+            "Enter an album or playlist id and a time".to_string(),
+        ));
+        return render_schedules(&state).await;
+    };
+
+    let ramp_up = match form.ramp_up_minutes.as_deref().map(str::trim) {
+        None | Some("") => None,
+        Some(minutes) => match minutes
+            .parse::<u64>()
+            .ok()
+            .filter(|minutes| *minutes <= MAX_RAMP_UP_MINUTES)
+        {
+            Some(0) => None,
+            Some(minutes) => Some(Duration::from_secs(minutes * 60)),
+            None => {
+                state.broadcast.send(Notification::Warning(format!(
+                    "Invalid ramp up minutes: {minutes}"
+                )));
+                return render_schedules(&state).await;
+            }
+        },
+    };
+
+    if let Err(e) = state
+        .database
+        .add_schedule(&target, time, weekdays, ramp_up)
+        .await
+    {
+        tracing::error!("Failed to add schedule: {}", e);
+        return ok_or_error_page(&state, Err(e));
+    }
+    render_schedules(&state).await
+}
+
+async fn remove_schedule(
+    State(state): State<Arc<AppState>>,
+    Form(form): Form<RemoveScheduleForm>,
+) -> ResponseResult {
+    if let Err(e) = state.database.remove_schedule(form.id).await {
+        tracing::error!("Failed to remove schedule: {}", e);
+        return ok_or_error_page(&state, Err(e));
+    }
+    render_schedules(&state).await
+}